@@ -0,0 +1,133 @@
+//! Release assets kept in `asset_cache` as `<owner>\<repo>\<tag>\<asset>`, e.g. on a network
+//! share used by several machines.
+//!
+//! The cache is never required, a missing or damaged file is downloaded again and failing to
+//! write to the cache is only a warning.
+
+use anyhow::Result;
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, SystemTime};
+
+use crate::warning;
+
+/// Path of an asset in the cache, characters Windows doesn't allow in names are replaced.
+fn entry(dir: &Path, repo: &str, tag: &str, name: &str) -> PathBuf {
+	let mut path = dir.to_path_buf();
+	for part in repo.to_lowercase().split('/') {
+		path.push(sanitize(part));
+	}
+	path.join(sanitize(tag)).join(sanitize(name))
+}
+
+fn sanitize(part: &str) -> String {
+	part.chars()
+		.map(|c| match c {
+			'<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+			c if c.is_control() => '_',
+			c => c,
+		})
+		.collect()
+}
+
+/// Copy the cached asset to `dest` if it has the `size` GitHub reports, 0 if unknown.
+///
+/// # Returns
+/// Whether the cached file was used.
+pub fn get(dir: &Path, repo: &str, tag: &str, name: &str, size: u64, dest: &Path) -> bool {
+	let path = entry(dir, repo, tag, name);
+	let Ok(meta) = fs::metadata(&path) else {
+		return false;
+	};
+	if size != 0 && meta.len() != size {
+		warning!("Cached {} has the wrong size, downloading it again", name);
+		_ = fs::remove_file(&path);
+		return false;
+	}
+	if fs::copy(&path, dest).is_err() {
+		return false;
+	}
+	// entries are pruned by age, so a used entry counts as new
+	_ = File::options()
+		.write(true)
+		.open(&path)
+		.and_then(|f| f.set_modified(SystemTime::now()));
+	true
+}
+
+/// Store a downloaded asset, failures are warnings.
+pub fn put(dir: &Path, repo: &str, tag: &str, name: &str, file: &Path) {
+	let path = entry(dir, repo, tag, name);
+	// copied under a temporary name first, so other machines never see a partial file
+	let tmp = path.with_extension(format!("{}.tmp", process::id()));
+	let result = path
+		.parent()
+		.map_or(Ok(()), fs::create_dir_all)
+		.and_then(|_| fs::copy(file, &tmp))
+		.and_then(|_| fs::rename(&tmp, &path));
+	if let Err(e) = result {
+		_ = fs::remove_file(&tmp);
+		warning!("Failed to cache {}: {}", name, e);
+	}
+}
+
+/// Remove a cached asset that turned out to be damaged.
+pub fn remove(dir: &Path, repo: &str, tag: &str, name: &str) {
+	_ = fs::remove_file(entry(dir, repo, tag, name));
+}
+
+/// Remove entries not used for `max_age`, then the least recently used ones until the cache
+/// is at most `max_size` bytes. Directories left empty are removed.
+///
+/// # Returns
+/// The number of removed files and their size.
+pub fn prune(dir: &Path, max_age: Option<Duration>, max_size: Option<u64>) -> Result<(usize, u64)> {
+	let mut files = Vec::new();
+	collect(dir, &mut files)?;
+	// newest first, so the budget keeps the most recently used entries
+	files.sort_by_key(|f| Reverse(f.1));
+	let now = SystemTime::now();
+	let (mut kept, mut count, mut freed) = (0, 0, 0);
+	for (path, modified, len) in files {
+		let old = max_age.is_some_and(|age| now.duration_since(modified).unwrap_or_default() > age);
+		let over = max_size.is_some_and(|size| kept + len > size);
+		if old || over {
+			fs::remove_file(&path)?;
+			count += 1;
+			freed += len;
+		} else {
+			kept += len;
+		}
+	}
+	remove_empty_dirs(dir);
+	Ok((count, freed))
+}
+
+fn collect(dir: &Path, files: &mut Vec<(PathBuf, SystemTime, u64)>) -> Result<()> {
+	for entry in fs::read_dir(dir)? {
+		let entry = entry?;
+		let meta = entry.metadata()?;
+		if meta.is_dir() {
+			collect(&entry.path(), files)?;
+		} else {
+			files.push((entry.path(), meta.modified()?, meta.len()));
+		}
+	}
+	Ok(())
+}
+
+fn remove_empty_dirs(dir: &Path) {
+	let Ok(entries) = fs::read_dir(dir) else {
+		return;
+	};
+	for entry in entries.flatten() {
+		let path = entry.path();
+		if path.is_dir() {
+			remove_empty_dirs(&path);
+			// fails if it's not empty
+			_ = fs::remove_dir(&path);
+		}
+	}
+}
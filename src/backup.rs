@@ -0,0 +1,132 @@
+//! Snapshot version.toml and the plugin directories into one zip archive.
+//!
+//! The archive mirrors the plugin directory, with version.toml at the root and every plugin in its
+//! directory.
+
+use anyhow::{bail, Result};
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::util::{format_size, long_path};
+use crate::{polling, print_message, Context};
+
+const CONFIG_ENTRY: &str = "version.toml";
+
+/// Write version.toml and the plugin directories `dirs` into `path`.
+/// Return the size of the archive.
+pub fn create(ctx: &Context, path: &Path, dirs: &[&str]) -> Result<u64> {
+	let mut zip = ZipWriter::new(File::create(path)?);
+	let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+	zip.start_file(CONFIG_ENTRY, options)?;
+	io::copy(&mut File::open(&ctx.config_path)?, &mut zip)?;
+
+	for dir in dirs {
+		let root = ctx.plugin_path.join(dir);
+		let mut size = 0;
+		let mut pending = vec![root.clone()];
+		while let Some(current) = pending.pop() {
+			for entry in fs::read_dir(long_path(&current))? {
+				let path = current.join(entry?.file_name());
+				let name = entry_name(path.strip_prefix(&ctx.plugin_path)?);
+				if path.is_dir() {
+					zip.add_directory(name, options)?;
+					pending.push(path);
+				} else {
+					zip.start_file(name, options)?;
+					size += io::copy(&mut File::open(long_path(&path))?, &mut zip)?;
+				}
+			}
+		}
+		print_message!("+", bright_green, "{} ({})", dir, format_size(size));
+	}
+
+	zip.finish()?;
+	Ok(fs::metadata(path)?.len())
+}
+
+/// Check that `path` is a backup that can be restored.
+///
+/// Fail if version.toml was changed after the archive was written, unless `force`.
+pub fn check(ctx: &Context, path: &Path, force: bool) -> Result<()> {
+	let mut archive = ZipArchive::new(File::open(path)?)?;
+	if archive.by_name(CONFIG_ENTRY).is_err() {
+		bail!(
+			"{} is not a ptr backup, version.toml not found",
+			path.display()
+		);
+	}
+	if !force && ctx.config_path.exists() {
+		let (config, backup) = (
+			fs::metadata(&ctx.config_path)?.modified()?,
+			fs::metadata(path)?.modified()?,
+		);
+		if config > backup {
+			bail!("version.toml is newer than the backup, use --force to restore anyway");
+		}
+	}
+	Ok(())
+}
+
+/// Replace the plugin directories and version.toml with the ones in `path`.
+/// Return the restored directories.
+pub fn restore(ctx: &Context, path: &Path) -> Result<Vec<String>> {
+	let mut archive = ZipArchive::new(File::open(path)?)?;
+
+	// group the entries by their plugin directory
+	let mut dirs: BTreeMap<String, Vec<(usize, PathBuf)>> = BTreeMap::new();
+	for i in 0..archive.len() {
+		let Some(name) = archive.by_index(i)?.enclosed_name() else {
+			continue;
+		};
+		let mut components = name.components();
+		let Some(Component::Normal(dir)) = components.next() else {
+			continue;
+		};
+		if components.as_path().as_os_str().is_empty() && dir == CONFIG_ENTRY {
+			continue;
+		}
+		dirs.entry(dir.to_string_lossy().into_owned())
+			.or_default()
+			.push((i, name));
+	}
+
+	for (dir, entries) in &dirs {
+		let root = ctx.plugin_path.join(dir);
+		if root.exists() {
+			polling::remove_dir_all(&root)?;
+		}
+		let mut size = 0;
+		for (i, name) in entries {
+			let mut file = archive.by_index(*i)?;
+			let out_path = long_path(&ctx.plugin_path.join(name));
+			if file.is_dir() {
+				fs::create_dir_all(out_path)?;
+				continue;
+			}
+			if let Some(p) = out_path.parent() {
+				fs::create_dir_all(p)?;
+			}
+			size += polling::copy(&mut file, &mut polling::create(out_path)?)?;
+		}
+		print_message!("+", bright_green, "{} ({})", dir, format_size(size));
+	}
+
+	let mut config = archive.by_name(CONFIG_ENTRY)?;
+	polling::copy(&mut config, &mut polling::create(&ctx.config_path)?)?;
+	Ok(dirs.into_keys().collect())
+}
+
+/// Name of the entry in the archive, always separated by `/`.
+fn entry_name(relative: &Path) -> String {
+	relative
+		.components()
+		.map(|c| c.as_os_str().to_string_lossy())
+		.collect::<Vec<_>>()
+		.join("/")
+}
@@ -0,0 +1,57 @@
+//! Latest release tags of repositories, so `list --latest` doesn't query every repository.
+//!
+//! The cache is never required, it's missing until an update or `list --latest --refresh`.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct LatestCache {
+	#[serde(default)]
+	repos: BTreeMap<String, Latest>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Latest {
+	pub tag: String,
+	/// When the release was published, missing in caches written before it was recorded.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub released: Option<String>,
+	/// Unix time the tag was fetched.
+	pub fetched_at: u64,
+}
+
+impl LatestCache {
+	fn path(dir: &Path) -> PathBuf {
+		dir.join("latest.toml")
+	}
+
+	/// Load the cache, None if it doesn't exist or can't be read.
+	pub fn load(dir: &Path) -> Option<Self> {
+		toml::from_str(&fs::read_to_string(Self::path(dir)).ok()?).ok()
+	}
+
+	pub fn save(&self, dir: &Path) -> Result<()> {
+		fs::create_dir_all(dir)?;
+		fs::write(Self::path(dir), toml::to_string(self)?)?;
+		Ok(())
+	}
+
+	pub fn get(&self, repo: &str) -> Option<&Latest> {
+		self.repos.get(&repo.to_lowercase())
+	}
+
+	pub fn set(&mut self, repo: &str, tag: &str, released: Option<&str>, fetched_at: u64) {
+		self.repos.insert(
+			repo.to_lowercase(),
+			Latest {
+				tag: tag.to_string(),
+				released: released.map(String::from),
+				fetched_at,
+			},
+		);
+	}
+}
@@ -1,33 +1,374 @@
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use colored::Colorize;
 use core::fmt;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize, Serializer};
-use std::collections::HashSet;
-use std::collections::{hash_map::Entry, BTreeMap, HashMap};
-use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs, mem};
 use tabwriter::TabWriter;
 
-use crate::polling;
-use crate::util::{get_powertoys_path, kill_ptr, start_ptr};
-use crate::{add, error, exit, gh_dl, remove, up_to_date, CONFIG_PATH, PLUGIN_PATH};
+use crate::cache::LatestCache;
+use crate::i18n::Msg;
+use crate::managed::{Managed, ManagedPlugin};
+use crate::notification::Summary;
+use crate::report::{Action, Report};
+use crate::reporter::{self, Event};
+use crate::safe_mode::{self, SafeMode};
+use crate::state::State;
+use crate::util::{
+	check_powertoys_updating, closest, compare_versions, compile_pattern, confirm,
+	download_release, expand_pattern, format_size, free_space, get_powertoys_path,
+	get_powertoys_version, get_releases, get_user, glob_match, hyperlink, is_powertoys_path,
+	is_ptr_running, is_writable, kill_needs_elevation, kill_ptr, long_path, mask_token,
+	parse_github_repo, parse_repo, pe_arch, plugin_dotnet_version, powertoys_dotnet_version,
+	print_assets, prompt, read_plugin_metadata, released_ago, remove_dir_elevated, render_markdown,
+	replace_dir_elevated, repo_url, run_hook, same_dir_name, search_plugin_repo, select_release,
+	start_ptr, validate_plugin_dir, wait_ptr, AssetFilter, Downloaded, ExtractLimits, GitHub,
+	PluginMetadata, RateLimit, ReleaseSource, RepoState, RunSettings,
+};
+use crate::{add, error, info, print_message, progress, remove, t, up_to_date, warning, Context};
+use crate::{asset_cache, backup, credential, i18n, polling};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
+	/// Layout of the file, see `SCHEMA`.
+	#[serde(default)]
+	schema: u32,
+	/// Minimum ptr version that understands this config, e.g. `>=0.10`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	ptr_version: Option<String>,
+	/// Language of messages, e.g. `de`, detected if not set.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	lang: Option<String>,
+	/// Fail on unknown keys instead of warning about them.
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	strict_config: bool,
 	arch: Arch,
+	/// Arch used instead of `arch` for this run, set by `--arch`.
+	#[serde(skip)]
+	arch_override: Option<Arch>,
+	/// Install plugins whose DLL is built for another arch, set by `--skip-arch-check`.
+	#[serde(skip)]
+	skip_arch_check: bool,
 	pt_path: PathBuf,
-	admin: bool,
-	pin: Option<HashSet<String>>,
+	/// Directory PowerToys Run loads plugins from, only read in profiles, see `ptr profile`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	plugin_path: Option<PathBuf>,
+	/// Kill PowerToys as administrator.
+	admin: Admin,
+	/// GitHub token for API requests.
+	token: Option<Token>,
+	/// Tokens of GitHub Enterprise Server hosts, e.g. `tokens."ghe.corp.com" = "..."`.
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	tokens: BTreeMap<String, Token>,
+	/// Token used instead of all others for this run, set by `--token`.
+	#[serde(skip)]
+	token_override: Option<Token>,
+	/// Refuse to install plugins requiring a newer PowerToys.
+	#[serde(default)]
+	strict_compat: bool,
+	/// Restart all of PowerToys or only PowerToys Run.
+	#[serde(default)]
+	restart_scope: RestartScope,
+	/// Show a toast notification after updates.
+	#[serde(default)]
+	notify: bool,
+	/// Unix time of the day of the last update, so updating again on the same day doesn't
+	/// change the file.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	last_checked: Option<u64>,
+	/// Days since the last update before a hint is shown, 0 to disable.
+	#[serde(default = "default_check_interval_days")]
+	check_interval_days: u64,
+	/// Seconds to keep retrying locked files.
+	#[serde(default = "default_retry_timeout")]
+	retry_timeout: u64,
+	/// Leave PowerToys stopped after changing plugins.
+	#[serde(default)]
+	no_restart: bool,
+	/// Don't kill or restart PowerToys, locked files are retried until `retry_timeout`.
+	#[serde(default)]
+	no_kill: bool,
+	/// Don't check the connection to GitHub before changing plugins, for proxies blocking HEAD.
+	#[serde(default)]
+	skip_network_check: bool,
+	/// Keep the `repo` of plugins when GitHub reports the repository was renamed.
+	#[serde(default)]
+	no_follow_renames: bool,
+	/// Remove the mark of the web from extracted files so PowerToys can load them.
+	#[serde(default = "default_unblock")]
+	unblock: bool,
+	/// Whether a failed plugin hook is a warning or aborts the change.
+	#[serde(default)]
+	hook_failure: HookFailure,
+	/// Experimental, update plugins while PowerToys runs by renaming the loaded directory
+	/// aside, see `Plugin::swap_with`.
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	swap_in_place: bool,
+	/// Other directories PowerToys loads plugins from, the first one is used by
+	/// `add --scope machine`. Defaults to `RunPlugins` of PowerToys.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	plugin_paths: Vec<PathBuf>,
+	/// Directory release assets are kept in and reused from, e.g. on a network share.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	asset_cache: Option<PathBuf>,
+	/// MB an archive may extract to, `ExtractLimits::DEFAULT_MAX_SIZE_MB` if not set.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	max_extract_size: Option<u64>,
+	/// `max_extract_size` for this run, set by `--max-extract-size`.
+	#[serde(skip)]
+	max_extract_size_override: Option<u64>,
+	/// `no_kill` for this run, set by `--no-kill`.
+	#[serde(skip)]
+	no_kill_override: bool,
+	/// Skip the hooks of plugins, set by `--no-hooks`.
+	#[serde(skip)]
+	skip_hooks: bool,
+	/// Don't check the free disk space before changing plugins, set by `--skip-space-check`.
+	#[serde(skip)]
+	skip_space_check: bool,
+	/// Kill PowerToys even while it's updating itself, set by `--force`.
+	#[serde(skip)]
+	ignore_updating: bool,
+	pin: Option<BTreeSet<String>>,
+	/// Report of this run, written if `--report` is given.
+	#[serde(skip)]
+	report: Report,
+	/// What happened to each plugin in this run, see `push_outcome`.
+	#[serde(skip)]
+	outcomes: Vec<(String, SyncOutcome)>,
+	/// Paths of this run, see `Context`.
+	#[serde(skip)]
+	ctx: Context,
 	#[serde(serialize_with = "sort_keys")]
 	plugins: HashMap<String, Plugin>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// A GitHub token, masked in debug output.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(transparent)]
+struct Token(String);
+
+impl fmt::Debug for Token {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&mask_token(&self.0))
+	}
+}
+
+/// Fields kept from an existing config when it's imported, other fields are reset.
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct ImportConfig {
+	ptr_version: Option<String>,
+	lang: Option<String>,
+	pt_path: Option<PathBuf>,
+	plugin_path: Option<PathBuf>,
+	arch: Option<Arch>,
+	admin: Option<Admin>,
+	token: Option<Token>,
+	tokens: Option<BTreeMap<String, Token>>,
+	no_restart: Option<bool>,
+	plugin_paths: Option<Vec<PathBuf>>,
+	asset_cache: Option<PathBuf>,
+	pin: Option<BTreeSet<String>>,
 	plugins: HashMap<String, Plugin>,
 }
 
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Requests a plugin takes at most, the releases and the repository if that fails.
+const REQUESTS_PER_PLUGIN: u64 = 2;
+
+fn default_check_interval_days() -> u64 {
+	14
+}
+
+fn default_retry_timeout() -> u64 {
+	10
+}
+
+fn default_unblock() -> bool {
+	true
+}
+
+/// Current Unix time in seconds.
+fn now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |d| d.as_secs())
+}
+
+/// Layout of version.toml written by this ptr, raised with a step in `MIGRATIONS` whenever
+/// existing files need to change.
+const SCHEMA: u32 = 1;
+
+/// Steps upgrading a document from the schema at its index to the next one.
+/// Keys a step doesn't know are kept.
+const MIGRATIONS: [fn(&mut toml::Table); SCHEMA as usize] = [migrate_v0];
+
+/// Files before `schema`, written without `admin` before 0.9.
+fn migrate_v0(table: &mut toml::Table) {
+	table.entry("admin").or_insert(true.into());
+}
+
+/// Upgrade `table` to `SCHEMA` step by step.
+/// Return whether it was changed, documents of a newer schema are left as is.
+fn migrate(table: &mut toml::Table) -> bool {
+	let schema = table
+		.get("schema")
+		.and_then(|s| s.as_integer())
+		.unwrap_or(0)
+		.max(0) as usize;
+	if schema >= MIGRATIONS.len() {
+		return false;
+	}
+	for step in &MIGRATIONS[schema..] {
+		step(table);
+	}
+	table.insert("schema".to_string(), i64::from(SCHEMA).into());
+	true
+}
+
+/// Read and parse the config at `path`, with the location of a parse error and how to recover.
+/// Older schemas are migrated first.
+fn read_config<T: DeserializeOwned>(path: &Path) -> Result<T> {
+	let original = fs::read_to_string(path)
+		.map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+	let mut content = original.clone();
+	let mut strict = false;
+	let mut versioned = false;
+	let mut hints = vec![];
+	if let Ok(mut table) = toml::from_str::<toml::Table>(&content) {
+		// checked before parsing, so newer keys aren't reported as errors instead
+		if let Some(required) = table.get("ptr_version").and_then(|v| v.as_str()) {
+			check_ptr_version(required, path)?;
+			versioned = true;
+		}
+		if let Some(lang) = table.get("lang").and_then(|v| v.as_str()) {
+			i18n::set_lang(lang);
+		}
+		strict = table.get("strict_config").and_then(|v| v.as_bool()) == Some(true);
+		hints = config_hints(&table);
+		if migrate(&mut table) {
+			content = toml::to_string(&table)?;
+		}
+	}
+	let config = toml::from_str(&content).map_err(|e| {
+		// the migrated text has other lines, the file's own error points to the right one
+		let e = if content == original {
+			e
+		} else {
+			toml::from_str::<T>(&original).err().unwrap_or(e)
+		};
+		let backup = path.with_extension("toml.bak");
+		let hint = if backup.exists() {
+			format!("fix the file or restore {}", backup.display())
+		} else {
+			"fix the file, or restore it with `ptr restore` if you have a backup".to_string()
+		};
+		hints.push(hint);
+		anyhow!(
+			"Failed to parse {}\n{}\n{}",
+			path.display(),
+			e.to_string().trim_end(),
+			hints.join("\n").bright_yellow()
+		)
+	})?;
+
+	let unknown = unknown_keys(&content);
+	if !unknown.is_empty() {
+		// keys of a newer ptr whose ptr_version wasn't raised, dropping them on save loses them
+		if versioned {
+			bail!(
+				"Unknown keys in {}: {}, they may need a newer ptr than its ptr_version, raise \
+				 ptr_version or update with `ptr self-update`",
+				path.display(),
+				unknown.join(", ")
+			);
+		}
+		if strict {
+			bail!(
+				"Unknown keys in {}: {}, fix them or set strict_config = false",
+				path.display(),
+				unknown.join(", ")
+			);
+		}
+		warning!(
+			"Unknown keys in {} are ignored and dropped when ptr saves it: {}",
+			path.display(),
+			unknown.join(", ")
+		);
+	}
+	Ok(config)
+}
+
+/// Keys serde would drop silently, e.g. a typo like `plugins.Foo.patern`, as dotted paths.
+/// Checked against the full config, since `ImportConfig` only reads some of the keys.
+fn unknown_keys(content: &str) -> Vec<String> {
+	let mut keys = vec![];
+	// keys seen before an error are still reported, the error itself is reported elsewhere
+	_ = serde_ignored::deserialize::<_, _, Config>(toml::Deserializer::new(content), |path| {
+		keys.push(path.to_string())
+	});
+	keys
+}
+
+/// Fixes for common mistakes in a hand-edited config, which fail with a type error.
+fn config_hints(table: &toml::Table) -> Vec<String> {
+	let mut hints = vec![];
+	if let Some(pin) = table.get("pin").and_then(|v| v.as_str()) {
+		hints.push(format!(
+			"pin is a list of plugin names, e.g. pin = [{pin:?}]"
+		));
+	}
+	let plugins = table.get("plugins").and_then(|v| v.as_table());
+	if plugins.is_some_and(|p| p.get("token").is_some_and(|t| t.is_str())) {
+		hints.push(
+			"token in [plugins] is read as a plugin, move it above [plugins] for all plugins or \
+			 into [plugins.<NAME>] for one"
+				.to_string(),
+		);
+	}
+	hints
+}
+
+/// Fail if this ptr is older than `required`, `>=0.10` or `0.10`, set in the file at `path`.
+fn check_ptr_version(required: &str, path: &Path) -> Result<()> {
+	let minimum = required.trim();
+	let minimum = minimum.strip_prefix(">=").unwrap_or(minimum).trim();
+	if minimum.starts_with(['<', '>', '=', '~', '^']) {
+		bail!(
+			"Unsupported ptr_version {} in {}, use `>=<VERSION>`",
+			required,
+			path.display()
+		);
+	}
+	let current = env!("CARGO_PKG_VERSION");
+	if compare_versions(current, minimum).is_lt() {
+		bail!(
+			"{} requires ptr {}, this is v{}, update with `ptr self-update`",
+			path.display(),
+			required,
+			current
+		);
+	}
+	Ok(())
+}
+
+/// `ptr_version` stamped into files written by `export`, the minor version of this ptr.
+fn export_ptr_version() -> String {
+	let current = env!("CARGO_PKG_VERSION");
+	let minor = current.splitn(3, '.').take(2).collect::<Vec<_>>().join(".");
+	format!(">={minor}")
+}
+
+/// A plugin ID in several directories, with whether ptr manages each directory.
+type Duplicate = (String, Vec<(PathBuf, bool)>);
+
 fn sort_keys<T, S>(value: &HashMap<String, T>, serializer: S) -> Result<S::Ok, S::Error>
 where
 	T: Serialize,
@@ -40,294 +381,4591 @@ where
 }
 
 impl Config {
-	pub fn new() -> Result<Self> {
-		if CONFIG_PATH.exists() {
-			Ok(toml::from_str(&fs::read_to_string(&*CONFIG_PATH).unwrap())?)
+	pub fn new(ctx: Context) -> Result<Self> {
+		if ctx.config_path.exists() {
+			let mut config: Self = read_config(&ctx.config_path)?;
+			check_dir_names(&config.plugins)?;
+			config.ctx = ctx;
+			config.heal_pt_path()?;
+			polling::set_timeout(Duration::from_secs(config.retry_timeout));
+			Ok(config)
 		} else {
-			let pt_path = get_powertoys_path()?;
-			Ok(Self {
-				arch: Arch::default(),
-				pt_path,
-				admin: true,
-				pin: None,
-				plugins: HashMap::new(),
-			})
+			Self::from_import(ctx, ImportConfig::default())
 		}
 	}
 
 	/// Ignore configs unrelated to plugins.
-	pub fn import() -> Result<Self> {
-		let pt_path = get_powertoys_path()?;
-		let import_config: ImportConfig =
-			toml::from_str(&fs::read_to_string(&*CONFIG_PATH).unwrap())?;
-		Ok(Self {
-			arch: Arch::default(),
-			pt_path,
-			admin: true,
-			pin: None,
-			plugins: import_config.plugins,
-		})
-	}
-
-	/// Note: This method already used in the other methods.
-	pub fn save(&self) -> Result<()> {
-		fs::write(&*CONFIG_PATH, toml::to_string(self)?)?;
-		Ok(())
+	pub fn import(ctx: Context) -> Result<Self> {
+		let import_config = read_config(&ctx.config_path)?;
+		Self::from_import(ctx, import_config)
 	}
 
-	pub fn restart(&self) {
-		kill_ptr(self.admin).unwrap_or_else(|e| exit!("Failed to kill PowerToys: {}", e));
-		start_ptr(&self.pt_path).unwrap_or_else(|e| exit!("Failed to start PowerToys: {}", e));
+	/// The config with the plugins in `path` merged, see `merge_file`.
+	pub fn import_file(ctx: Context, path: &Path, pins: bool) -> Result<Self> {
+		let mut config = Self::new(ctx)?;
+		config.merge_file(path, pins)?;
+		Ok(config)
 	}
 
-	pub fn import_plugins(&mut self) {
-		let mut new_plugins: HashMap<String, Plugin> = HashMap::new();
-		kill_ptr(self.admin).unwrap_or_else(|e| exit!("Failed to kill PowerToys: {}", e));
-		for (name, plugin) in &self.plugins {
-			match Plugin::add(name, plugin.repo.clone(), None, &self.arch) {
-				Ok(plugin) => {
-					add!(name, &plugin.version);
-					new_plugins.insert(name.clone(), plugin);
-				}
-				Err(e) => exit!("Failed to import {}: {}", name, e),
+	/// Merge the plugins in `path`, written by `export`, into the config, and its pins unless
+	/// `!pins`. Plugins already in the config are kept as they are. Hooks are dropped, the file
+	/// may come from anyone and nothing should run from it unasked.
+	fn merge_file(&mut self, path: &Path, pins: bool) -> Result<()> {
+		let content = fs::read_to_string(path)
+			.map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+		if let Some(required) = toml::from_str::<toml::Table>(&content)
+			.ok()
+			.as_ref()
+			.and_then(|t| t.get("ptr_version"))
+			.and_then(|v| v.as_str())
+		{
+			check_ptr_version(required, path)?;
+		}
+		let manifest: ImportConfig = toml::from_str(&content)
+			.map_err(|e| anyhow!("Failed to parse {}\n{}", path.display(), e))?;
+		check_dir_names(&manifest.plugins)?;
+		for (name, mut plugin) in manifest.plugins {
+			if self.plugins.keys().any(|k| k.eq_ignore_ascii_case(&name)) {
+				continue;
 			}
+			if plugin.post_install.take().is_some() | plugin.pre_remove.take().is_some() {
+				warning!(
+					"Hooks of {} in {} are ignored, add them to version.toml to run them",
+					name,
+					path.display()
+				);
+			}
+			self.plugins.insert(name, plugin);
 		}
-		start_ptr(&self.pt_path).unwrap_or_else(|e| error!("Failed to start PowerToys: {}", e));
-		self.plugins = new_plugins;
-		self.save()
-			.unwrap_or_else(|e| exit!("Failed to save config: {}", e));
+		if let Some(pin) = manifest.pin.filter(|_| pins) {
+			self.pin.get_or_insert_with(BTreeSet::new).extend(pin);
+		}
+		Ok(())
 	}
 
-	pub fn add(&mut self, name: &str, repo: String, version: Option<String>) -> Result<()> {
-		if let Entry::Vacant(e) = self.plugins.entry(name.to_string()) {
-			kill_ptr(self.admin).unwrap_or_else(|e| exit!("Failed to kill PowerToys: {}", e));
-			let version = &e
-				.insert(Plugin::add(name, repo, version, &self.arch)?)
-				.version;
-			add!(name, version);
-			start_ptr(&self.pt_path).unwrap_or_else(|e| error!("Failed to start PowerToys: {}", e));
-			self.save()?;
-			Ok(())
+	/// Build the config from plugins installed in the plugin directory and the other ones,
+	/// using the GitHub repository in plugin.json.
+	///
+	/// Plugins are merged into the existing config, keeping its entries and other fields,
+	/// unless `overwrite`.
+	pub fn init(ctx: Context, overwrite: bool) -> Result<Self> {
+		let mut config = if !ctx.config_path.exists() {
+			Self::from_import(ctx, ImportConfig::default())?
+		} else if overwrite {
+			if !confirm(&t!(OverwriteConfig), false, ctx.assume_yes)? {
+				bail!("Cancelled");
+			}
+			let import_config = fs::read_to_string(&ctx.config_path)
+				.ok()
+				.and_then(|s| toml::from_str::<ImportConfig>(&s).ok())
+				.unwrap_or_default();
+			Self::from_import(
+				ctx,
+				ImportConfig {
+					plugins: HashMap::new(),
+					..import_config
+				},
+			)?
 		} else {
-			bail!("Plugin already exists")
+			Self::new(ctx)?
+		};
+
+		let dirs = config.installed_dirs()?;
+		if dirs.is_empty() && !config.ctx.assume_yes {
+			config.onboard()?;
+		}
+		let mut skipped = vec![];
+		for (root, dir) in dirs {
+			if let Some((name, plugin)) = config.plugins.iter().find(|(n, p)| {
+				n.eq_ignore_ascii_case(&dir) || p.dir_name(n).eq_ignore_ascii_case(&dir)
+			}) {
+				up_to_date!(name, plugin.version);
+				continue;
+			}
+			let path = root
+				.as_deref()
+				.unwrap_or(&config.ctx.plugin_path)
+				.join(&dir);
+			let metadata = match read_plugin_metadata(&path) {
+				Ok(metadata) => metadata,
+				Err(e) => {
+					error!(e);
+					continue;
+				}
+			};
+			match config.find_repo(&dir, &metadata) {
+				Some(repo) => {
+					let version = metadata.version.unwrap_or_default();
+					add!(dir, version);
+					if let Some(root) = &root {
+						info!("  {} {}", "root:".dimmed(), root.display());
+					}
+					config.plugins.insert(
+						dir,
+						Plugin {
+							repo,
+							version,
+							released: None,
+							dir: None,
+							pattern: None,
+							asset: None,
+							hold: vec![],
+							token: None,
+							post_install: None,
+							pre_remove: None,
+							root,
+							ignore_errors: false,
+						},
+					);
+				}
+				None => skipped.push(dir),
+			}
 		}
+		if !skipped.is_empty() {
+			info!("{}", t!(ReposNotFound).bright_yellow());
+			skipped.iter().for_each(|d| info!("  {d}"));
+		}
+		config.save()?;
+		Ok(config)
 	}
 
-	pub fn update(&mut self, names: Vec<String>, versions: Option<Vec<String>>) {
-		kill_ptr(self.admin).unwrap_or_else(|e| exit!("Failed to kill PowerToys: {}", e));
+	/// Walk through the settings and first plugins on a machine without plugins.
+	/// Every step is skipped with an empty answer.
+	fn onboard(&mut self) -> Result<()> {
+		info!("{}", t!(OnboardIntro).bright_green());
 
-		// Update plugins with versions first.
-		let without_versions = if let Some(versions) = versions {
-			let (with_versions, without_versions) = names
-				.split_at_checked(versions.len())
-				.unwrap_or((&names, &[]));
-			for (name, version) in with_versions.iter().zip(versions) {
-				if let Some(plugin) = self.plugins.get_mut(name) {
-					match plugin.update_to(name, &self.arch, &version) {
-						Ok(updated) => {
-							if updated {
-								add!(name, plugin.version)
-							} else {
-								up_to_date!(name, plugin.version)
-							}
-						}
-						Err(e) => error!("Failed to update {}: {}", name, e),
-					}
+		if !confirm(
+			&t!(PowerToysFoundAt, self.pt_path.display()),
+			true,
+			self.ctx.assume_yes,
+		)? {
+			let input = prompt(&t!(EnterPowerToysDir), self.ctx.assume_yes)?;
+			if !input.is_empty() {
+				let path = PathBuf::from(&input);
+				if is_powertoys_path(&path) {
+					self.pt_path = path;
+				} else {
+					warning!("PowerToys not found at {}, keeping the detected one", input);
 				}
 			}
-			without_versions
-		} else {
-			&names
-		};
-		for name in without_versions {
-			if let Some(plugin) = self.plugins.get_mut(name) {
-				match plugin.update(name, &self.arch) {
-					Ok(updated) => {
-						if updated {
-							add!(name, plugin.version)
-						} else {
-							up_to_date!(name, plugin.version)
-						}
-					}
-					Err(e) => error!("Failed to update {}: {}", name, e),
+		}
+
+		if !confirm(&t!(InstallForArch, self.arch), true, self.ctx.assume_yes)? {
+			let input = prompt(&t!(EnterArch), self.ctx.assume_yes)?;
+			if !input.is_empty() {
+				match input.parse() {
+					Ok(arch) => self.arch = arch,
+					Err(e) => warning!("{}, keeping {}", e, self.arch),
 				}
 			}
 		}
-		start_ptr(&self.pt_path).unwrap_or_else(|e| error!("Failed to start PowerToys: {}", e));
-		self.save()
-			.unwrap_or_else(|e| exit!("Failed to save config: {}", e));
-	}
 
-	pub fn update_all(&mut self) {
-		kill_ptr(self.admin).unwrap_or_else(|e| exit!("Failed to kill PowerToys: {}", e));
-		for (name, plugin) in &mut self.plugins {
-			if let Some(pins) = &self.pin {
-				if pins.contains(name) {
-					continue;
-				}
+		if self.token().is_none() {
+			info!("{}", t!(TokenHint));
+			let token = prompt(&t!(EnterToken), self.ctx.assume_yes)?;
+			if !token.is_empty() {
+				credential::write_token(&token)?;
+				info!("{}", t!(TokenSaved));
 			}
-			match plugin.update(name, &self.arch) {
-				Ok(updated) => {
-					if updated {
-						add!(name, plugin.version)
-					} else {
-						up_to_date!(name, plugin.version)
-					}
+		}
+
+		self.save()?;
+		loop {
+			let input = prompt(&t!(AddPlugin), self.ctx.assume_yes)?;
+			let Some((name, repo)) = input.split_once(char::is_whitespace) else {
+				if !input.is_empty() {
+					warning!("Expected a name and a repository");
+					continue;
 				}
-				Err(e) => error!("Failed to update {}: {}", name, e),
+				break;
+			};
+			let repo = parse_github_repo(repo.trim()).unwrap_or(repo.trim().to_string());
+			if let Err(e) = self.add(name, repo, None, None, None, Scope::User, false) {
+				error!(t!(FailedToAdd, name, e));
 			}
 		}
-		start_ptr(&self.pt_path).unwrap_or_else(|e| error!("Failed to start PowerToys: {}", e));
-		self.save()
-			.unwrap_or_else(|e| exit!("Failed to save config: {}", e));
+		Ok(())
 	}
 
-	pub fn remove(&mut self, names: Vec<String>) {
-		kill_ptr(self.admin).unwrap_or_else(|e| exit!("Failed to kill PowerToys: {}", e));
-		for name in names {
-			if let Some(plugin) = self.plugins.get(&name) {
-				match plugin.remove(&name) {
-					Ok(_) => {
-						self.plugins.remove(&name);
-						remove!(name);
+	/// Find the GitHub repository of the plugin from URLs in plugin.json, GitHub code search if
+	/// token is set, and finally asking the user.
+	fn find_repo(&self, dir: &str, metadata: &PluginMetadata) -> Option<String> {
+		if let Some(repo) = [
+			&metadata.website,
+			&metadata.url_source_code,
+			&metadata.url_download,
+		]
+		.into_iter()
+		.flatten()
+		.find_map(|url| parse_github_repo(url))
+		{
+			return Some(repo);
+		}
+
+		if self.token().is_some() {
+			match search_plugin_repo(&self.github(), &metadata.name) {
+				Ok(Some(repo)) => {
+					if confirm(&t!(UseFoundRepo, repo, dir), true, self.ctx.assume_yes)
+						.unwrap_or(false)
+					{
+						return Some(repo);
 					}
-					Err(e) => error!("Failed to remove {}: {}", name, e),
 				}
+				Ok(None) => {}
+				Err(e) => error!(e),
 			}
 		}
-		start_ptr(&self.pt_path).unwrap_or_else(|e| error!("Failed to start PowerToys: {}", e));
-		self.save()
-			.unwrap_or_else(|e| exit!("Failed to save config: {}", e));
-	}
 
-	pub fn pin_add(&mut self, names: Vec<String>) {
-		if let Some(pins) = self.pin.as_mut() {
-			names.into_iter().for_each(|n| {
-				pins.insert(n);
-			});
+		let input = prompt(&t!(EnterRepo, dir), self.ctx.assume_yes).ok()?;
+		if input.is_empty() {
+			None
 		} else {
-			self.pin = Some(HashSet::from_iter(names));
+			Some(parse_github_repo(&input).unwrap_or(input))
 		}
-		self.save()
-			.unwrap_or_else(|e| exit!("Failed to save config: {}", e));
 	}
 
-	pub fn pin_remove(&mut self, names: Vec<String>) {
-		if let Some(pins) = self.pin.as_mut() {
-			names.iter().for_each(|n| {
-				pins.remove(n);
-			});
-		} else {
-			self.pin = Some(HashSet::from_iter(names));
+	/// Detect PowerToys again if `pt_path` no longer exists, e.g. it moved to another install
+	/// location after an update.
+	fn heal_pt_path(&mut self) -> Result<()> {
+		if is_powertoys_path(&self.pt_path) {
+			return Ok(());
 		}
-		self.save()
-			.unwrap_or_else(|e| exit!("Failed to save config: {}", e));
+		warning!("PowerToys not found at {}", self.pt_path.display());
+		self.pt_path = get_powertoys_path(self.ctx.assume_yes)?;
+		self.save()?;
+		info!("pt_path updated to {}", self.pt_path.display());
+		Ok(())
 	}
 
-	pub fn pin_list(&self) {
-		if let Some(pins) = self.pin.as_ref() {
-			pins.iter().for_each(|n| println!("{n}"));
+	/// Default config with the fields in `import_config`.
+	/// `pt_path` is detected if it's not given or doesn't exist, and `arch` if it's not given.
+	fn from_import(ctx: Context, import_config: ImportConfig) -> Result<Self> {
+		check_dir_names(&import_config.plugins)?;
+		let pt_path = match import_config.pt_path.filter(|p| is_powertoys_path(p)) {
+			Some(pt_path) => pt_path,
+			None => get_powertoys_path(ctx.assume_yes)?,
+		};
+		// PowerToys Run creates it on first launch, which may not have happened yet
+		let plugin_path = &ctx.plugin_path;
+		if !plugin_path.exists() {
+			fs::create_dir_all(plugin_path)
+				.map_err(|e| anyhow!("Failed to create {}: {}", plugin_path.display(), e))?;
+			info!("{} {}", "created".dimmed(), plugin_path.display());
 		}
+		let arch = match import_config.arch {
+			Some(arch) => arch,
+			None => Arch::detect()?,
+		};
+		Ok(Self {
+			schema: SCHEMA,
+			ptr_version: import_config.ptr_version,
+			lang: import_config.lang,
+			strict_config: false,
+			arch,
+			arch_override: None,
+			skip_arch_check: false,
+			pt_path,
+			plugin_path: import_config.plugin_path,
+			admin: import_config.admin.unwrap_or_default(),
+			token: import_config.token,
+			tokens: import_config.tokens.unwrap_or_default(),
+			token_override: None,
+			strict_compat: false,
+			restart_scope: RestartScope::default(),
+			notify: false,
+			last_checked: None,
+			check_interval_days: default_check_interval_days(),
+			retry_timeout: default_retry_timeout(),
+			no_restart: import_config.no_restart.unwrap_or_default(),
+			no_kill: false,
+			skip_network_check: false,
+			no_follow_renames: false,
+			unblock: default_unblock(),
+			swap_in_place: false,
+			max_extract_size: None,
+			max_extract_size_override: None,
+			no_kill_override: false,
+			skip_hooks: false,
+			skip_space_check: false,
+			ignore_updating: false,
+			hook_failure: HookFailure::default(),
+			plugin_paths: import_config.plugin_paths.unwrap_or_default(),
+			asset_cache: import_config.asset_cache,
+			pin: import_config.pin,
+			report: Report::default(),
+			outcomes: Vec::new(),
+			ctx,
+			plugins: import_config.plugins,
+		})
 	}
 
-	pub fn pin_reset(&mut self) {
-		self.pin = None;
-		self.save()
-			.unwrap_or_else(|e| exit!("Failed to save config: {}", e));
+	/// Use `token` for all requests instead of the configured ones, without saving it.
+	pub fn override_token(&mut self, token: String) {
+		self.token_override = Some(Token(token));
 	}
-}
 
-impl fmt::Display for Config {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		let mut tw = TabWriter::new(vec![]);
-		writeln!(&mut tw, "{}", "Plugins:".bright_green()).unwrap();
-		let btree_map: BTreeMap<_, _> = self.plugins.iter().collect();
-		for (name, plugin) in &btree_map {
-			writeln!(
-				&mut tw,
-				"  {}\t{}\t{}",
-				name.bright_cyan(),
-				plugin.repo,
-				plugin.version
-			)
-			.unwrap();
+	/// Write a JSON report of the changed plugins to `path` at the end of the run.
+	pub fn report_to(&mut self, path: PathBuf) {
+		self.report.write_to(path);
+	}
+
+	fn write_report(&mut self) {
+		let plugins = &self.plugins;
+		self.report
+			.fill_released(|name| plugins.get(name).and_then(|p| p.released.clone()));
+		if let Err(e) = self.report.write() {
+			error!("Failed to write report: {}", e);
 		}
-		tw.flush().unwrap();
-		write!(
-			f,
-			"{}",
-			String::from_utf8(tw.into_inner().unwrap()).unwrap()
-		)
 	}
-}
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub enum Arch {
-	#[serde(rename = "x64")]
-	X64,
-	#[serde(rename = "arm64")]
-	ARM64,
-}
+	/// Install assets of `arch` instead of the configured one, without saving it.
+	pub fn override_arch(&mut self, arch: Arch) {
+		self.arch_override = Some(arch);
+	}
+
+	/// Install plugins even if their DLL is built for another arch, without saving it.
+	pub fn skip_arch_check(&mut self) {
+		self.skip_arch_check = true;
+	}
+
+	/// Allow archives to extract to `mb` MB for this run, without saving it.
+	pub fn override_max_extract_size(&mut self, mb: u64) {
+		self.max_extract_size_override = Some(mb);
+	}
+
+	/// Leave PowerToys alone in this run, like `no_kill` without saving it.
+	pub fn override_no_kill(&mut self) {
+		self.no_kill_override = true;
+	}
 
-impl Default for Arch {
-	fn default() -> Self {
-		match std::env::consts::ARCH {
-			"x86_64" => Arch::X64,
-			"aarch64" => Arch::ARM64,
-			_ => unreachable!(),
+	/// Don't run the hooks of plugins in this run.
+	pub fn skip_hooks(&mut self) {
+		self.skip_hooks = true;
+	}
+
+	/// Change plugins in this run without checking the free disk space first.
+	pub fn skip_space_check(&mut self) {
+		self.skip_space_check = true;
+	}
+
+	/// Kill PowerToys in this run even while its updater is running.
+	pub fn ignore_updating(&mut self) {
+		self.ignore_updating = true;
+	}
+
+	/// Note: This method already used in the other methods.
+	/// Write the config, skipped if the file is the same.
+	pub fn save(&self) -> Result<()> {
+		// an older ptr would drop the fields it doesn't know
+		if self.schema > SCHEMA {
+			bail!(
+				"{} has schema {}, newer than this ptr understands ({}), nothing was written, \
+				 update with `ptr self-update`",
+				self.ctx.config_path.display(),
+				self.schema,
+				SCHEMA
+			);
 		}
+		let content = toml::to_string(self)?;
+		if fs::read_to_string(&self.ctx.config_path).is_ok_and(|c| c == content) {
+			_ = self.managed().save(&self.ctx.cache_path);
+			return Ok(());
+		}
+		fs::write(&self.ctx.config_path, content)?;
+		_ = self.managed().save(&self.ctx.cache_path);
+		Ok(())
 	}
-}
 
-impl fmt::Display for Arch {
-	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		match self {
-			Arch::X64 => write!(f, "x64"),
-			Arch::ARM64 => write!(f, "arm64"),
+	/// Plugins in this config, recorded for `import --prune`.
+	fn managed(&self) -> Managed {
+		Managed {
+			plugins: self
+				.plugins
+				.iter()
+				.map(|(name, p)| {
+					(
+						name.clone(),
+						ManagedPlugin {
+							dir: p.dir.clone(),
+							root: p.root.clone(),
+							pre_remove: p.pre_remove.clone(),
+						},
+					)
+				})
+				.collect(),
 		}
 	}
-}
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Plugin {
-	repo: String,
-	version: String,
-}
+	/// Restart PowerToys, and wait until it's running again if `wait`.
+	pub fn restart(&self, wait: bool) -> Result<()> {
+		self.check_updating()?;
+		kill_ptr(self.elevate_kill(), &self.restart_scope)
+			.map_err(|e| anyhow!(t!(FailedToKill, "PowerToys", e)))?;
+		if wait {
+			wait_ptr(&self.restart_scope, false)?;
+		}
+		start_ptr(&self.pt_path, &self.restart_scope)
+			.map_err(|e| anyhow!(t!(FailedToStart, "PowerToys", e)))?;
+		if wait {
+			wait_ptr(&self.restart_scope, true)?;
+		}
+		Ok(())
+	}
 
-impl Plugin {
-	/// Add a plugin with the specified version, None for the latest version.
-	fn add(name: &str, repo: String, version: Option<String>, arch: &Arch) -> Result<Self> {
-		let version = gh_dl!(name, &repo, version.as_deref(), arch)?;
-		Ok(Self { repo, version })
-	}
-
-	/// Update the plugin to the latest version.
-	/// Return `true` if the version is updated.
-	fn update(&mut self, name: &str, arch: &Arch) -> Result<bool> {
-		let version = gh_dl!(name, &self.repo, None, arch, &self.version)?;
-		if version != self.version {
-			self.version = version;
-			Ok(true)
+	/// Kill PowerToys and report whether it was running.
+	pub fn kill_now(&self) -> Result<()> {
+		let name = self.restart_scope.name();
+		if !is_ptr_running(&self.restart_scope) {
+			info!("{}", t!(NotRunning, name));
+			return Ok(());
+		}
+		kill_ptr(self.elevate_kill(), &self.restart_scope)
+			.map_err(|e| anyhow!(t!(FailedToKill, name, e)))?;
+		remove!(name);
+		Ok(())
+	}
+
+	/// Start PowerToys unless it's already running.
+	pub fn start_now(&self) -> Result<()> {
+		let name = self.restart_scope.name();
+		if is_ptr_running(&self.restart_scope) {
+			info!("{}", t!(AlreadyRunning, name));
+			return Ok(());
+		}
+		start_ptr(&self.pt_path, &self.restart_scope)
+			.map_err(|e| anyhow!(t!(FailedToStart, name, e)))?;
+		print_message!("+", bright_green, name);
+		Ok(())
+	}
+
+	/// Whether PowerToys has to be killed as administrator.
+	fn elevate_kill(&self) -> bool {
+		self.admin
+			.elevate(|| kill_needs_elevation(&self.restart_scope))
+	}
+
+	/// What happens to PowerToys around a change, `--no-kill` added to the config.
+	fn restart_plan(&self) -> RestartPlan {
+		RestartPlan::new(self.no_kill || self.no_kill_override, self.no_restart)
+	}
+
+	/// How failed hooks are handled, None if hooks are skipped in this run.
+	fn hook_failure(&self) -> Option<HookFailure> {
+		(!self.skip_hooks).then_some(self.hook_failure)
+	}
+
+	/// Fail if PowerToys is updating itself, unless `ignore_updating` is set.
+	fn check_updating(&self) -> Result<()> {
+		if self.ignore_updating {
+			return Ok(());
+		}
+		check_powertoys_updating()
+	}
+
+	/// Kill PowerToys before changing plugins, unless `no_kill` is set.
+	fn kill(&self) -> Result<()> {
+		if self.restart_plan() == RestartPlan::Untouched {
+			return Ok(());
+		}
+		self.check_updating()?;
+		kill_ptr(self.elevate_kill(), &self.restart_scope)
+			.map_err(|e| anyhow!(t!(FailedToKill, "PowerToys", e)))?;
+		Ok(())
+	}
+
+	/// Start PowerToys after changing plugins, unless `no_kill` or `no_restart` is set.
+	fn start(&self) {
+		match self.restart_plan() {
+			RestartPlan::Untouched => {
+				warning!(t!(NotRestarted));
+				return;
+			}
+			RestartPlan::Killed => return,
+			RestartPlan::Restarted => {}
+		}
+		start_ptr(&self.pt_path, &self.restart_scope)
+			.unwrap_or_else(|e| error!(t!(FailedToStart, "PowerToys", e)));
+	}
+
+	/// Kill PowerToys before updating plugins, unless they're swapped in place.
+	fn kill_for_update(&self) -> Result<()> {
+		if self.swap_in_place {
+			self.clean_swapped(false);
+			Ok(())
+		} else {
+			self.kill()
+		}
+	}
+
+	/// Start PowerToys after updating plugins, or tell that the running session keeps the old
+	/// versions if they were swapped in place.
+	fn start_after_update(&self) {
+		if !self.swap_in_place {
+			self.start();
+		} else if self
+			.outcomes
+			.iter()
+			.any(|(_, o)| matches!(o, SyncOutcome::Updated { .. }))
+		{
+			warning!(
+				"Plugins were swapped in place, PowerToys keeps the old versions until it restarts"
+			);
+		}
+	}
+
+	/// Remove the old plugin directories left by `swap_in_place` that are no longer loaded,
+	/// printing them if `verbose`.
+	pub fn clean_swapped(&self, verbose: bool) {
+		let roots = [self.ctx.plugin_path.clone()]
+			.into_iter()
+			.chain(self.other_roots());
+		for root in roots {
+			let Ok(entries) = fs::read_dir(&root) else {
+				continue;
+			};
+			for path in entries.flatten().map(|e| e.path()) {
+				if !is_swapped_dir(&path) {
+					continue;
+				}
+				match fs::remove_dir_all(&path) {
+					Ok(_) if verbose => remove!(path.display()),
+					Ok(_) => {}
+					Err(e) if verbose => {
+						warning!(
+							"{} is still in use, restart PowerToys first: {}",
+							path.display(),
+							e
+						)
+					}
+					Err(_) => {}
+				}
+			}
+		}
+	}
+
+	/// Install the plugins in the config, and remove the plugins ptr installed before that are
+	/// no longer in it if `prune`.
+	pub fn import_plugins(&mut self, prune: bool) -> Result<()> {
+		let mut new_plugins: HashMap<String, Plugin> = HashMap::new();
+		let stale = if prune {
+			self.stale_plugins()?
 		} else {
-			Ok(false)
+			Vec::new()
+		};
+		self.check_network(self.repos(self.plugins.keys()))?;
+		self.check_rate_limit(self.plugins.len(), false)?;
+		let opts = self.install_options(false);
+		if !self.skip_space_check {
+			let size = self
+				.plugins
+				.iter()
+				.map(|(name, p)| {
+					let filter = AssetFilter {
+						name: Some(name),
+						pattern: p.pattern.as_deref(),
+						installed: p.asset.as_deref().map(|a| (p.version.as_str(), a)),
+						assume_yes: opts.assume_yes,
+					};
+					Plugin::asset_size(&p.repo, None, None, &filter, p.token.as_ref(), &opts)
+				})
+				.sum();
+			check_free_space(&self.ctx.plugin_path, size)?;
+		}
+		self.kill()?;
+		let total = self.plugins.len();
+		// directories claimed by the plugins imported so far
+		let mut taken: Vec<String> = Vec::new();
+		for (i, (name, plugin)) in mem::take(&mut self.plugins).into_iter().enumerate() {
+			progress!(i + 1, total, name);
+			// pinned plugins stay at the version they were pinned at
+			let version = self.is_pinned(&name).then(|| plugin.version.clone());
+			let filter = AssetFilter {
+				name: Some(&name),
+				pattern: plugin.pattern.as_deref(),
+				installed: plugin
+					.asset
+					.as_deref()
+					.map(|a| (plugin.version.as_str(), a)),
+				assume_yes: opts.assume_yes,
+			};
+			match Plugin::add(
+				&name,
+				plugin.repo.clone(),
+				version,
+				plugin.dir.clone(),
+				&filter,
+				plugin.token.clone(),
+				plugin.root.clone(),
+				&opts,
+				&taken,
+			) {
+				Ok(new_plugin) => {
+					taken.push(new_plugin.dir_name(&name).to_string());
+					let outcome = SyncOutcome::Installed {
+						from: plugin.version.clone(),
+						to: new_plugin.version.clone(),
+					};
+					self.push_outcome(&name, outcome, Msg::FailedToImport, opts.verbose);
+					let new_plugin = Plugin {
+						hold: plugin.hold,
+						post_install: plugin.post_install,
+						pre_remove: plugin.pre_remove,
+						ignore_errors: plugin.ignore_errors,
+						..new_plugin
+					};
+					if let Err(e) = new_plugin.run_hook(
+						&self.ctx.plugin_path,
+						&name,
+						"post_install",
+						new_plugin.post_install.as_deref(),
+						opts.hook_failure,
+						opts.verbose,
+					) {
+						error!(t!(FailedToImport, name, e));
+					}
+					new_plugins.insert(name, new_plugin);
+				}
+				Err(e) => {
+					let outcome = SyncOutcome::Failed {
+						from: Some(plugin.version.clone()),
+						error: e,
+						ignored: plugin.ignore_errors,
+					};
+					self.push_outcome(&name, outcome, Msg::FailedToImport, opts.verbose);
+					// keep the entry so it can be imported again
+					taken.push(plugin.dir_name(&name).to_string());
+					new_plugins.insert(name, plugin);
+				}
+			}
 		}
+		for (name, plugin) in stale {
+			match plugin.remove(&self.ctx.plugin_path, &name, self.hook_failure()) {
+				Ok(_) => {
+					self.report.record(&name, None, None, Action::Removed);
+					remove!(name);
+				}
+				Err(e) => error!(t!(FailedToRemove, name, e)),
+			}
+		}
+		self.start();
+		self.plugins = new_plugins;
+		self.save()
+			.map_err(|e| anyhow!(t!(FailedToSaveConfig, e)))?;
+		self.write_report();
+		Ok(())
 	}
 
-	/// Update the plugin to specific version.
-	/// Return `true` if the version is updated.
-	fn update_to(&mut self, name: &str, arch: &Arch, version: &str) -> Result<bool> {
-		let version = gh_dl!(name, &self.repo, Some(version), arch, &self.version)?;
-		if version != self.version {
-			self.version = version;
-			Ok(true)
+	/// Plugins installed by ptr as of the last saved config that are not in this config,
+	/// confirmed by the user.
+	///
+	/// Directories still used by a plugin in this config are kept, as are directories that
+	/// were never in a config.
+	fn stale_plugins(&self) -> Result<Vec<(String, Plugin)>> {
+		let paths: Vec<PathBuf> = self
+			.plugins
+			.iter()
+			.map(|(n, p)| p.path(&self.ctx.plugin_path, n))
+			.collect();
+		let stale: Vec<(String, Plugin)> = Managed::load(&self.ctx.cache_path)
+			.plugins
+			.into_iter()
+			.filter(|(name, _)| !self.plugins.contains_key(name))
+			.map(|(name, m)| {
+				let plugin = Plugin {
+					repo: String::new(),
+					version: String::new(),
+					released: None,
+					dir: m.dir,
+					pattern: None,
+					asset: None,
+					hold: Vec::new(),
+					token: None,
+					post_install: None,
+					pre_remove: m.pre_remove,
+					root: m.root,
+					ignore_errors: false,
+				};
+				(name, plugin)
+			})
+			.filter(|(name, p)| {
+				let path = p.path(&self.ctx.plugin_path, name);
+				is_valid_dir_name(p.dir_name(name)) && path.is_dir() && !paths.contains(&path)
+			})
+			.collect();
+		if stale.is_empty() {
+			return Ok(stale);
+		}
+		info!("{}", t!(NoLongerInConfig).yellow());
+		for (name, plugin) in &stale {
+			info!(
+				"  {} ({})",
+				name,
+				plugin.path(&self.ctx.plugin_path, name).display()
+			);
+		}
+		if confirm(&t!(RemoveThem), false, self.ctx.assume_yes)? {
+			Ok(stale)
 		} else {
-			Ok(false)
+			Ok(Vec::new())
 		}
 	}
 
-	/// Remove the `PLUGIN_PATH/name` directory.
-	fn remove(&self, name: &str) -> Result<()> {
-		polling::remove_dir_all(&*PLUGIN_PATH.join(name))?;
+	#[allow(clippy::too_many_arguments)]
+	pub fn add(
+		&mut self,
+		name: &str,
+		repo: String,
+		version: Option<String>,
+		dir: Option<String>,
+		pattern: Option<String>,
+		scope: Scope,
+		skip_validation: bool,
+	) -> Result<()> {
+		let opts = self.install_options(skip_validation);
+		self.add_with(name, repo, version, dir, pattern, scope, &opts)
+	}
+
+	#[allow(clippy::too_many_arguments)]
+	fn add_with(
+		&mut self,
+		name: &str,
+		repo: String,
+		version: Option<String>,
+		dir: Option<String>,
+		pattern: Option<String>,
+		scope: Scope,
+		opts: &InstallOptions,
+	) -> Result<()> {
+		if let Some(pattern) = &pattern {
+			compile_pattern(&expand_pattern(pattern, &self.arch, "", Some(name)))?;
+		}
+		let root = self.scope_root(scope)?;
+		let taken: Vec<String> = self
+			.plugins
+			.iter()
+			.map(|(n, p)| p.dir_name(n).to_string())
+			.collect();
+		if self.plugins.contains_key(name) {
+			bail!("Plugin already exists")
+		}
+		self.check_network([repo.as_str()])?;
+		self.check_rate_limit(1, false)?;
+		let filter = AssetFilter {
+			name: Some(name),
+			pattern: pattern.as_deref(),
+			installed: None,
+			assume_yes: opts.assume_yes,
+		};
+		if !self.skip_space_check {
+			check_free_space(
+				&self.ctx.plugin_path,
+				Plugin::asset_size(&repo, version.as_deref(), None, &filter, None, opts),
+			)?;
+		}
+		self.kill()?;
+		let plugin = Plugin::add(name, repo, version, dir, &filter, None, root, opts, &taken)?;
+		add!(name, plugin.version);
+		self.plugins.insert(name.to_string(), plugin);
+		self.start();
+		self.save()?;
 		Ok(())
 	}
+
+	/// Print what `add` would install without downloading or changing anything.
+	/// Return whether the plugin would be installed.
+	pub fn add_dry_run(
+		&self,
+		name: &str,
+		repo: &str,
+		version: Option<&str>,
+		dir: Option<&str>,
+		pattern: Option<&str>,
+		scope: Scope,
+	) -> Result<bool> {
+		if let Some(pattern) = pattern {
+			compile_pattern(&expand_pattern(pattern, &self.arch, "", Some(name)))?;
+		}
+		if self.plugins.contains_key(name) {
+			bail!("Plugin already exists")
+		}
+		let opts = self.install_options(false);
+		let filter = AssetFilter {
+			name: Some(name),
+			pattern,
+			installed: None,
+			assume_yes: opts.assume_yes,
+		};
+		info!("{} {}", name.bright_cyan(), repo);
+		let changes = Plugin::print_plan(name, repo, version, None, &filter, None, &opts)?;
+		info!(
+			"  {} {}",
+			"directory:".dimmed(),
+			dir.unwrap_or("`Name` in plugin.json")
+		);
+		if let Some(root) = self.scope_root(scope)? {
+			info!("  {} {}", "root:".dimmed(), root.display());
+		}
+		self.print_restart_plan(changes);
+		Ok(changes)
+	}
+
+	/// Print what `update` would change without downloading or changing anything.
+	/// Return whether any plugin would be updated.
+	pub fn update_dry_run(&self, targets: Vec<(String, Option<String>)>) -> bool {
+		let opts = self.install_options(false);
+		let mut changes = false;
+		for (i, (name, version)) in targets.iter().enumerate() {
+			progress!(i + 1, targets.len(), name);
+			let Some(plugin) = self.plugins.get(name) else {
+				error!(t!(FailedToUpdate, name, self.not_found(name)));
+				continue;
+			};
+			match plugin.plan(name, version.as_deref(), &opts) {
+				Ok(true) => {
+					changes = true;
+					info!("  {} {}", "directory:".dimmed(), plugin.dir_name(name));
+				}
+				Ok(false) => up_to_date!(name, plugin.version),
+				Err(e) => error!("Failed to check {}: {}", name, e),
+			}
+		}
+		self.print_restart_plan(changes);
+		changes
+	}
+
+	/// Print what happens to PowerToys when plugins are changed.
+	fn print_restart_plan(&self, changes: bool) {
+		let plan = match self.restart_plan() {
+			_ if !changes => "not touched, nothing to change",
+			RestartPlan::Untouched => "not touched",
+			RestartPlan::Killed => "killed, not restarted",
+			RestartPlan::Restarted => "killed and restarted",
+		};
+		info!("{} {}", "PowerToys:".dimmed(), plan);
+	}
+
+	/// Drop pinned plugins from `targets` unless `force_pinned` or the user confirms.
+	pub fn filter_pinned(
+		&mut self,
+		targets: Vec<(String, Option<String>)>,
+		force_pinned: bool,
+	) -> Result<Vec<(String, Option<String>)>> {
+		let mut kept = vec![];
+		for (name, version) in targets {
+			if force_pinned || !self.is_pinned(&name) {
+				kept.push((name, version));
+				continue;
+			}
+			if confirm(&t!(PinnedUpdateAnyway, name), false, self.ctx.assume_yes)? {
+				kept.push((name, version));
+			} else {
+				let version = self.plugins[&name].version.clone();
+				self.push_outcome(
+					&name,
+					SyncOutcome::SkippedPinned { version },
+					Msg::FailedToUpdate,
+					false,
+				);
+			}
+		}
+		Ok(kept)
+	}
+
+	fn is_pinned(&self, name: &str) -> bool {
+		self.pin.as_ref().is_some_and(|p| p.contains(name))
+	}
+
+	/// Update plugins to the version paired with each name, None for the latest version.
+	pub fn update(
+		&mut self,
+		targets: Vec<(String, Option<String>)>,
+		skip_validation: bool,
+		notify: bool,
+		verbose: bool,
+	) -> Result<()> {
+		let opts = InstallOptions {
+			verbose,
+			..self.install_options(skip_validation)
+		};
+		self.update_with(targets, notify, &opts)
+	}
+
+	fn update_with(
+		&mut self,
+		mut targets: Vec<(String, Option<String>)>,
+		notify: bool,
+		opts: &InstallOptions,
+	) -> Result<()> {
+		self.check_network(self.repos(targets.iter().map(|(n, _)| n)))?;
+		let covered = self.check_rate_limit(targets.len(), true)?;
+		targets.truncate(covered);
+		self.check_space(
+			targets.iter().map(|(n, v)| (n.as_str(), v.as_deref())),
+			opts,
+		)?;
+		self.kill_for_update()?;
+
+		let mut latest = vec![];
+		for (i, (name, version)) in targets.iter().enumerate() {
+			progress!(i + 1, targets.len(), name);
+			let outcome = match self.plugins.get_mut(name) {
+				Some(plugin) => plugin.sync(name, version.as_deref(), opts),
+				None => SyncOutcome::Failed {
+					from: None,
+					error: anyhow!(self.not_found(name)),
+					ignored: false,
+				},
+			};
+			if version.is_none() && outcome.succeeded() {
+				latest.push(name);
+			}
+			self.push_outcome(name, outcome, Msg::FailedToUpdate, opts.verbose);
+		}
+		self.start_after_update();
+		self.cache_latest(&latest);
+		self.last_checked = Some(now() / SECS_PER_DAY * SECS_PER_DAY);
+		self.save()
+			.map_err(|e| anyhow!(t!(FailedToSaveConfig, e)))?;
+		if notify || self.notify {
+			self.summary().notify();
+		}
+		self.write_report();
+		Ok(())
+	}
+
+	/// Print a hint if plugins haven't been updated in `check_interval_days`.
+	pub fn stale_hint(&self) {
+		let Some(last_checked) = self.last_checked else {
+			return;
+		};
+		let days = now().saturating_sub(last_checked) / SECS_PER_DAY;
+		if self.check_interval_days > 0 && days >= self.check_interval_days {
+			reporter::report(Event::Hint {
+				text: format!("plugins last checked {days} days ago, run `ptr check`"),
+			});
+		}
+	}
+
+	/// Names of the plugins updated by `update --all`, except pinned ones and `exclude`, which
+	/// can be glob patterns.
+	pub fn all_targets(&mut self, exclude: &[String]) -> Vec<String> {
+		let mut excluded = BTreeSet::new();
+		for name in expand_globs(exclude, self.plugins.keys()) {
+			match self.plugins.keys().find(|k| k.eq_ignore_ascii_case(&name)) {
+				Some(key) => {
+					excluded.insert(key.clone());
+				}
+				None => warning!("{} is not installed, not excluded", name),
+			}
+		}
+		let mut targets = vec![];
+		let mut skipped = vec![];
+		for (name, plugin) in &self.plugins {
+			let version = plugin.version.clone();
+			if self.pin.as_ref().is_some_and(|p| p.contains(name)) {
+				skipped.push((name.clone(), SyncOutcome::SkippedPinned { version }));
+			} else if excluded.contains(name) {
+				skipped.push((name.clone(), SyncOutcome::SkippedExcluded { version }));
+			} else {
+				targets.push(name.clone());
+			}
+		}
+		skipped.sort_by(|a, b| a.0.cmp(&b.0));
+		for (name, outcome) in skipped {
+			self.push_outcome(&name, outcome, Msg::FailedToUpdate, false);
+		}
+		targets.sort();
+		targets
+	}
+
+	/// Update all plugins except pinned ones and `exclude`, which can be glob patterns.
+	pub fn update_all(
+		&mut self,
+		exclude: &[String],
+		skip_validation: bool,
+		notify: bool,
+		verbose: bool,
+	) -> Result<()> {
+		let opts = InstallOptions {
+			verbose,
+			..self.install_options(skip_validation)
+		};
+		let mut targets = self.all_targets(exclude);
+		self.check_network(self.repos(&targets))?;
+		let covered = self.check_rate_limit(targets.len(), true)?;
+		targets.truncate(covered);
+		self.check_space(targets.iter().map(|n| (n.as_str(), None)), &opts)?;
+		self.kill_for_update()?;
+		let mut latest = vec![];
+		for (i, name) in targets.iter().enumerate() {
+			progress!(i + 1, targets.len(), name);
+			let Some(plugin) = self.plugins.get_mut(name) else {
+				continue;
+			};
+			let outcome = plugin.sync(name, None, &opts);
+			if outcome.succeeded() {
+				latest.push(name);
+			}
+			self.push_outcome(name, outcome, Msg::FailedToUpdate, opts.verbose);
+		}
+		self.start_after_update();
+		self.cache_latest(&latest);
+		self.last_checked = Some(now() / SECS_PER_DAY * SECS_PER_DAY);
+		self.save()
+			.map_err(|e| anyhow!(t!(FailedToSaveConfig, e)))?;
+		if notify || self.notify {
+			self.summary().notify();
+		}
+		self.write_report();
+		Ok(())
+	}
+
+	/// Review the outdated plugins of `update --all`, choosing to update, skip or pin each, then
+	/// pin and update the chosen ones with one kill and restart of PowerToys.
+	pub fn update_interactive(
+		&mut self,
+		exclude: &[String],
+		skip_validation: bool,
+		notify: bool,
+		verbose: bool,
+	) -> Result<()> {
+		if self.ctx.assume_yes || !reporter::is_interactive() {
+			bail!("--interactive needs a terminal, use `ptr update --all` or `ptr pin add` in scripts");
+		}
+		let targets = self.all_targets(exclude);
+		self.check_network(self.repos(&targets))?;
+		let cache = self.refresh_latest()?;
+		let outdated: Vec<(String, String, String)> = self
+			.outdated(&cache)
+			.into_iter()
+			.filter(|(name, _)| targets.contains(name))
+			.filter_map(|(name, plugin)| {
+				let latest = cache.get(&plugin.repo)?.tag.clone();
+				Some((name.clone(), plugin.version.clone(), latest))
+			})
+			.collect();
+		if outdated.is_empty() {
+			info!("{}", t!(AllUpToDate));
+			return Ok(());
+		}
+
+		let mut choices = vec![Choice::Update; outdated.len()];
+		loop {
+			for (i, ((name, version, latest), choice)) in outdated.iter().zip(&choices).enumerate()
+			{
+				info!(
+					"  {:>2} {} {} {} -> {}",
+					i + 1,
+					choice,
+					name.bright_cyan(),
+					version,
+					latest.bright_green()
+				);
+			}
+			let input = prompt(&t!(ChooseUpdates), self.ctx.assume_yes)?;
+			match input.as_str() {
+				"" => break,
+				"q" => return Ok(()),
+				_ => {}
+			}
+			for part in input.split([' ', ',']).filter(|p| !p.is_empty()) {
+				match part.parse::<usize>() {
+					Ok(n) if (1..=choices.len()).contains(&n) => {
+						choices[n - 1] = choices[n - 1].next();
+					}
+					_ => warning!("{} is not a number from 1 to {}", part, choices.len()),
+				}
+			}
+		}
+
+		let mut update = vec![];
+		for ((name, version, _), choice) in outdated.into_iter().zip(choices) {
+			match choice {
+				Choice::Update => update.push((name, None)),
+				Choice::Skip => self.push_outcome(
+					&name,
+					SyncOutcome::SkippedExcluded { version },
+					Msg::FailedToUpdate,
+					false,
+				),
+				Choice::Pin => {
+					self.pin
+						.get_or_insert_with(BTreeSet::new)
+						.insert(name.clone());
+					self.push_outcome(
+						&name,
+						SyncOutcome::SkippedPinned { version },
+						Msg::FailedToUpdate,
+						false,
+					);
+				}
+			}
+		}
+		if update.is_empty() {
+			self.save()
+				.map_err(|e| anyhow!(t!(FailedToSaveConfig, e)))?;
+			self.write_report();
+			Ok(())
+		} else {
+			// saves the pins along with the updated versions
+			self.update(update, skip_validation, notify, verbose)
+		}
+	}
+
+	/// Print what happened to a plugin and record it for the report and the summary, so
+	/// `update`, `update --all` and `import` report alike. `failure` is the message for errors.
+	fn push_outcome(&mut self, name: &str, outcome: SyncOutcome, failure: Msg, verbose: bool) {
+		match &outcome {
+			SyncOutcome::Installed { from, to } => {
+				add!(name, to);
+				self.report
+					.record(name, Some(from), Some(to), Action::Installed);
+			}
+			SyncOutcome::Updated { from, to, diff } => {
+				add!(name, to);
+				if verbose {
+					diff.print();
+				}
+				self.report
+					.record(name, Some(from), Some(to), Action::Updated);
+			}
+			SyncOutcome::UpToDate { version } => {
+				up_to_date!(name, version);
+				self.report
+					.record(name, Some(version), Some(version), Action::UpToDate);
+			}
+			SyncOutcome::SkippedPinned { version } => {
+				print_message!("~", bright_yellow, "{} pinned, skipped", name);
+				self.report
+					.record(name, Some(version), Some(version), Action::SkippedPinned);
+			}
+			SyncOutcome::SkippedExcluded { version } => {
+				print_message!("~", bright_yellow, "{} skipped", name);
+				self.report
+					.record(name, Some(version), Some(version), Action::SkippedExcluded);
+			}
+			SyncOutcome::Failed {
+				from,
+				error,
+				ignored,
+			} => {
+				if *ignored {
+					info!("  {}", format!("ignored: {error}").dimmed());
+				} else {
+					error!(i18n::fill(i18n::tr(failure), &[&name, error]));
+				}
+				self.report.failed(name, from.as_deref(), error.to_string());
+			}
+		}
+		self.outcomes.push((name.to_string(), outcome));
+	}
+
+	/// Summary of the changed and failed plugins of this run for the notification.
+	fn summary(&self) -> Summary {
+		let mut summary = Summary::default();
+		for (name, outcome) in &self.outcomes {
+			match outcome {
+				SyncOutcome::Updated { to, .. } => summary.updated(name, to),
+				SyncOutcome::Failed { ignored: false, .. } => summary.failed(name),
+				_ => {}
+			}
+		}
+		summary
+	}
+
+	/// Remove plugins, or every plugin if `all`.
+	pub fn remove(&mut self, names: Vec<String>, all: bool) -> Result<()> {
+		let names = if all {
+			self.plugins.keys().cloned().collect()
+		} else {
+			names
+		};
+		let mut kept = vec![];
+		for name in names {
+			if !self.is_pinned(&name)
+				|| confirm(&t!(PinnedRemoveAnyway, name), false, self.ctx.assume_yes)?
+			{
+				kept.push(name);
+			}
+		}
+		self.kill()?;
+		for name in kept {
+			let Some(plugin) = self.plugins.get(&name) else {
+				error!(t!(FailedToRemove, name, self.not_found(&name)));
+				continue;
+			};
+			match plugin.remove(&self.ctx.plugin_path, &name, self.hook_failure()) {
+				Ok(_) => {
+					self.plugins.remove(&name);
+					if let Some(pins) = self.pin.as_mut() {
+						pins.remove(&name);
+					}
+					remove!(name);
+				}
+				Err(e) => error!(t!(FailedToRemove, name, e)),
+			}
+		}
+		self.start();
+		self.save().map_err(|e| anyhow!(t!(FailedToSaveConfig, e)))
+	}
+
+	/// Pin plugins, `names` can be glob patterns like `Git*`, or every plugin if `all`.
+	pub fn pin_add(&mut self, names: Vec<String>, all: bool) -> Result<()> {
+		let names = if all {
+			self.plugins.keys().cloned().collect()
+		} else {
+			expand_globs(&names, self.plugins.keys())
+		};
+		let pins = self.pin.get_or_insert_with(BTreeSet::new);
+		for name in names {
+			if pins.insert(name.clone()) {
+				print_message!("+", bright_green, "{}", name);
+			}
+		}
+		self.save().map_err(|e| anyhow!(t!(FailedToSaveConfig, e)))
+	}
+
+	/// Remove pins, `names` can be glob patterns like `Git*`.
+	pub fn pin_remove(&mut self, names: Vec<String>) -> Result<()> {
+		let Some(pins) = self.pin.as_mut() else {
+			return Ok(());
+		};
+		for name in expand_globs(&names, pins.iter()) {
+			if pins.remove(&name) {
+				remove!(name);
+			}
+		}
+		self.save().map_err(|e| anyhow!(t!(FailedToSaveConfig, e)))
+	}
+
+	/// Print pins, marking the ones of plugins not in config.
+	pub fn pin_list(&self) {
+		if let Some(pins) = self.pin.as_ref() {
+			for name in pins {
+				if self.plugins.contains_key(name) {
+					info!("{name}");
+				} else {
+					info!("{} {}", name.bright_red(), "(not installed)".dimmed());
+				}
+			}
+		}
+	}
+
+	/// Remove pins of plugins not in config.
+	pub fn pin_prune(&mut self) -> Result<()> {
+		let Some(pins) = self.pin.as_mut() else {
+			return Ok(());
+		};
+		pins.retain(|name| {
+			let keep = self.plugins.contains_key(name);
+			if !keep {
+				remove!(name);
+			}
+			keep
+		});
+		self.save().map_err(|e| anyhow!(t!(FailedToSaveConfig, e)))
+	}
+
+	pub fn pin_reset(&mut self) -> Result<()> {
+		self.pin = None;
+		self.save().map_err(|e| anyhow!(t!(FailedToSaveConfig, e)))
+	}
+
+	/// Write version.toml and the installed plugins into a zip archive at `path`.
+	pub fn backup(&self, path: &Path) -> Result<()> {
+		let btree_map: BTreeMap<_, _> = self.plugins.iter().collect();
+		// the archive mirrors the plugin directory
+		for (name, plugin) in &btree_map {
+			if let Some(root) = &plugin.root {
+				warning!("{}: skipped, installed in {}", name, root.display());
+			}
+		}
+		let dirs: Vec<&str> = btree_map
+			.into_iter()
+			.filter(|(name, plugin)| {
+				plugin.root.is_none() && plugin.is_installed(&self.ctx.plugin_path, name)
+			})
+			.map(|(name, plugin)| plugin.dir_name(name))
+			.collect();
+		let size = backup::create(&self.ctx, path, &dirs)?;
+		info!(
+			"{}",
+			t!(BackedUp, dirs.len(), path.display(), format_size(size))
+		);
+		Ok(())
+	}
+
+	/// Write the plugins, and the pins if `pins`, to `path` for `import --file` on another
+	/// machine. Tokens, plugin roots and hooks are left out since they belong to this machine.
+	pub fn export(&self, path: &Path, pins: bool) -> Result<()> {
+		let mut plugins = toml::Table::new();
+		for (name, plugin) in &self.plugins {
+			let toml::Value::Table(mut table) = toml::Value::try_from(plugin)? else {
+				unreachable!("a plugin is a table");
+			};
+			for key in ["token", "root", "post_install", "pre_remove"] {
+				table.remove(key);
+			}
+			plugins.insert(name.clone(), table.into());
+		}
+		let mut manifest = toml::Table::new();
+		// the file may use keys of this ptr, so older ones refuse it
+		manifest.insert("ptr_version".to_string(), export_ptr_version().into());
+		if let Some(pin) = self.pin.as_ref().filter(|p| pins && !p.is_empty()) {
+			manifest.insert("pin".to_string(), toml::Value::try_from(pin)?);
+		}
+		manifest.insert("plugins".to_string(), plugins.into());
+		fs::write(path, toml::to_string(&manifest)?)
+			.map_err(|e| anyhow!("Failed to write {}: {}", path.display(), e))?;
+		info!("{}", t!(Exported, self.plugins.len(), path.display()));
+		Ok(())
+	}
+
+	/// Restore version.toml and the plugins from an archive written by `backup`.
+	///
+	/// Refuse to overwrite a config changed after the backup, unless `force`.
+	pub fn restore(&self, path: &Path, force: bool) -> Result<()> {
+		backup::check(&self.ctx, path, force)?;
+		self.kill()?;
+		let result = backup::restore(&self.ctx, path);
+		self.start();
+		let dirs = result?;
+		info!(
+			"{}",
+			t!(
+				Restored,
+				dirs.len(),
+				path.display(),
+				format_size(fs::metadata(path)?.len())
+			)
+		);
+		Ok(())
+	}
+
+	/// Move every installed plugin aside with PowerToys killed, e.g. when a plugin makes
+	/// PowerToys Run crash on startup. See `safe_mode`.
+	pub fn safe_mode_on(&self) -> Result<()> {
+		if SafeMode::is_on(&self.ctx.cache_path) {
+			bail!("Safe mode is already on, `ptr safe-mode off` restores the plugins");
+		}
+		let dirs: BTreeMap<_, _> = self
+			.plugins
+			.iter()
+			.filter(|(name, plugin)| plugin.is_installed(&self.ctx.plugin_path, name))
+			.map(|(name, plugin)| (name, plugin.path(&self.ctx.plugin_path, name)))
+			.collect();
+		if dirs.is_empty() {
+			info!("{}", t!(NoPluginsToDisable));
+			return Ok(());
+		}
+		self.kill()?;
+		let mut safe_mode = SafeMode::default();
+		for (name, dir) in dirs {
+			match safe_mode::disable(&dir) {
+				Ok(()) => {
+					safe_mode.dirs.insert(name.clone(), dir);
+					// saved as it goes, so `off` finds the directories even if ptr is stopped
+					safe_mode.save(&self.ctx.cache_path)?;
+					print_message!("-", bright_red, "{} disabled", name);
+				}
+				Err(e) => error!("Failed to disable {}: {}", name, e),
+			}
+		}
+		self.start();
+		info!("{}", t!(SafeModeOn, safe_mode.dirs.len()));
+		Ok(())
+	}
+
+	/// Restore the plugins disabled by `safe_mode_on`, the ones that fail stay disabled.
+	pub fn safe_mode_off(&self) -> Result<()> {
+		let Some(mut safe_mode) = SafeMode::load(&self.ctx.cache_path)? else {
+			bail!("Safe mode is off");
+		};
+		self.kill()?;
+		for (name, dir) in mem::take(&mut safe_mode.dirs) {
+			match safe_mode::enable(&dir) {
+				Ok(()) => print_message!("+", bright_green, "{} enabled", name),
+				Err(e) => {
+					error!("Failed to enable {}: {}", name, e);
+					safe_mode.dirs.insert(name, dir);
+				}
+			}
+		}
+		safe_mode.save(&self.ctx.cache_path)?;
+		self.start();
+		Ok(())
+	}
+
+	/// Find the plugin that makes PowerToys Run crash by enabling half of the disabled plugins
+	/// at a time and asking whether it still crashes. The others are enabled again, the culprit
+	/// stays disabled.
+	pub fn safe_mode_bisect(&self) -> Result<()> {
+		let Some(mut safe_mode) = SafeMode::load(&self.ctx.cache_path)? else {
+			bail!("Safe mode is off, `ptr safe-mode on` disables the plugins first");
+		};
+		if self.ctx.assume_yes || !reporter::is_interactive() {
+			bail!("bisect needs a terminal to answer whether PowerToys Run crashes");
+		}
+		let mut suspects: Vec<String> = safe_mode.dirs.keys().cloned().collect();
+		while suspects.len() > 1 {
+			let half = suspects.split_off(suspects.len() / 2);
+			if self.safe_mode_try(&safe_mode, &half)? {
+				suspects = half;
+			}
+		}
+		let Some(culprit) = suspects.pop() else {
+			return Ok(());
+		};
+		// the last suspect is only known to crash alone once it's tried alone
+		if !self.safe_mode_try(&safe_mode, std::slice::from_ref(&culprit))? {
+			bail!("No plugin makes PowerToys Run crash on its own, all stay disabled");
+		}
+		self.kill()?;
+		safe_mode.dirs.retain(|name, dir| {
+			*name == culprit
+				|| safe_mode::enable(dir)
+					.map_err(|e| error!("Failed to enable {}: {}", name, e))
+					.is_err()
+		});
+		safe_mode.save(&self.ctx.cache_path)?;
+		self.start();
+		info!("{}", t!(SafeModeCulprit, culprit.bright_cyan(), culprit));
+		Ok(())
+	}
+
+	/// Enable `names` and start PowerToys, ask whether PowerToys Run crashes, then disable them
+	/// again.
+	fn safe_mode_try(&self, safe_mode: &SafeMode, names: &[String]) -> Result<bool> {
+		let dirs: Vec<&PathBuf> = names.iter().filter_map(|n| safe_mode.dirs.get(n)).collect();
+		self.kill()?;
+		for dir in &dirs {
+			safe_mode::enable(dir)?;
+		}
+		self.start();
+		let crashed = confirm(
+			&t!(DoesItCrash, names.join(", ")),
+			false,
+			self.ctx.assume_yes,
+		);
+		self.kill()?;
+		for dir in &dirs {
+			safe_mode::disable(dir)?;
+		}
+		crashed
+	}
+
+	/// Print the plugins, with the installed asset if `verbose`, and the cached latest version if
+	/// `latest`.
+	///
+	/// The latest versions are fetched again if `refresh`.
+	pub fn list(
+		&self,
+		verbose: bool,
+		latest: bool,
+		refresh: bool,
+		repo: RepoStyle,
+		disk: DiskColumns,
+	) -> Result<()> {
+		let cache = match (latest, refresh) {
+			(_, true) => Some(self.refresh_latest()?),
+			(true, false) => LatestCache::load(&self.ctx.cache_path),
+			(false, false) => None,
+		};
+		info!(self
+			.table(verbose, cache.as_ref(), repo, disk)
+			.trim_end_matches('\n'));
+		Ok(())
+	}
+
+	/// Fetch the latest version of every plugin and print the outdated ones, and write the
+	/// counts for `ptr state` if `write_state`.
+	///
+	/// # Returns
+	/// Whether any plugin is outdated.
+	pub fn check(&self, write_state: bool) -> Result<bool> {
+		self.check_network(self.repos(self.plugins.keys()))?;
+		// one request per plugin, the cached versions beat an error for each plugin
+		let cache = match self.rate_limit() {
+			Some(limit) if limit.remaining < self.plugins.len() as u64 => {
+				warning!(
+					"Skipping the live check, rate limited for {} minutes, showing cached versions",
+					limit.minutes_to_reset()
+				);
+				LatestCache::load(&self.ctx.cache_path).unwrap_or_default()
+			}
+			_ => self.refresh_latest()?,
+		};
+		let outdated = self.outdated(&cache);
+		for (name, plugin) in &outdated {
+			let latest = cache.get(&plugin.repo);
+			let released = latest
+				.and_then(|l| l.released.as_deref())
+				.and_then(released_ago)
+				.map(|r| format!(" {}", format!("({r})").dimmed()))
+				.unwrap_or_default();
+			info!(
+				"  {} {} -> {}{}",
+				name,
+				plugin.version,
+				latest.map_or("-", |l| l.tag.as_str()).bright_green(),
+				released
+			);
+		}
+		if outdated.is_empty() {
+			info!("{}", t!(AllUpToDate));
+		}
+		if write_state || State::exists(&self.ctx.cache_path) {
+			self.write_state(&cache)?;
+		}
+		Ok(!outdated.is_empty())
+	}
+
+	/// Plugins with a newer version in `cache`, by name.
+	fn outdated<'a>(&'a self, cache: &LatestCache) -> Vec<(&'a String, &'a Plugin)> {
+		let mut outdated: Vec<_> = self
+			.plugins
+			.iter()
+			.filter(|(_, p)| {
+				cache
+					.get(&p.repo)
+					.is_some_and(|l| compare_versions(&l.tag, &p.version).is_gt())
+			})
+			.collect();
+		outdated.sort_by_key(|(name, _)| *name);
+		outdated
+	}
+
+	/// Write the counts of the plugins for `ptr state`, stale after `check_interval_days`.
+	fn write_state(&self, cache: &LatestCache) -> Result<()> {
+		State {
+			checked_at: now(),
+			stale_after: self.check_interval_days * SECS_PER_DAY,
+			plugins: self.plugins.len(),
+			outdated: self.outdated(cache).len(),
+			failed: self
+				.plugins
+				.values()
+				.filter(|p| cache.get(&p.repo).is_none())
+				.count(),
+		}
+		.save(&self.ctx.cache_path)
+	}
+
+	/// Fetch the latest version of every plugin into the cache.
+	fn refresh_latest(&self) -> Result<LatestCache> {
+		let mut cache = LatestCache::load(&self.ctx.cache_path).unwrap_or_default();
+		let global = self.github();
+		for (name, plugin) in &self.plugins {
+			let source = plugin
+				.token
+				.as_ref()
+				.filter(|_| self.token_override.is_none())
+				.map(|t| GitHub::for_plugin(&t.0).offline(self.ctx.offline));
+			match source
+				.as_ref()
+				.unwrap_or(&global)
+				.latest_release(&plugin.repo)
+			{
+				Ok(release) => cache.set(
+					&plugin.repo,
+					&release.tag_name,
+					release.published_at.as_deref(),
+					now(),
+				),
+				Err(e) => error!("Failed to fetch the latest version of {}: {}", name, e),
+			}
+		}
+		cache.save(&self.ctx.cache_path)?;
+		Ok(cache)
+	}
+
+	/// Record the versions of plugins updated to the latest version, and refresh the state of
+	/// `ptr state` if it's used.
+	fn cache_latest(&self, names: &[impl AsRef<str>]) {
+		let mut cache = LatestCache::load(&self.ctx.cache_path).unwrap_or_default();
+		for name in names {
+			if let Some(plugin) = self.plugins.get(name.as_ref()) {
+				cache.set(
+					&plugin.repo,
+					&plugin.version,
+					plugin.released.as_deref(),
+					now(),
+				);
+			}
+		}
+		_ = cache.save(&self.ctx.cache_path);
+		if State::exists(&self.ctx.cache_path) {
+			_ = self.write_state(&cache);
+		}
+	}
+
+	/// Table of the plugins for `list`.
+	fn table(
+		&self,
+		verbose: bool,
+		latest: Option<&LatestCache>,
+		repo: RepoStyle,
+		disk: DiskColumns,
+	) -> String {
+		let mut tw = TabWriter::new(vec![]);
+		let repo_text = |p: &Plugin| {
+			if repo.url {
+				repo_url(&p.repo)
+			} else {
+				p.repo.clone()
+			}
+		};
+		writeln!(&mut tw, "{}", "Plugins:".bright_green()).unwrap();
+		let settings = RunSettings::load(&self.ctx.run_settings_path).ok();
+		let btree_map: BTreeMap<_, _> = self.plugins.iter().collect();
+		let roots = btree_map.values().any(|p| p.root.is_some());
+		let dirs = btree_map.values().any(|p| p.dir.is_some());
+		for (name, plugin) in &btree_map {
+			let metadata = read_plugin_metadata(&plugin.path(&self.ctx.plugin_path, name));
+			let disabled = settings.as_ref().is_some_and(|s| {
+				metadata
+					.as_ref()
+					.is_ok_and(|m| s.is_disabled(m) == Some(true))
+			});
+			write!(
+				&mut tw,
+				"  {}\t{}\t{}",
+				name.bright_cyan(),
+				repo_text(plugin),
+				plugin.version,
+			)
+			.unwrap();
+			if let Some(cache) = latest {
+				write!(&mut tw, "\t{}", self.latest_column(plugin, cache)).unwrap();
+			}
+			if verbose {
+				write!(&mut tw, "\t{}", plugin.asset.as_deref().unwrap_or("-")).unwrap();
+			}
+			if dirs {
+				let dir = plugin.dir.as_deref().unwrap_or("-");
+				write!(&mut tw, "\t{}", dir.dimmed()).unwrap();
+			}
+			if roots {
+				let root = plugin
+					.root
+					.as_ref()
+					.map_or("-".to_string(), |r| r.display().to_string());
+				write!(&mut tw, "\t{}", root.dimmed()).unwrap();
+			}
+			if disk.installed {
+				// the same comparison as `diff`
+				let installed = match &metadata {
+					Ok(m) => match m.version.as_deref() {
+						Some(v) if compare_versions(&plugin.version, v).is_ne() => {
+							v.bright_yellow()
+						}
+						Some(v) => v.normal(),
+						None => "-".normal(),
+					},
+					Err(_) => "no plugin.json".bright_red(),
+				};
+				write!(&mut tw, "\t{installed}").unwrap();
+			}
+			write!(
+				&mut tw,
+				"\t{}",
+				if !plugin.is_installed(&self.ctx.plugin_path, name) {
+					"missing".bright_red()
+				} else if disabled {
+					"disabled".bright_red()
+				} else {
+					"".normal()
+				}
+			)
+			.unwrap();
+			if disk.long {
+				let (author, description) = metadata.as_ref().map_or((None, None), |m| {
+					(m.author.as_deref(), m.description.as_deref())
+				});
+				let released = plugin.released.as_deref().unwrap_or("-");
+				write!(
+					&mut tw,
+					"\t{}\t{}\t{}",
+					released.get(..10).unwrap_or(released),
+					author.unwrap_or("-"),
+					description.unwrap_or("-").dimmed()
+				)
+				.unwrap();
+			}
+			writeln!(&mut tw).unwrap();
+		}
+		tw.flush().unwrap();
+		let table = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+		if !repo.hyperlinks {
+			return table;
+		}
+		// linked after aligning, tabwriter would count the invisible link as text
+		let mut lines = table.lines();
+		let mut linked = format!("{}\n", lines.next().unwrap_or_default());
+		for (line, (_, plugin)) in lines.zip(&btree_map) {
+			let text = repo_text(plugin);
+			linked.push_str(&line.replacen(&text, &hyperlink(&repo_url(&plugin.repo), &text), 1));
+			linked.push('\n');
+		}
+		linked
+	}
+
+	/// The cached latest version with its age, marked stale after `check_interval_days`.
+	fn latest_column(&self, plugin: &Plugin, cache: &LatestCache) -> String {
+		let Some(latest) = cache.get(&plugin.repo) else {
+			return "-".to_string();
+		};
+		let tag = match compare_versions(&latest.tag, &plugin.version) {
+			Ordering::Greater => latest.tag.bright_green(),
+			_ => latest.tag.normal(),
+		};
+		let age = now().saturating_sub(latest.fetched_at);
+		let stale = self.check_interval_days > 0 && age / SECS_PER_DAY >= self.check_interval_days;
+		let age = match age {
+			a if a >= SECS_PER_DAY => format!("{}d", a / SECS_PER_DAY),
+			a if a >= 60 * 60 => format!("{}h", a / (60 * 60)),
+			a => format!("{}m", a / 60),
+		};
+		if stale {
+			format!("{} {}", tag, format!("({age}, stale)").bright_yellow())
+		} else {
+			format!("{} {}", tag, format!("({age})").dimmed())
+		}
+	}
+
+	/// Print the assets of a release, and which one would be installed.
+	pub fn assets(&self, repo: &str, version: Option<&str>, pattern: Option<&str>) -> Result<()> {
+		let source = self.github();
+		let arch = self.arch_override.as_ref().unwrap_or(&self.arch);
+		print_assets(&source, repo, version, arch, pattern)
+	}
+
+	/// Compare the config with the installed plugins, and print the differences.
+	/// Return whether any difference is left.
+	///
+	/// Versions in plugin.json are written into the config, and unmanaged copies of managed
+	/// plugins are deleted after confirmation, if `fix`.
+	pub fn diff(&mut self, fix: bool) -> Result<bool> {
+		let mut missing = vec![];
+		let mut mismatched = vec![];
+		let mut misnamed = vec![];
+		let btree_map: BTreeMap<_, _> = self.plugins.iter().collect();
+		for (name, plugin) in btree_map {
+			if let Some(actual) = plugin.misnamed_dir(&self.ctx.plugin_path, name) {
+				misnamed.push((name.clone(), actual));
+				continue;
+			}
+			if !plugin.is_installed(&self.ctx.plugin_path, name) {
+				missing.push(name.clone());
+				continue;
+			}
+			let installed = read_plugin_metadata(&plugin.path(&self.ctx.plugin_path, name))
+				.ok()
+				.and_then(|m| m.version);
+			if let Some(installed) = installed {
+				if compare_versions(&plugin.version, &installed).is_ne() {
+					mismatched.push((name.clone(), installed));
+				}
+			}
+		}
+		let untracked: Vec<String> = self
+			.installed_dirs()?
+			.into_iter()
+			.map(|(_, d)| d)
+			.filter(|d| {
+				!self
+					.plugins
+					.iter()
+					.any(|(n, p)| same_dir_name(n, d) || same_dir_name(p.dir_name(n), d))
+			})
+			.collect();
+
+		if !missing.is_empty() {
+			info!("{}", "Missing on disk:".bright_red());
+			for name in &missing {
+				info!("  {}", name.bright_cyan());
+			}
+		}
+		if !mismatched.is_empty() {
+			info!(
+				"{}",
+				"Version mismatch (config -> plugin.json):".bright_yellow()
+			);
+			for (name, installed) in &mismatched {
+				info!(
+					"  {} {} -> {}",
+					name.bright_cyan(),
+					self.plugins[name].version,
+					installed
+				);
+			}
+		}
+		if !misnamed.is_empty() {
+			info!(
+				"{}",
+				"Directory name differs by case or spaces, fix with `ptr diff --fix`:"
+					.bright_yellow()
+			);
+			for (name, actual) in &misnamed {
+				info!(
+					"  {} {:?}",
+					name.bright_cyan(),
+					actual.file_name().unwrap_or_default()
+				);
+			}
+		}
+		if !untracked.is_empty() {
+			info!(
+				"{}",
+				"Not in config, add them with `ptr init`:".bright_yellow()
+			);
+			for dir in &untracked {
+				info!("  {}", dir);
+			}
+		}
+		let mut duplicates = self.duplicates()?;
+		if !duplicates.is_empty() {
+			info!(
+				"{}",
+				"Same plugin in several directories, PowerToys may load either:".bright_yellow()
+			);
+			for (id, dirs) in &duplicates {
+				info!("  {}", id.bright_cyan());
+				for (dir, managed) in dirs {
+					let mark = if *managed { "managed" } else { "unmanaged" };
+					info!("    {} {}", dir.display(), mark.dimmed());
+				}
+			}
+		}
+
+		if fix && !mismatched.is_empty() {
+			for (name, installed) in mem::take(&mut mismatched) {
+				let plugin = self.plugins.get_mut(&name).unwrap();
+				// keep the tag style so the next update compares against the same form
+				plugin.version = if plugin.version.starts_with('v') && !installed.starts_with('v') {
+					format!("v{installed}")
+				} else {
+					installed
+				};
+				add!(name, plugin.version);
+			}
+			self.save()?;
+		}
+		if fix && !duplicates.is_empty() {
+			self.remove_duplicates(&mut duplicates)?;
+		}
+		if fix && !misnamed.is_empty() {
+			self.kill()?;
+			for (name, _) in mem::take(&mut misnamed) {
+				let plugin = &self.plugins[&name];
+				match plugin.fix_dir_name(&self.ctx.plugin_path, &name) {
+					Ok(Some(old)) => info!("Renamed {:?} to {:?}", old, plugin.dir_name(&name)),
+					Ok(None) => {}
+					Err(e) => {
+						error!("Failed to rename the directory of {}: {}", name, e);
+						misnamed.push((name, PathBuf::new()));
+					}
+				}
+			}
+		}
+		let different = !(missing.is_empty()
+			&& mismatched.is_empty()
+			&& misnamed.is_empty()
+			&& untracked.is_empty()
+			&& duplicates.is_empty());
+		if !different {
+			info!("{}", t!(ConfigMatches).bright_green());
+		}
+		Ok(different)
+	}
+
+	/// Check offline why an installed plugin may not load in PowerToys Run, printing each check
+	/// with a remedy if it failed.
+	///
+	/// # Returns
+	/// Whether any check failed.
+	pub fn explain(&self, name: &str) -> Result<bool> {
+		let plugin = self
+			.plugins
+			.get(name)
+			.ok_or_else(|| anyhow!(self.not_found(name)))?;
+		let reinstall = format!(
+			"reinstall it with `ptr remove {name}` and `ptr add {name} {}`",
+			plugin.repo
+		);
+		let mut checks: Vec<(String, Option<String>)> = vec![];
+		let misnamed = plugin.misnamed_dir(&self.ctx.plugin_path, name);
+		let dir = misnamed
+			.clone()
+			.unwrap_or_else(|| plugin.path(&self.ctx.plugin_path, name));
+		if !dir.is_dir() {
+			checks.push((
+				format!("{} exists", dir.display()),
+				Some(format!("run `ptr update {name}` to reinstall it")),
+			));
+			return Ok(print_checks(&checks));
+		}
+		if let Some(actual) = &misnamed {
+			checks.push((
+				format!(
+					"directory {:?} matches {:?}",
+					actual.file_name().unwrap_or_default(),
+					plugin.dir_name(name)
+				),
+				Some("run `ptr diff --fix` to rename it".to_string()),
+			));
+		}
+
+		let metadata = match read_plugin_metadata(&dir) {
+			Ok(metadata) => {
+				checks.push(("plugin.json parses".to_string(), None));
+				Some(metadata)
+			}
+			Err(e) => {
+				checks.push((e.to_string(), Some(reinstall.clone())));
+				None
+			}
+		};
+		if let Some(metadata) = &metadata {
+			match &metadata.execute_file_name {
+				None => checks.push((
+					"plugin.json has ExecuteFileName".to_string(),
+					Some("the plugin is broken, report it to its author".to_string()),
+				)),
+				Some(file) if !dir.join(file).is_file() => {
+					checks.push((format!("{file} exists"), Some(reinstall.clone())))
+				}
+				Some(file) => match pe_arch(&dir.join(file)) {
+					Ok(pe) if pe.runs_on(&self.arch) => {
+						checks.push((format!("{file} is {pe}, arch is {}", self.arch), None))
+					}
+					Ok(pe) => checks.push((
+						format!("{file} is {pe}, arch is {}", self.arch),
+						Some(format!(
+							"set `pattern` to choose the {} asset and reinstall it",
+							self.arch
+						)),
+					)),
+					Err(e) => checks.push((e.to_string(), Some(reinstall.clone()))),
+				},
+			}
+		}
+
+		match blocked_files(&dir)? {
+			0 => checks.push(("no files blocked by Zone.Identifier".to_string(), None)),
+			count => checks.push((
+				format!("{count} files blocked by Zone.Identifier"),
+				Some(format!(
+					"set `unblock = true` in version.toml and reinstall it, or run `Get-ChildItem \
+					 -Recurse '{}' | Unblock-File`",
+					dir.display()
+				)),
+			)),
+		}
+
+		if let Some(metadata) = &metadata {
+			match RunSettings::load(&self.ctx.run_settings_path).map(|s| s.is_disabled(metadata)) {
+				Ok(Some(false)) => checks.push(("enabled in PowerToys Run".to_string(), None)),
+				Ok(Some(true)) => checks.push((
+					"enabled in PowerToys Run".to_string(),
+					Some(format!("run `ptr enable {name}`")),
+				)),
+				Ok(None) => checks.push((
+					"loaded by PowerToys Run".to_string(),
+					Some(
+						"run `ptr restart`, then check the PowerToys Run log if it's still missing"
+							.to_string(),
+					),
+				)),
+				Err(e) => checks.push((
+					e.to_string(),
+					Some("open PowerToys Run once so it writes its settings".to_string()),
+				)),
+			}
+		}
+
+		if let Some(id) = metadata.as_ref().and_then(|m| m.id.as_deref()) {
+			let others: Vec<_> = self
+				.duplicates()?
+				.into_iter()
+				.filter(|(dup, _)| dup.eq_ignore_ascii_case(id))
+				.flat_map(|(_, dirs)| dirs)
+				.map(|(path, _)| path)
+				.filter(|path| !path.ends_with(dir.file_name().unwrap_or_default()))
+				.collect();
+			if others.is_empty() {
+				checks.push((format!("no other directory has ID {id}"), None));
+			} else {
+				for other in others {
+					checks.push((
+						format!("{} has the same ID", other.display()),
+						Some("run `ptr diff --fix` to delete the copy".to_string()),
+					));
+				}
+			}
+		}
+		Ok(print_checks(&checks))
+	}
+
+	/// Directories under the plugin roots with the same plugin ID in plugin.json, by ID, with
+	/// whether each is managed by ptr. Directories without a readable ID are skipped.
+	fn duplicates(&self) -> Result<Vec<Duplicate>> {
+		let mut ids: BTreeMap<String, (String, Vec<(PathBuf, bool)>)> = BTreeMap::new();
+		for (root, dir) in self.installed_dirs()? {
+			let path = root.as_deref().unwrap_or(&self.ctx.plugin_path).join(&dir);
+			let Some(id) = read_plugin_metadata(&path).ok().and_then(|m| m.id) else {
+				continue;
+			};
+			let managed = self
+				.plugins
+				.iter()
+				.any(|(n, p)| p.root == root && same_dir_name(p.dir_name(n), &dir));
+			ids.entry(id.to_lowercase())
+				.or_insert_with(|| (id, vec![]))
+				.1
+				.push((path, managed));
+		}
+		Ok(ids
+			.into_values()
+			.filter(|(_, dirs)| dirs.len() > 1)
+			.collect())
+	}
+
+	/// Delete the unmanaged directories of `duplicates` that have a managed copy, after
+	/// confirmation, and drop the resolved ones.
+	fn remove_duplicates(&self, duplicates: &mut Vec<Duplicate>) -> Result<()> {
+		let mut strays: Vec<PathBuf> = vec![];
+		for dir in duplicates
+			.iter()
+			.filter(|(_, dirs)| dirs.iter().any(|(_, managed)| *managed))
+			.flat_map(|(_, dirs)| dirs.iter().filter(|(_, managed)| !managed))
+			.map(|(dir, _)| dir)
+		{
+			if confirm(
+				&t!(DeleteUnmanaged, dir.display()),
+				false,
+				self.ctx.assume_yes,
+			)? {
+				strays.push(dir.clone());
+			}
+		}
+		if strays.is_empty() {
+			return Ok(());
+		}
+		self.kill()?;
+		for dir in &strays {
+			let result = match dir.parent() {
+				Some(root) if !is_writable(root) => remove_dir_elevated(dir),
+				_ => polling::remove_dir_all(dir).map_err(anyhow::Error::from),
+			};
+			match result {
+				Ok(_) => remove!(dir.display()),
+				Err(e) => error!("Failed to delete {}: {}", dir.display(), e),
+			}
+		}
+		self.start();
+		for (_, dirs) in duplicates.iter_mut() {
+			dirs.retain(|(dir, _)| dir.exists());
+		}
+		duplicates.retain(|(_, dirs)| dirs.len() > 1);
+		Ok(())
+	}
+
+	/// Absolute directory of an installed plugin.
+	pub fn plugin_dir(&self, name: &str) -> Result<PathBuf> {
+		let Some(plugin) = self.plugins.get(name) else {
+			bail!(self.not_found(name));
+		};
+		if !plugin.is_installed(&self.ctx.plugin_path, name) {
+			bail!("Plugin {} is missing on disk", name);
+		}
+		Ok(plugin.path(&self.ctx.plugin_path, name))
+	}
+
+	/// Plugin directories other than the default one, `RunPlugins` of PowerToys if it exists and
+	/// none is configured.
+	fn other_roots(&self) -> Vec<PathBuf> {
+		if !self.plugin_paths.is_empty() {
+			return self.plugin_paths.clone();
+		}
+		self.machine_root()
+			.into_iter()
+			.filter(|p| p.is_dir())
+			.collect()
+	}
+
+	/// Plugin directory loaded for all users.
+	fn machine_root(&self) -> Option<PathBuf> {
+		self.plugin_paths
+			.first()
+			.cloned()
+			.or_else(|| self.pt_path.parent().map(|p| p.join("RunPlugins")))
+	}
+
+	/// Plugin directory to install to for `scope`, None for the default one.
+	fn scope_root(&self, scope: Scope) -> Result<Option<PathBuf>> {
+		match scope {
+			Scope::User => Ok(None),
+			Scope::Machine => self.machine_root().map(Some).ok_or(anyhow!(
+				"No machine plugin directory, set plugin_paths in version.toml"
+			)),
+		}
+	}
+
+	/// Directories of the installed plugins with their plugin directory, None for the default one.
+	/// Missing plugin directories other than the default one are skipped.
+	fn installed_dirs(&self) -> Result<Vec<(Option<PathBuf>, String)>> {
+		let mut dirs: Vec<_> = plugin_dirs(&self.ctx.plugin_path)?
+			.into_iter()
+			.map(|d| (None, d))
+			.collect();
+		for root in self.other_roots() {
+			if let Ok(other) = plugin_dirs(&root) {
+				dirs.extend(other.into_iter().map(|d| (Some(root.clone()), d)));
+			}
+		}
+		Ok(dirs)
+	}
+
+	/// Error message for a plugin not in config, with the closest name if any.
+	fn not_found(&self, name: &str) -> String {
+		match closest(name, self.plugins.keys().map(String::as_str)) {
+			Some(closest) => format!("Plugin {name} not found, did you mean {closest}?"),
+			None => format!("Plugin {name} not found"),
+		}
+	}
+
+	/// Print release notes after the installed version, `name` can be `owner/repo` for a plugin
+	/// not in config.
+	pub fn changelog(&self, name: &str, to: Option<&str>) -> Result<()> {
+		let (repo, from) = match self.plugins.get(name) {
+			Some(plugin) => (plugin.repo.as_str(), Some(plugin.version.as_str())),
+			None if name.contains('/') => (parse_repo(name), None),
+			None => bail!(self.not_found(name)),
+		};
+		let releases = get_releases(&self.github(), repo, from, to)?;
+		if releases.is_empty() {
+			up_to_date!(name, from.unwrap_or_default());
+			return Ok(());
+		}
+		for release in releases {
+			let date = release.published_at.as_deref().unwrap_or_default();
+			info!("{} ({})", release.tag_name, date.get(..10).unwrap_or(date));
+			for line in render_markdown(release.body.as_deref().unwrap_or_default()).lines() {
+				info!("  {}", line);
+			}
+			info!("");
+		}
+		Ok(())
+	}
+
+	pub fn auth_login(&mut self) -> Result<()> {
+		if let Some(token) = &self.token {
+			if confirm(&t!(MoveToken), true, self.ctx.assume_yes)? {
+				credential::write_token(&token.0)?;
+				self.token = None;
+				self.save()?;
+				info!("{}", t!(TokenMoved));
+				return Ok(());
+			}
+		}
+		let token = prompt(&t!(EnterToken), self.ctx.assume_yes)?;
+		if token.is_empty() {
+			bail!("No token entered");
+		}
+		credential::write_token(&token)?;
+		info!("{}", t!(TokenSaved));
+		Ok(())
+	}
+
+	pub fn auth_logout(&self) -> Result<()> {
+		credential::delete_token()?;
+		info!("{}", t!(TokenRemoved));
+		Ok(())
+	}
+
+	pub fn auth_status(&self) -> Result<()> {
+		let Some((token, source)) = self.token() else {
+			bail!("No token found, log in with `ptr auth login`");
+		};
+		let (login, remaining) = get_user(&GitHub::new(Some(&token)).offline(self.ctx.offline))?;
+		info!(
+			"{}",
+			t!(LoggedInAs, login.bright_cyan(), mask_token(&token), source)
+		);
+		if let Some(remaining) = remaining {
+			info!("{}", t!(RateLimit, remaining));
+		}
+		Ok(())
+	}
+
+	/// Set the `Disabled` flag of the plugins in PowerToys Run settings, restarting PowerToys
+	/// so it doesn't overwrite the change.
+	pub fn set_disabled(&self, names: Vec<String>, disabled: bool) -> Result<()> {
+		let plugins: Vec<_> = names
+			.iter()
+			.filter_map(|name| {
+				let Some(plugin) = self.plugins.get(name) else {
+					error!(self.not_found(name));
+					return None;
+				};
+				match read_plugin_metadata(&plugin.path(&self.ctx.plugin_path, name)) {
+					Ok(metadata) => Some((name, metadata)),
+					Err(e) => {
+						error!(e);
+						None
+					}
+				}
+			})
+			.collect();
+		if plugins.is_empty() {
+			return Ok(());
+		}
+
+		self.kill()?;
+		match RunSettings::load(&self.ctx.run_settings_path) {
+			Ok(mut settings) => {
+				for (name, metadata) in &plugins {
+					if settings.set_disabled(metadata, disabled) {
+						if disabled {
+							remove!(name);
+						} else {
+							add!(name, "enabled");
+						}
+					} else {
+						error!(
+							"{} has not been loaded by PowerToys Run yet, no settings found",
+							name
+						);
+					}
+				}
+				settings
+					.save(&self.ctx.run_settings_path)
+					.unwrap_or_else(|e| error!("Failed to save PowerToys Run settings: {}", e));
+			}
+			Err(e) => error!(e),
+		}
+		self.start();
+		Ok(())
+	}
+
+	/// GitHub token from `token` in config, Windows Credential Manager, or `GITHUB_TOKEN`,
+	/// along with where it's from.
+	fn token(&self) -> Option<(String, &'static str)> {
+		self.token_override
+			.clone()
+			.map(|t| (t.0, "--token"))
+			.or_else(|| self.token.clone().map(|t| (t.0, "version.toml")))
+			.or_else(|| credential::read_token().map(|t| (t, "Credential Manager")))
+			.or_else(|| {
+				env::var("GITHUB_TOKEN")
+					.ok()
+					.filter(|t| !t.is_empty())
+					.map(|t| (t, "GITHUB_TOKEN"))
+			})
+	}
+
+	/// Before PowerToys is killed, warn if GitHub's rate limit doesn't cover `plugins` and ask
+	/// to continue. With `--yes` only as many plugins as the limit covers are processed if
+	/// `partial`, otherwise it fails. Skipped if the limit can't be fetched.
+	///
+	/// # Returns
+	/// How many of the plugins to process.
+	fn check_rate_limit(&self, plugins: usize, partial: bool) -> Result<usize> {
+		if self.skip_network_check {
+			return Ok(plugins);
+		}
+		let Some(limit) = self.rate_limit() else {
+			return Ok(plugins);
+		};
+		let needed = plugins as u64 * REQUESTS_PER_PLUGIN;
+		if limit.remaining >= needed {
+			return Ok(plugins);
+		}
+		let hint = if self.token().is_none() {
+			", log in with `ptr auth login` for 5000 an hour"
+		} else {
+			""
+		};
+		warning!(
+			"GitHub allows {} more requests for {} minutes, {} plugins may need {}{}",
+			limit.remaining,
+			limit.minutes_to_reset(),
+			plugins,
+			needed,
+			hint
+		);
+		if !self.ctx.assume_yes {
+			return match confirm(&t!(ContinueAnyway), false, self.ctx.assume_yes)? {
+				true => Ok(plugins),
+				false => bail!("Cancelled"),
+			};
+		}
+		let covered = (limit.remaining / REQUESTS_PER_PLUGIN) as usize;
+		if !partial || covered == 0 {
+			bail!("Not enough requests left for this run, nothing was changed");
+		}
+		warning!(
+			"Processing the first {} plugins, run again after the limit resets for the rest",
+			covered
+		);
+		Ok(covered)
+	}
+
+	/// GitHub client with the token of this run, and the tokens of GitHub Enterprise Server
+	/// hosts.
+	fn github(&self) -> GitHub {
+		let hosts = self
+			.tokens
+			.iter()
+			.map(|(host, token)| (host.to_lowercase(), token.0.clone()))
+			.collect();
+		GitHub::new(self.token().map(|(t, _)| t).as_deref())
+			.with_hosts(hosts)
+			.offline(self.ctx.offline)
+	}
+
+	/// Rate limit of the configured token, None if it can't be fetched.
+	fn rate_limit(&self) -> Option<RateLimit> {
+		self.github().rate_limit().ok()
+	}
+
+	/// Fail fast before PowerToys is killed if the hosts of `repos` can't be reached.
+	fn check_network<'a>(&self, repos: impl IntoIterator<Item = &'a str>) -> Result<()> {
+		if self.skip_network_check {
+			return Ok(());
+		}
+		GitHub::new(None).offline(self.ctx.offline).probe(repos)
+	}
+
+	/// Repositories of the plugins in `names`.
+	fn repos<'a>(&'a self, names: impl IntoIterator<Item = &'a String>) -> Vec<&'a str> {
+		names
+			.into_iter()
+			.filter_map(|n| self.plugins.get(n))
+			.map(|p| p.repo.as_str())
+			.collect()
+	}
+
+	/// Fail before anything is changed if the plugin volume can't hold the assets `targets`
+	/// would be updated to, with the version to update to if any.
+	fn check_space<'a>(
+		&self,
+		targets: impl Iterator<Item = (&'a str, Option<&'a str>)>,
+		opts: &InstallOptions,
+	) -> Result<()> {
+		if self.skip_space_check {
+			return Ok(());
+		}
+		let size = targets
+			.filter_map(|(name, version)| {
+				let plugin = self.plugins.get(name)?;
+				Some(plugin.download_size(name, version, opts))
+			})
+			.sum();
+		check_free_space(&self.ctx.plugin_path, size)
+	}
+
+	fn install_options(&self, skip_validation: bool) -> InstallOptions {
+		InstallOptions {
+			arch: self.arch_override.as_ref().unwrap_or(&self.arch).clone(),
+			source: Box::new(self.github()),
+			pt_version: get_powertoys_version(&self.pt_path).ok(),
+			pt_dotnet: powertoys_dotnet_version(&self.pt_path),
+			strict_compat: self.strict_compat,
+			skip_validation,
+			skip_arch_check: self.skip_arch_check,
+			verbose: false,
+			plugin_tokens: self.token_override.is_none(),
+			plugin_path: self.ctx.plugin_path.clone(),
+			follow_renames: !self.no_follow_renames,
+			unblock: self.unblock,
+			hook_failure: self.hook_failure(),
+			swap_in_place: self.swap_in_place,
+			extract_limits: ExtractLimits::with_max_size_mb(
+				self.max_extract_size_override
+					.or(self.max_extract_size)
+					.unwrap_or(ExtractLimits::DEFAULT_MAX_SIZE_MB),
+			),
+			asset_cache: self.asset_cache.clone(),
+			assume_yes: self.ctx.assume_yes,
+			offline: self.ctx.offline,
+		}
+	}
+
+	/// Remove assets in `asset_cache` not used for `max_age_days`, then the least recently used
+	/// ones beyond `max_size_mb`.
+	pub fn clean_cache(&self, max_age_days: Option<u64>, max_size_mb: Option<u64>) -> Result<()> {
+		let Some(dir) = &self.asset_cache else {
+			bail!("asset_cache is not set in version.toml");
+		};
+		if !dir.exists() {
+			return Ok(());
+		}
+		let (count, freed) = asset_cache::prune(
+			dir,
+			max_age_days.map(|d| Duration::from_secs(d * 24 * 60 * 60)),
+			max_size_mb.map(|mb| mb * 1024 * 1024),
+		)?;
+		info!("{}", t!(CacheCleaned, count, format_size(freed)));
+		Ok(())
+	}
+}
+
+impl fmt::Display for Config {
+	/// `{:#}` shows the installed asset.
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{}",
+			self.table(
+				f.alternate(),
+				None,
+				RepoStyle::default(),
+				DiskColumns::default()
+			)
+		)
+	}
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum Arch {
+	#[serde(rename = "x64")]
+	X64,
+	#[serde(rename = "arm64")]
+	ARM64,
+}
+
+impl Arch {
+	/// Architecture of the running system.
+	pub fn detect() -> Result<Self> {
+		match env::consts::ARCH {
+			"x86_64" => Ok(Arch::X64),
+			"aarch64" => Ok(Arch::ARM64),
+			arch => bail!(
+				"Unsupported architecture '{}', set arch = \"x64\" or \"arm64\" in version.toml",
+				arch
+			),
+		}
+	}
+
+	pub const ALL: [Arch; 2] = [Arch::X64, Arch::ARM64];
+
+	/// Names of the architecture used in release assets.
+	pub fn aliases(&self) -> &'static [&'static str] {
+		match self {
+			Arch::X64 => &["x64", "x86_64"],
+			Arch::ARM64 => &["arm64", "aarch64"],
+		}
+	}
+}
+
+impl FromStr for Arch {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.to_lowercase();
+		Arch::ALL
+			.into_iter()
+			.find(|a| a.aliases().contains(&s.as_str()))
+			.ok_or_else(|| format!("unknown arch '{s}', expected x64 or arm64"))
+	}
+}
+
+impl fmt::Display for Arch {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Arch::X64 => write!(f, "x64"),
+			Arch::ARM64 => write!(f, "arm64"),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum RestartScope {
+	/// Restart the PowerToys runner with all modules.
+	#[default]
+	Runner,
+	/// Restart only the PowerToys Run launcher, leaving other modules running.
+	Launcher,
+}
+
+impl RestartScope {
+	/// Name of what is restarted, for messages.
+	fn name(&self) -> &'static str {
+		match self {
+			RestartScope::Runner => "PowerToys",
+			RestartScope::Launcher => "PowerToys Run",
+		}
+	}
+}
+
+/// Whether PowerToys is killed as administrator.
+///
+/// `true` and `false` from older configs are read as `always` and `never`, which are also
+/// written that way so older ptr can still read the config.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub enum Admin {
+	/// Only if PowerToys runs elevated and ptr doesn't.
+	#[default]
+	Auto,
+	Always,
+	Never,
+}
+
+impl Admin {
+	/// Whether to kill as administrator, `probe` tells if it's needed and is only called for
+	/// `auto`. Errors of `probe` fall back to elevating, which works in either case.
+	pub fn elevate(self, probe: impl FnOnce() -> Result<bool>) -> bool {
+		match self {
+			Admin::Always => true,
+			Admin::Never => false,
+			Admin::Auto => probe().unwrap_or(true),
+		}
+	}
+}
+
+impl FromStr for Admin {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"auto" => Ok(Admin::Auto),
+			"always" | "true" => Ok(Admin::Always),
+			"never" | "false" => Ok(Admin::Never),
+			_ => Err(format!(
+				"unknown admin '{s}', expected auto, always or never"
+			)),
+		}
+	}
+}
+
+impl Serialize for Admin {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Admin::Auto => serializer.serialize_str("auto"),
+			Admin::Always => serializer.serialize_bool(true),
+			Admin::Never => serializer.serialize_bool(false),
+		}
+	}
+}
+
+impl<'de> Deserialize<'de> for Admin {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		#[derive(Deserialize)]
+		#[serde(untagged)]
+		enum Value {
+			Bool(bool),
+			Name(String),
+		}
+		match Value::deserialize(deserializer)? {
+			Value::Bool(true) => Ok(Admin::Always),
+			Value::Bool(false) => Ok(Admin::Never),
+			Value::Name(name) => name.parse().map_err(serde::de::Error::custom),
+		}
+	}
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum HookFailure {
+	/// Report the failure and continue.
+	#[default]
+	Warn,
+	/// Abort the install or removal of the plugin.
+	Error,
+}
+
+/// Where `add` installs a plugin.
+#[derive(Clone, Copy, Default, Debug)]
+pub enum Scope {
+	/// The plugin directory of the current user.
+	#[default]
+	User,
+	/// The first of `plugin_paths`, loaded for all users.
+	Machine,
+}
+
+impl FromStr for Scope {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"user" => Ok(Scope::User),
+			"machine" => Ok(Scope::Machine),
+			_ => Err(format!("unknown scope '{s}', expected user or machine")),
+		}
+	}
+}
+
+/// What happens to PowerToys around a change of plugins.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RestartPlan {
+	/// Neither killed nor restarted, by `no_kill`.
+	Untouched,
+	/// Killed and left closed, by `no_restart`.
+	Killed,
+	Restarted,
+}
+
+impl RestartPlan {
+	/// `no_kill` wins over `no_restart`, since a PowerToys that isn't killed can't be restarted.
+	fn new(no_kill: bool, no_restart: bool) -> Self {
+		match (no_kill, no_restart) {
+			(true, _) => RestartPlan::Untouched,
+			(false, true) => RestartPlan::Killed,
+			(false, false) => RestartPlan::Restarted,
+		}
+	}
+}
+
+/// What `update --interactive` does with an outdated plugin.
+#[derive(Clone, Copy)]
+enum Choice {
+	Update,
+	Skip,
+	Pin,
+}
+
+impl Choice {
+	fn next(self) -> Self {
+		match self {
+			Choice::Update => Choice::Skip,
+			Choice::Skip => Choice::Pin,
+			Choice::Pin => Choice::Update,
+		}
+	}
+}
+
+impl fmt::Display for Choice {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Choice::Update => write!(f, "{}", "[update]".bright_green()),
+			Choice::Skip => write!(f, "{}", "[skip]  ".dimmed()),
+			Choice::Pin => write!(f, "{}", "[pin]   ".bright_yellow()),
+		}
+	}
+}
+
+/// Options shared by the plugin installs in one command.
+struct InstallOptions {
+	arch: Arch,
+	source: Box<dyn ReleaseSource>,
+	pt_version: Option<String>,
+	/// Major version of .NET bundled with PowerToys.
+	pt_dotnet: Option<u32>,
+	strict_compat: bool,
+	skip_validation: bool,
+	skip_arch_check: bool,
+	/// Print files changed by updates.
+	verbose: bool,
+	/// Use the token of a plugin instead of `source` if it has one.
+	plugin_tokens: bool,
+	/// Plugin directory of plugins without a `root`.
+	plugin_path: PathBuf,
+	/// Update the `repo` of plugins whose repository was renamed.
+	follow_renames: bool,
+	/// Remove the mark of the web from staged files.
+	unblock: bool,
+	/// See `Config::hook_failure`.
+	hook_failure: Option<HookFailure>,
+	/// See `Config::swap_in_place`.
+	swap_in_place: bool,
+	extract_limits: ExtractLimits,
+	/// See `Config::asset_cache`.
+	asset_cache: Option<PathBuf>,
+	/// See `Context::assume_yes`.
+	assume_yes: bool,
+	/// See `Context::offline`.
+	offline: bool,
+}
+
+impl InstallOptions {
+	/// Fail if the `ExecuteFileName` DLL is built for another arch, e.g. an asset named x64
+	/// containing ARM64 binaries. AnyCPU assemblies pass for both, a DLL that can't be read is
+	/// left to the layout check.
+	fn check_arch(&self, dir: &Path) -> Result<()> {
+		let Some(file) = read_plugin_metadata(dir)
+			.ok()
+			.and_then(|m| m.execute_file_name)
+		else {
+			return Ok(());
+		};
+		let Ok(pe) = pe_arch(&dir.join(&file)) else {
+			return Ok(());
+		};
+		if !pe.runs_on(&self.arch) {
+			bail!(
+				"Asset contains {} binaries but arch is {}, choose another asset with --pattern or \
+				 --arch, or use --skip-arch-check to install anyway",
+				pe,
+				self.arch
+			);
+		}
+		Ok(())
+	}
+
+	/// Check the newly extracted plugin layout, and warn if it requires a newer PowerToys or
+	/// .NET. Requiring a newer PowerToys is an error in strict mode.
+	fn validate(&self, name: &str, dir: &Path) -> Result<()> {
+		if !self.skip_validation {
+			validate_plugin_dir(dir)
+				.map_err(|e| anyhow!("{}, use --skip-validation to install anyway", e))?;
+		}
+		if !self.skip_arch_check {
+			self.check_arch(dir)?;
+		}
+		self.check_dotnet(name, dir);
+
+		let Some(pt_version) = &self.pt_version else {
+			return Ok(());
+		};
+		let Some(required) = read_plugin_metadata(dir)
+			.ok()
+			.and_then(|m| m.min_power_toys_version)
+		else {
+			return Ok(());
+		};
+		if compare_versions(pt_version, &required).is_lt() {
+			let msg = format!(
+				"{} requires PowerToys {} or newer, but {} is installed",
+				name, required, pt_version
+			);
+			if self.strict_compat {
+				bail!(msg);
+			}
+			warning!(msg);
+		}
+		Ok(())
+	}
+
+	/// Warn if the plugin targets a newer .NET than the one bundled with PowerToys, which fails
+	/// to load it.
+	fn check_dotnet(&self, name: &str, dir: &Path) {
+		let (Some(pt_dotnet), Some(plugin_dotnet)) = (self.pt_dotnet, plugin_dotnet_version(dir))
+		else {
+			return;
+		};
+		if plugin_dotnet > pt_dotnet {
+			warning!(
+				"{} targets .NET {}, but PowerToys {} bundles .NET {} and may not load it",
+				name,
+				plugin_dotnet,
+				self.pt_version.as_deref().unwrap_or("installed"),
+				pt_dotnet
+			);
+		}
+	}
+}
+
+/// Directory in the plugin directory to extract new versions before replacing the installed
+/// plugin.
+const STAGING_DIR: &str = ".staging";
+
+/// Space needed per byte of asset, the archive and its contents at up to 4x compression.
+const SPACE_FACTOR: u64 = 5;
+/// Space kept free on top of the assets.
+const SPACE_MARGIN: u64 = 64 * 1024 * 1024;
+
+/// Fail if the volume of `plugin_path`, which also holds the downloads and `STAGING_DIR`,
+/// can't hold assets of `size` bytes.
+fn check_free_space(plugin_path: &Path, size: u64) -> Result<()> {
+	if size == 0 {
+		return Ok(());
+	}
+	let free = match free_space(plugin_path) {
+		Ok(free) => free,
+		Err(e) => {
+			warning!("Failed to check free disk space: {}", e);
+			return Ok(());
+		}
+	};
+	let needed = size * SPACE_FACTOR + SPACE_MARGIN;
+	if free < needed {
+		bail!(
+			"Not enough disk space for {} of plugins, {} needed and {} free, nothing was changed. \
+			 Free some space or use --skip-space-check",
+			format_size(size),
+			format_size(needed),
+			format_size(free)
+		);
+	}
+	Ok(())
+}
+
+/// Replace glob patterns in `names` with the `candidates` they match, printing the matches.
+/// Plain names are kept as is.
+fn expand_globs<'a>(names: &[String], candidates: impl Iterator<Item = &'a String>) -> Vec<String> {
+	let candidates: Vec<&String> = candidates.collect();
+	let mut expanded = vec![];
+	for name in names {
+		if !name.contains(['*', '?']) {
+			expanded.push(name.clone());
+			continue;
+		}
+		let matched: Vec<String> = candidates
+			.iter()
+			.filter(|c| glob_match(name, c))
+			.map(|c| c.to_string())
+			.collect();
+		if matched.is_empty() {
+			warning!("{} matches nothing", name);
+		} else {
+			info!("{} {}", format!("{name}:").dimmed(), matched.join(", "));
+		}
+		expanded.extend(matched);
+	}
+	expanded
+}
+
+/// Directories in `root` except `STAGING_DIR` and the ones set aside, sorted
+/// case-insensitively.
+fn plugin_dirs(root: &Path) -> Result<Vec<String>> {
+	let mut dirs: Vec<String> = fs::read_dir(root)?
+		.filter_map(|e| e.ok())
+		.filter(|e| {
+			let path = e.path();
+			path.is_dir() && !is_swapped_dir(&path) && !safe_mode::is_disabled(&path)
+		})
+		.map(|e| e.file_name().to_string_lossy().into_owned())
+		.filter(|d| d != STAGING_DIR)
+		.collect();
+	dirs.sort_by_key(|d| d.to_lowercase());
+	Ok(dirs)
+}
+
+/// plugin.json of a directory swapped aside is renamed to this, so PowerToys doesn't load the
+/// old version next to the new one when it starts before the directory is removed.
+const SWAPPED_MARKER: &str = "plugin.json.ptr-old";
+
+/// Whether the directory is an old version left by `swap_in_place`.
+fn is_swapped_dir(path: &Path) -> bool {
+	path.file_name()
+		.is_some_and(|n| n.to_string_lossy().contains(".old-"))
+		&& path.join(SWAPPED_MARKER).is_file()
+}
+
+fn staging_path(plugin_path: &Path, name: &str) -> PathBuf {
+	plugin_path.join(STAGING_DIR).join(name)
+}
+
+/// Remove the staged plugin, and `STAGING_DIR` itself if it's empty.
+fn clean_staging(plugin_path: &Path, name: &str) -> Result<()> {
+	let staging = staging_path(plugin_path, name);
+	if staging.exists() {
+		polling::remove_dir_all(&staging)?;
+	}
+	_ = fs::remove_dir(plugin_path.join(STAGING_DIR));
+	Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Plugin {
+	repo: String,
+	version: String,
+	/// When the release of `version` was published, recorded on the next install or update.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	released: Option<String>,
+	/// Directory in the plugin directory if it's different from the plugin name.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	dir: Option<String>,
+	/// Regex matched against asset names instead of the arch.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pattern: Option<String>,
+	/// Name of the installed asset, preferred when the same version is installed again.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	asset: Option<String>,
+	/// Files in the plugin directory kept as is during updates.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	hold: Vec<String>,
+	/// GitHub token for this plugin, used instead of the global token.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	token: Option<Token>,
+	/// Command run in the plugin directory after it's installed or updated.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	post_install: Option<String>,
+	/// Command run in the plugin directory before it's removed.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pre_remove: Option<String>,
+	/// Plugin directory the plugin is installed in if it's not the default one.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	root: Option<PathBuf>,
+	/// Report failed updates in one dimmed line, e.g. for an archived repository.
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	ignore_errors: bool,
+}
+
+impl Plugin {
+	/// Add a plugin with the specified version, None for the latest version.
+	///
+	/// The plugin is installed to `dir` in `root`, or the `Name` in plugin.json in the plugin
+	/// directory of `opts` if not specified. `taken` are the directories used by other plugins.
+	#[allow(clippy::too_many_arguments)]
+	fn add(
+		name: &str,
+		repo: String,
+		version: Option<String>,
+		dir: Option<String>,
+		filter: &AssetFilter,
+		token: Option<Token>,
+		root: Option<PathBuf>,
+		opts: &InstallOptions,
+		taken: &[String],
+	) -> Result<Self> {
+		let result = Self::stage(
+			name,
+			&repo,
+			version.as_deref(),
+			None,
+			filter,
+			token.as_ref(),
+			opts,
+		)
+		.and_then(|downloaded| {
+			let dir = Self::resolve_dir(&opts.plugin_path, name, dir, taken, opts.assume_yes)?;
+			let target = root.as_deref().unwrap_or(&opts.plugin_path).join(&dir);
+			Self::replace_with(&target, &staging_path(&opts.plugin_path, name))?;
+			Ok((downloaded, dir))
+		});
+		clean_staging(&opts.plugin_path, name)?;
+		let (
+			Downloaded {
+				tag,
+				asset,
+				repo: renamed,
+				released,
+			},
+			dir,
+		) = result?;
+		if dir != name {
+			info!("  {} {}", "directory:".dimmed(), dir);
+		}
+		Ok(Self {
+			repo: renamed.unwrap_or(repo),
+			version: tag,
+			released,
+			dir: (dir != name).then_some(dir),
+			pattern: filter.pattern.map(String::from),
+			asset,
+			hold: vec![],
+			token,
+			post_install: None,
+			pre_remove: None,
+			root,
+			ignore_errors: false,
+		})
+	}
+
+	/// Update the plugin to `version`, the latest if None, as an outcome for the run.
+	fn sync(&mut self, name: &str, version: Option<&str>, opts: &InstallOptions) -> SyncOutcome {
+		let from = self.version.clone();
+		match self.update_to_version(name, version, opts) {
+			Ok(Some(diff)) => SyncOutcome::Updated {
+				from,
+				to: self.version.clone(),
+				diff,
+			},
+			Ok(None) => SyncOutcome::UpToDate {
+				version: self.version.clone(),
+			},
+			Err(e) => SyncOutcome::Failed {
+				from: Some(from),
+				error: self.explain_failure(e, opts),
+				ignored: self.ignore_errors,
+			},
+		}
+	}
+
+	/// Update the plugin to `version`, the latest if None.
+	/// Return the changed files if the version is updated.
+	fn update_to_version(
+		&mut self,
+		name: &str,
+		version: Option<&str>,
+		opts: &InstallOptions,
+	) -> Result<Option<FileDiff>> {
+		let filter = AssetFilter {
+			name: Some(name),
+			pattern: self.pattern.as_deref(),
+			installed: self.asset.as_deref().map(|a| (self.version.as_str(), a)),
+			assume_yes: opts.assume_yes,
+		};
+		if let Some(old) = self.fix_dir_name(&opts.plugin_path, name).map_err(|e| {
+			anyhow!(
+				"directory name differs from '{}' and renaming it failed, fix it with `ptr diff --fix`: {}",
+				self.dir_name(name),
+				e
+			)
+		})? {
+			warning!(
+				"{}: renamed directory '{}' to '{}'",
+				name,
+				old,
+				self.dir_name(name)
+			);
+		}
+		// reinstall the recorded version if the directory is gone
+		let (version, current_version) = if self.is_installed(&opts.plugin_path, name) {
+			(version, Some(self.version.as_str()))
+		} else {
+			warning!("{}: missing on disk, reinstalling", name);
+			(version.or(Some(&self.version)), None)
+		};
+		let result = Self::stage(
+			name,
+			&self.repo,
+			version,
+			current_version,
+			&filter,
+			self.token.as_ref(),
+			opts,
+		)
+		.and_then(|downloaded| {
+			if downloaded.asset.is_none() {
+				return Ok((downloaded, None));
+			}
+			let (dir, staging) = (
+				self.path(&opts.plugin_path, name),
+				staging_path(&opts.plugin_path, name),
+			);
+			self.keep_held(name, &dir, &staging)?;
+			let diff = FileDiff::between(&dir, &staging)?;
+			if opts.swap_in_place && dir.exists() {
+				self.swap_with(&dir, &staging)?;
+			} else {
+				Self::replace_with(&dir, &staging)?;
+			}
+			Ok((downloaded, Some(diff)))
+		});
+		clean_staging(&opts.plugin_path, name)?;
+		let (
+			Downloaded {
+				tag,
+				asset,
+				repo,
+				released,
+			},
+			diff,
+		) = result?;
+		// kept if the API leaves it out for the same release, backfilled otherwise
+		if released.is_some() || tag != self.version {
+			self.released = released;
+		}
+		self.version = tag;
+		if let Some(repo) = repo {
+			self.repo = repo;
+		}
+		if asset.is_some() {
+			self.asset = asset;
+			self.run_hook(
+				&opts.plugin_path,
+				name,
+				"post_install",
+				self.post_install.as_deref(),
+				opts.hook_failure,
+				opts.verbose,
+			)?;
+		}
+		Ok(diff)
+	}
+
+	/// Print the release the plugin would be updated to, see `print_plan`.
+	fn plan(&self, name: &str, version: Option<&str>, opts: &InstallOptions) -> Result<bool> {
+		let filter = AssetFilter {
+			name: Some(name),
+			pattern: self.pattern.as_deref(),
+			installed: self.asset.as_deref().map(|a| (self.version.as_str(), a)),
+			assume_yes: opts.assume_yes,
+		};
+		let (version, current_version) = if self.is_installed(&opts.plugin_path, name) {
+			(version, Some(self.version.as_str()))
+		} else {
+			warning!("{}: missing on disk, would be reinstalled", name);
+			(version.or(Some(&self.version)), None)
+		};
+		Self::print_plan(
+			name,
+			&self.repo,
+			version,
+			current_version,
+			&filter,
+			self.token.as_ref(),
+			opts,
+		)
+	}
+
+	/// Size of the asset the plugin would be updated to, see `asset_size`.
+	fn download_size(&self, name: &str, version: Option<&str>, opts: &InstallOptions) -> u64 {
+		let filter = AssetFilter {
+			name: Some(name),
+			pattern: self.pattern.as_deref(),
+			installed: self.asset.as_deref().map(|a| (self.version.as_str(), a)),
+			assume_yes: opts.assume_yes,
+		};
+		let (version, current_version) = if self.is_installed(&opts.plugin_path, name) {
+			(version, Some(self.version.as_str()))
+		} else {
+			(version.or(Some(&self.version)), None)
+		};
+		Self::asset_size(
+			&self.repo,
+			version,
+			current_version,
+			&filter,
+			self.token.as_ref(),
+			opts,
+		)
+	}
+
+	/// Size of the asset that would be installed, 0 if `current_version` is already the release
+	/// or the release can't be fetched, which is reported when it's installed.
+	fn asset_size(
+		repo: &str,
+		version: Option<&str>,
+		current_version: Option<&str>,
+		filter: &AssetFilter,
+		token: Option<&Token>,
+		opts: &InstallOptions,
+	) -> u64 {
+		let plugin_source = Self::token_source(token, opts);
+		let source = match &plugin_source {
+			Some(source) => source as &dyn ReleaseSource,
+			None => opts.source.as_ref(),
+		};
+		select_release(source, repo, version, &opts.arch, current_version, filter)
+			.ok()
+			.and_then(|s| s.asset().map(|(_, size)| size))
+			.unwrap_or(0)
+	}
+
+	/// Print the version and asset that would be installed, without downloading it.
+	/// Return false if `current_version` is already the release.
+	fn print_plan(
+		name: &str,
+		repo: &str,
+		version: Option<&str>,
+		current_version: Option<&str>,
+		filter: &AssetFilter,
+		token: Option<&Token>,
+		opts: &InstallOptions,
+	) -> Result<bool> {
+		let plugin_source = Self::token_source(token, opts);
+		let source = match &plugin_source {
+			Some(source) => source as &dyn ReleaseSource,
+			None => opts.source.as_ref(),
+		};
+		let selection = select_release(source, repo, version, &opts.arch, current_version, filter)
+			.map_err(|e| match &plugin_source {
+				Some(_) => anyhow!("{} (with the token of {})", e, name),
+				None => e,
+			})?;
+		if let Some(current) = selection
+			.repo
+			.as_ref()
+			.filter(|c| !c.eq_ignore_ascii_case(repo))
+		{
+			warning!("{}: {} is now {}", name, repo, current);
+		}
+		if selection.up_to_date {
+			return Ok(false);
+		}
+		info!(
+			"  {} {} -> {}",
+			"version:".dimmed(),
+			current_version.unwrap_or("none"),
+			selection.tag
+		);
+		match selection.asset() {
+			Some((asset, size)) => {
+				info!("  {} {} ({})", "asset:".dimmed(), asset, format_size(size))
+			}
+			None => info!(
+				"  {} {}",
+				"asset:".dimmed(),
+				"none matches, you would be asked to choose".bright_yellow()
+			),
+		}
+		Ok(true)
+	}
+
+	/// Name an archived or missing repository as the reason of a failed update, since GitHub
+	/// only answers 404 for the release then.
+	fn explain_failure(&self, e: anyhow::Error, opts: &InstallOptions) -> anyhow::Error {
+		let plugin_source = Self::token_source(self.token.as_ref(), opts);
+		let source = match &plugin_source {
+			Some(source) => source as &dyn ReleaseSource,
+			None => opts.source.as_ref(),
+		};
+		let hint = if self.ignore_errors {
+			String::new()
+		} else {
+			", set ignore_errors = true on the plugin to silence this".to_string()
+		};
+		match source.repo_state(&self.repo) {
+			Some(RepoState::Archived) => {
+				anyhow!("repository {} is archived{} ({})", self.repo, hint, e)
+			}
+			Some(RepoState::Unavailable) => anyhow!(
+				"repository {} is deleted or private{} ({})",
+				self.repo,
+				hint,
+				e
+			),
+			None => e,
+		}
+	}
+
+	/// GitHub client with the token of the plugin, unless `--token` is given.
+	fn token_source(token: Option<&Token>, opts: &InstallOptions) -> Option<GitHub> {
+		token
+			.filter(|_| opts.plugin_tokens)
+			.map(|t| GitHub::for_plugin(&t.0).offline(opts.offline))
+	}
+
+	/// Copy held files from the installed directory over the staged ones.
+	fn keep_held(&self, name: &str, dir: &Path, staging: &Path) -> Result<()> {
+		for file in &self.hold {
+			let (old, new) = (dir.join(file), staging.join(file));
+			if !old.is_file() {
+				continue;
+			}
+			if new.exists() && fs::read(&new)? != fs::read(&old)? {
+				warning!(
+					"{}: keeping held file {}, the new release changes it",
+					name,
+					file
+				);
+			}
+			if let Some(parent) = new.parent() {
+				fs::create_dir_all(parent)?;
+			}
+			fs::copy(&old, &new)?;
+		}
+		Ok(())
+	}
+
+	/// Run `command` of the `hook` in the plugin directory, skipped if `hook_failure` is None.
+	/// A failed hook is only an error if `hook_failure` is `error`.
+	fn run_hook(
+		&self,
+		plugin_path: &Path,
+		name: &str,
+		hook: &str,
+		command: Option<&str>,
+		hook_failure: Option<HookFailure>,
+		verbose: bool,
+	) -> Result<()> {
+		let Some(command) = command else {
+			return Ok(());
+		};
+		let Some(hook_failure) = hook_failure else {
+			info!("  {} {} skipped", format!("{hook}:").dimmed(), command);
+			return Ok(());
+		};
+		info!("  {} {}", format!("{hook}:").dimmed(), command);
+		match run_hook(command, &self.path(plugin_path, name)) {
+			Ok(output) => {
+				if verbose && !output.is_empty() {
+					info!("{}", output.trim_end().dimmed());
+				}
+				Ok(())
+			}
+			Err(e) => match hook_failure {
+				HookFailure::Warn => {
+					warning!("{}: {} hook failed: {}", name, hook, e);
+					Ok(())
+				}
+				HookFailure::Error => bail!("{} hook failed: {}", hook, e),
+			},
+		}
+	}
+
+	/// Whether the plugin directory exists and has plugin.json.
+	fn is_installed(&self, plugin_path: &Path, name: &str) -> bool {
+		self.path(plugin_path, name).join("plugin.json").is_file()
+	}
+
+	/// Absolute directory of the plugin, in `root` or else in `plugin_path`.
+	fn path(&self, plugin_path: &Path, name: &str) -> PathBuf {
+		self.root
+			.as_deref()
+			.unwrap_or(plugin_path)
+			.join(self.dir_name(name))
+	}
+
+	/// Directory on disk whose name differs from the configured one only by case or
+	/// surrounding spaces, None if the name matches exactly or there's no such directory.
+	fn misnamed_dir(&self, plugin_path: &Path, name: &str) -> Option<PathBuf> {
+		let dir_name = self.dir_name(name);
+		let root = long_path(self.root.as_deref().unwrap_or(plugin_path));
+		fs::read_dir(root).ok()?.flatten().find_map(|entry| {
+			let actual = entry.file_name().to_string_lossy().into_owned();
+			(actual != dir_name && same_dir_name(&actual, dir_name) && entry.path().is_dir())
+				.then(|| entry.path())
+		})
+	}
+
+	/// Rename a misnamed directory to the configured name.
+	///
+	/// # Returns
+	/// The name the directory had, None if it already matched.
+	fn fix_dir_name(&self, plugin_path: &Path, name: &str) -> Result<Option<String>> {
+		let Some(actual) = self.misnamed_dir(plugin_path, name) else {
+			return Ok(None);
+		};
+		let old = actual
+			.file_name()
+			.map(|n| n.to_string_lossy().into_owned())
+			.unwrap_or_default();
+		let target = long_path(&self.path(plugin_path, name));
+		if old.eq_ignore_ascii_case(self.dir_name(name)) {
+			// a case-only rename, go through a temporary name since the target already resolves
+			let tmp = target.with_extension(format!("ptr-rename-{}", std::process::id()));
+			polling::rename(&actual, &tmp)?;
+			polling::rename(&tmp, &target)?;
+		} else {
+			polling::rename(&actual, &target)?;
+		}
+		Ok(Some(old))
+	}
+
+	/// Directory name in the plugin directory.
+	fn dir_name<'a>(&'a self, name: &'a str) -> &'a str {
+		self.dir.as_deref().unwrap_or(name)
+	}
+
+	/// Extract the release into `STAGING_DIR` and validate it.
+	/// Nothing is extracted if `current_version` is the latest.
+	///
+	/// # Returns
+	/// The release that was downloaded, its `repo` is only set if the repository was renamed
+	/// and renames are followed.
+	fn stage(
+		name: &str,
+		repo: &str,
+		version: Option<&str>,
+		current_version: Option<&str>,
+		filter: &AssetFilter,
+		token: Option<&Token>,
+		opts: &InstallOptions,
+	) -> Result<Downloaded> {
+		clean_staging(&opts.plugin_path, name)?;
+		let staging = staging_path(&opts.plugin_path, name);
+		// the plugin token is ignored with `--token`
+		let plugin_source = Self::token_source(token, opts);
+		let source = match &plugin_source {
+			Some(source) => source as &dyn ReleaseSource,
+			None => opts.source.as_ref(),
+		};
+		let mut downloaded = download_release(
+			source,
+			&staging,
+			repo,
+			version,
+			&opts.arch,
+			current_version,
+			filter,
+			opts.asset_cache.as_deref(),
+			&opts.extract_limits,
+		)
+		.map_err(|e| match &plugin_source {
+			Some(_) => anyhow!("{} (with the token of {})", e, name),
+			None => e,
+		})?;
+		downloaded.repo = Self::renamed(name, repo, downloaded.repo.take(), opts);
+		if downloaded.asset.is_some() {
+			opts.validate(name, &staging)?;
+			if opts.unblock {
+				let count = unblock(&staging)?;
+				if opts.verbose && count > 0 {
+					info!("  {} {} files", "unblocked:".dimmed(), count);
+				}
+			}
+		}
+		Ok(downloaded)
+	}
+
+	/// The new name of `repo` if GitHub publishes its releases under `current` and renames are
+	/// followed.
+	fn renamed(
+		name: &str,
+		repo: &str,
+		current: Option<String>,
+		opts: &InstallOptions,
+	) -> Option<String> {
+		let current = current.filter(|c| !c.eq_ignore_ascii_case(repo))?;
+		if !opts.follow_renames {
+			warning!(
+				"{}: {} is now {}, kept since no_follow_renames is set",
+				name,
+				repo,
+				current
+			);
+			return None;
+		}
+		warning!("{}: {} is now {}, updating repo", name, repo, current);
+		Some(current)
+	}
+
+	/// Choose the directory for the staged plugin, in order:
+	/// `dir`, an existing directory with the same plugin ID if the user adopts it,
+	/// `Name` in plugin.json if the user accepts it, and the plugin name.
+	fn resolve_dir(
+		plugin_path: &Path,
+		name: &str,
+		dir: Option<String>,
+		taken: &[String],
+		assume_yes: bool,
+	) -> Result<String> {
+		let dir = match dir {
+			Some(dir) => dir,
+			None => match read_plugin_metadata(&staging_path(plugin_path, name)) {
+				Ok(metadata) => {
+					// some plugins look up resources relative to a directory named like `Name`
+					let canonical = if metadata.name == name
+						|| !is_valid_dir_name(&metadata.name)
+						|| !confirm(
+							&t!(UseNameAsDir, name, format!("{:?}", metadata.name)),
+							true,
+							assume_yes,
+						)? {
+						name.to_string()
+					} else {
+						metadata.name
+					};
+					let existing = metadata
+						.id
+						.and_then(|id| find_plugin_dir(plugin_path, &id, &canonical))
+						.filter(|d| !taken.iter().any(|t| t.eq_ignore_ascii_case(d)));
+					match existing {
+						Some(existing) => {
+							if confirm(&t!(AdoptExisting, name, existing), true, assume_yes)? {
+								existing
+							} else {
+								warning!(
+									"{} and {} contain the same plugin, PowerToys may load either",
+									existing,
+									canonical
+								);
+								canonical
+							}
+						}
+						None => canonical,
+					}
+				}
+				Err(_) => name.to_string(),
+			},
+		};
+		check_dir_name(&dir)?;
+		if taken.iter().any(|t| t.eq_ignore_ascii_case(&dir)) {
+			bail!("Directory {} is used by another plugin", dir);
+		}
+		Ok(dir)
+	}
+
+	/// Replace `target` with the staged directory, restoring it from `<dir>.old` next to it if
+	/// the swap fails. The swap runs as administrator if the plugin directory isn't writable.
+	fn replace_with(target: &Path, staging: &Path) -> Result<()> {
+		let (Some(root), Some(dir)) = (target.parent(), target.file_name()) else {
+			bail!("Invalid plugin directory: {}", target.display());
+		};
+		let mut backup = dir.to_os_string();
+		backup.push(".old");
+		// next to the target, a machine-wide plugin directory may be on another volume and
+		// the elevated swap can't write to the staging directory of the user
+		let backup = root.join(backup);
+		if !is_writable(root) {
+			return replace_dir_elevated(target, staging, &backup);
+		}
+		if !target.exists() {
+			polling::rename(staging, target)?;
+			return Ok(());
+		}
+
+		if backup.exists() {
+			polling::remove_dir_all(&backup)?;
+		}
+		polling::rename(target, &backup)?;
+		if let Err(e) = polling::rename(staging, target) {
+			polling::rename(&backup, target)?;
+			return Err(e.into());
+		}
+		polling::remove_dir_all(&backup)?;
+		Ok(())
+	}
+
+	/// Replace `target` with the staged directory while PowerToys may have it loaded: Windows
+	/// allows renaming a directory whose DLLs are mapped, so it's renamed to
+	/// `<dir>.old-<version>` next to it and removed if nothing holds it, otherwise by
+	/// `Config::clean_swapped` on a later run.
+	fn swap_with(&self, target: &Path, staging: &Path) -> Result<()> {
+		let (Some(root), Some(dir)) = (target.parent(), target.file_name()) else {
+			bail!("Invalid plugin directory: {}", target.display());
+		};
+		if !is_writable(root) {
+			bail!(
+				"swap_in_place needs a writable plugin directory, {} isn't",
+				root.display()
+			);
+		}
+		let mut old = dir.to_os_string();
+		old.push(format!(".old-{}", self.version));
+		let mut old = root.join(old);
+		// a leftover of the same version that is still loaded
+		if old.exists() && fs::remove_dir_all(&old).is_err() {
+			old.as_mut_os_string()
+				.push(format!("-{}", std::process::id()));
+		}
+		polling::rename(target, &old)?;
+		if let Err(e) = polling::rename(staging, target) {
+			polling::rename(&old, target)?;
+			return Err(e.into());
+		}
+		// marked first, a partial removal may already have deleted plugin.json
+		polling::rename(old.join("plugin.json"), old.join(SWAPPED_MARKER))?;
+		_ = fs::remove_dir_all(&old);
+		Ok(())
+	}
+
+	/// Remove the plugin directory.
+	fn remove(
+		&self,
+		plugin_path: &Path,
+		name: &str,
+		hook_failure: Option<HookFailure>,
+	) -> Result<()> {
+		self.run_hook(
+			plugin_path,
+			name,
+			"pre_remove",
+			self.pre_remove.as_deref(),
+			hook_failure,
+			false,
+		)?;
+		// the directory may only differ by case or a trailing space from the configured name
+		let dir = self
+			.misnamed_dir(plugin_path, name)
+			.unwrap_or_else(|| self.path(plugin_path, name));
+		match dir.parent() {
+			Some(root) if !is_writable(root) => remove_dir_elevated(&dir)?,
+			_ => polling::remove_dir_all(&dir)?,
+		}
+		Ok(())
+	}
+}
+
+/// How `list` shows the repository of plugins.
+#[derive(Default, Clone, Copy)]
+pub struct RepoStyle {
+	/// Show `https://github.com/<repo>` instead of `<repo>`.
+	pub url: bool,
+	/// Make the repository a link in terminals supporting OSC 8 hyperlinks.
+	pub hyperlinks: bool,
+}
+
+/// Columns `list` reads from the plugin.json of each plugin.
+#[derive(Default, Clone, Copy)]
+pub struct DiskColumns {
+	/// The version in plugin.json, highlighted if it differs from the recorded one.
+	pub installed: bool,
+	/// The author and description in plugin.json.
+	pub long: bool,
+}
+
+/// What happened to one plugin in `update`, `update --all` or `import`.
+#[derive(Debug)]
+enum SyncOutcome {
+	/// Installed by `import`, `from` is the version in the imported config.
+	Installed {
+		from: String,
+		to: String,
+	},
+	Updated {
+		from: String,
+		to: String,
+		diff: FileDiff,
+	},
+	UpToDate {
+		version: String,
+	},
+	SkippedPinned {
+		version: String,
+	},
+	SkippedExcluded {
+		version: String,
+	},
+	/// `ignored` if the plugin has `ignore_errors`, which keeps it out of the summary.
+	Failed {
+		from: Option<String>,
+		error: anyhow::Error,
+		ignored: bool,
+	},
+}
+
+impl SyncOutcome {
+	/// Whether the plugin is at the version it was synced to.
+	fn succeeded(&self) -> bool {
+		matches!(
+			self,
+			SyncOutcome::Installed { .. }
+				| SyncOutcome::Updated { .. }
+				| SyncOutcome::UpToDate { .. }
+		)
+	}
+}
+
+/// Files that differ between two plugin directories, relative to the directories.
+#[derive(Default, Debug)]
+struct FileDiff {
+	changed: Vec<String>,
+	added: Vec<String>,
+	removed: Vec<String>,
+}
+
+impl FileDiff {
+	fn between(old: &Path, new: &Path) -> Result<Self> {
+		let (old_files, new_files) = (list_files(old)?, list_files(new)?);
+		let mut diff = Self::default();
+		for file in &new_files {
+			if !old_files.contains(file) {
+				diff.added.push(file.clone());
+			} else if fs::read(old.join(file))? != fs::read(new.join(file))? {
+				diff.changed.push(file.clone());
+			}
+		}
+		diff.removed = old_files
+			.into_iter()
+			.filter(|f| !new_files.contains(f))
+			.collect();
+		Ok(diff)
+	}
+
+	fn print(&self) {
+		for (symbol, files) in [
+			("~".bright_yellow(), &self.changed),
+			("+".bright_green(), &self.added),
+			("-".bright_red(), &self.removed),
+		] {
+			for file in files {
+				info!("    {} {}", symbol, file);
+			}
+		}
+	}
+}
+
+/// All files in `dir` recursively, sorted and relative to `dir`.
+fn list_files(dir: &Path) -> Result<Vec<String>> {
+	let mut files = vec![];
+	if !dir.exists() {
+		return Ok(files);
+	}
+	let mut dirs = vec![dir.to_path_buf()];
+	while let Some(current) = dirs.pop() {
+		for entry in fs::read_dir(&current)? {
+			let path = entry?.path();
+			if path.is_dir() {
+				dirs.push(path);
+			} else if let Ok(relative) = path.strip_prefix(dir) {
+				files.push(relative.to_string_lossy().into_owned());
+			}
+		}
+	}
+	files.sort();
+	Ok(files)
+}
+
+/// Print the checks of `explain`, a check failed if it has a remedy.
+///
+/// # Returns
+/// Whether any check failed.
+fn print_checks(checks: &[(String, Option<String>)]) -> bool {
+	for (check, remedy) in checks {
+		match remedy {
+			None => print_message!("ok  ", bright_green, check),
+			Some(remedy) => {
+				print_message!("fail", bright_red, check);
+				info!("     {}", remedy.dimmed());
+			}
+		}
+	}
+	checks.iter().any(|(_, remedy)| remedy.is_some())
+}
+
+/// Number of files in `dir` with a `Zone.Identifier` stream.
+fn blocked_files(dir: &Path) -> Result<usize> {
+	Ok(list_files(dir)?
+		.into_iter()
+		.filter(|file| {
+			let mut stream = dir.join(file).into_os_string();
+			stream.push(":Zone.Identifier");
+			Path::new(&stream).exists()
+		})
+		.count())
+}
+
+/// Delete the `Zone.Identifier` stream of all files in `dir`.
+/// Return the number of files that had one.
+fn unblock(dir: &Path) -> Result<usize> {
+	let mut count = 0;
+	for file in list_files(dir)? {
+		let mut stream = dir.join(file).into_os_string();
+		stream.push(":Zone.Identifier");
+		match fs::remove_file(stream) {
+			Ok(_) => count += 1,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+			Err(e) => return Err(e.into()),
+		}
+	}
+	Ok(count)
+}
+
+/// Whether `name` can be used as the directory of a plugin, a single path component inside
+/// the plugin directory. `%` is refused too, the directory may be changed by an elevated
+/// cmd.exe script that would expand it.
+fn is_valid_dir_name(name: &str) -> bool {
+	!name.trim().is_empty()
+		&& name != STAGING_DIR
+		&& !name.ends_with(['.', ' '])
+		&& !name.contains(['\\', '/', ':', '*', '?', '"', '<', '>', '|', '%'])
+}
+
+fn check_dir_name(dir: &str) -> Result<()> {
+	if !is_valid_dir_name(dir) {
+		bail!("{:?} can't be used as a plugin directory name", dir);
+	}
+	Ok(())
+}
+
+/// Fail if the directory of a plugin, its `dir` or its name, isn't a valid directory name, so a
+/// config or manifest can't point outside the plugin directory.
+fn check_dir_names<'a>(plugins: impl IntoIterator<Item = (&'a String, &'a Plugin)>) -> Result<()> {
+	for (name, plugin) in plugins {
+		check_dir_name(plugin.dir_name(name))
+			.map_err(|e| anyhow!("Invalid directory of plugin {}: {}", name, e))?;
+	}
+	Ok(())
+}
+
+/// Find the directory in `plugin_path` other than `exclude` containing the plugin ID.
+fn find_plugin_dir(plugin_path: &Path, id: &str, exclude: &str) -> Option<String> {
+	fs::read_dir(plugin_path)
+		.ok()?
+		.filter_map(|e| e.ok())
+		.filter(|e| e.path().is_dir())
+		.map(|e| e.file_name().to_string_lossy().into_owned())
+		.filter(|d| !d.eq_ignore_ascii_case(exclude) && d != STAGING_DIR)
+		.find(|d| {
+			read_plugin_metadata(&plugin_path.join(d))
+				.ok()
+				.and_then(|m| m.id)
+				.is_some_and(|i| i.eq_ignore_ascii_case(id))
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::Arc;
+
+	fn plugin(version: &str) -> Plugin {
+		toml::from_str(&format!("repo = \"owner/Foo\"\nversion = \"{version}\"")).unwrap()
+	}
+
+	/// A plugin directory with plugin.json and its DLL, both holding `version`.
+	fn plugin_dir(path: &Path, version: &str) {
+		fs::create_dir_all(path).unwrap();
+		fs::write(
+			path.join("plugin.json"),
+			format!(r#"{{"Name": "Foo", "Version": "{version}", "ExecuteFileName": "Foo.dll"}}"#),
+		)
+		.unwrap();
+		fs::write(path.join("Foo.dll"), version).unwrap();
+	}
+
+	fn config(plugins: &str) -> Config {
+		let content = format!(
+			"schema = 1\narch = \"x64\"\npt_path = \"PowerToys.exe\"\nadmin = false\nno_kill = true\n\n[plugins]\n{plugins}"
+		);
+		toml::from_str(&content).unwrap()
+	}
+
+	/// Actions recorded in the report, by plugin.
+	fn report_actions(config: &Config) -> Vec<(String, String)> {
+		let report = serde_json::to_value(&config.report).unwrap();
+		report["plugins"]
+			.as_array()
+			.unwrap()
+			.iter()
+			.map(|p| {
+				let name = p["name"].as_str().unwrap().to_string();
+				(name, p["action"].as_str().unwrap().to_string())
+			})
+			.collect()
+	}
+
+	#[test]
+	fn sync_outcome_succeeded() {
+		let version = || "1.0.0".to_string();
+		assert!(SyncOutcome::Installed {
+			from: version(),
+			to: version()
+		}
+		.succeeded());
+		assert!(SyncOutcome::UpToDate { version: version() }.succeeded());
+		assert!(SyncOutcome::Updated {
+			from: version(),
+			to: version(),
+			diff: FileDiff::default()
+		}
+		.succeeded());
+		assert!(!SyncOutcome::SkippedPinned { version: version() }.succeeded());
+		assert!(!SyncOutcome::SkippedExcluded { version: version() }.succeeded());
+		assert!(!SyncOutcome::Failed {
+			from: None,
+			error: anyhow!("failed"),
+			ignored: true
+		}
+		.succeeded());
+	}
+
+	#[test]
+	fn push_outcome_records_report_and_summary() {
+		let mut config = config("");
+		let outcomes = [
+			(
+				"A",
+				SyncOutcome::Installed {
+					from: "1.0.0".into(),
+					to: "1.1.0".into(),
+				},
+			),
+			(
+				"B",
+				SyncOutcome::Updated {
+					from: "1.0.0".into(),
+					to: "2.0.0".into(),
+					diff: FileDiff::default(),
+				},
+			),
+			(
+				"C",
+				SyncOutcome::UpToDate {
+					version: "1.0.0".into(),
+				},
+			),
+			(
+				"D",
+				SyncOutcome::SkippedPinned {
+					version: "1.0.0".into(),
+				},
+			),
+			(
+				"E",
+				SyncOutcome::SkippedExcluded {
+					version: "1.0.0".into(),
+				},
+			),
+			(
+				"F",
+				SyncOutcome::Failed {
+					from: Some("1.0.0".into()),
+					error: anyhow!("x"),
+					ignored: false,
+				},
+			),
+			(
+				"G",
+				SyncOutcome::Failed {
+					from: None,
+					error: anyhow!("x"),
+					ignored: true,
+				},
+			),
+		];
+		for (name, outcome) in outcomes {
+			config.push_outcome(name, outcome, Msg::FailedToUpdate, false);
+		}
+
+		let actions: Vec<_> = report_actions(&config)
+			.into_iter()
+			.map(|(n, a)| format!("{n} {a}"))
+			.collect();
+		assert_eq!(
+			actions,
+			[
+				"A installed",
+				"B updated",
+				"C up-to-date",
+				"D skipped-pinned",
+				"E skipped-excluded",
+				"F failed",
+				"G failed",
+			]
+		);
+		// ignored failures aren't in the notification
+		let mut summary = Summary::default();
+		summary.updated("B", "2.0.0");
+		summary.failed("F");
+		assert_eq!(config.summary(), summary);
+	}
+
+	#[test]
+	fn export_import_file_round_trip() {
+		let temp = tempfile::tempdir().unwrap();
+		let path = temp.path().join("plugins.toml");
+		let mut exported = config(
+			"Foo = { repo = \"owner/Foo\", version = \"1.0.0\", token = \"ghp_secret\", \
+			 post_install = \"calc.exe\" }\n\
+			 Bar = { repo = \"owner/Bar\", version = \"2.0.0\", dir = \"BarDir\" }\n",
+		);
+		exported.pin = Some(BTreeSet::from(["Foo".to_string()]));
+		exported.export(&path, true).unwrap();
+
+		let content = fs::read_to_string(&path).unwrap();
+		assert!(content.contains(&format!("ptr_version = \"{}\"", export_ptr_version())));
+		assert!(!content.contains("ghp_secret"));
+		assert!(!content.contains("calc.exe"));
+
+		let mut imported = config("Bar = { repo = \"owner/Bar\", version = \"1.0.0\" }\n");
+		imported.merge_file(&path, true).unwrap();
+		assert_eq!(imported.pin, exported.pin);
+		let foo = &imported.plugins["Foo"];
+		assert_eq!(
+			(foo.repo.as_str(), foo.version.as_str()),
+			("owner/Foo", "1.0.0")
+		);
+		assert!(foo.token.is_none());
+		// plugins already in the config are kept
+		assert_eq!(imported.plugins["Bar"].version, "1.0.0");
+
+		let mut without_pins = config("");
+		without_pins.merge_file(&path, false).unwrap();
+		assert_eq!(without_pins.plugins.len(), 2);
+		assert!(without_pins.pin.is_none());
+	}
+
+	#[test]
+	fn import_file_twice_adds_nothing() {
+		let temp = tempfile::tempdir().unwrap();
+		let ctx = Context::with_local_app_data(temp.path().to_path_buf(), None).unwrap();
+		let pt_path = temp.path().join("PowerToys.exe");
+		fs::write(&pt_path, "").unwrap();
+		fs::create_dir_all(&ctx.plugin_path).unwrap();
+		fs::write(
+			&ctx.config_path,
+			format!(
+				"pt_path = {}\narch = \"x64\"\n[plugins]\n",
+				toml::Value::String(pt_path.display().to_string())
+			),
+		)
+		.unwrap();
+		let manifest = temp.path().join("plugins.toml");
+		fs::write(
+			&manifest,
+			"pin = [\"Foo\"]\n[plugins]\n\
+			 Foo = { repo = \"owner/Foo\", version = \"1.0.0\", pre_remove = \"calc.exe\" }\n\
+			 Bar = { repo = \"owner/Bar\", version = \"2.0.0\" }\n",
+		)
+		.unwrap();
+
+		for _ in 0..2 {
+			let config = Config::import_file(ctx.clone(), &manifest, true).unwrap();
+			config.save().unwrap();
+		}
+
+		let saved = fs::read_to_string(&ctx.config_path).unwrap();
+		assert_eq!(saved.matches("owner/Foo").count(), 1, "{saved}");
+		assert_eq!(saved.matches("owner/Bar").count(), 1, "{saved}");
+		assert!(!saved.contains("calc.exe"), "{saved}");
+		let config = Config::new(ctx.clone()).unwrap();
+		assert_eq!(config.plugins.len(), 2);
+		assert_eq!(config.pin, Some(BTreeSet::from(["Foo".to_string()])));
+
+		// a plugin differing only by case is the same plugin
+		fs::write(
+			&manifest,
+			"[plugins]\nfoo = { repo = \"owner/Foo\", version = \"1.0.0\" }\n",
+		)
+		.unwrap();
+		let config = Config::import_file(ctx, &manifest, true).unwrap();
+		assert_eq!(config.plugins.len(), 2);
+	}
+
+	#[test]
+	fn import_file_refuses_newer_ptr_version() {
+		let temp = tempfile::tempdir().unwrap();
+		let path = temp.path().join("plugins.toml");
+		fs::write(&path, "ptr_version = \">=999.0\"\n[plugins]\n").unwrap();
+		let error = config("").merge_file(&path, true).unwrap_err();
+		assert!(
+			error.to_string().contains("requires ptr >=999.0"),
+			"{error}"
+		);
+	}
+
+	#[test]
+	fn swap_with_removes_old_version() {
+		let temp = tempfile::tempdir().unwrap();
+		let (target, staging) = (temp.path().join("Foo"), temp.path().join("staging"));
+		plugin_dir(&target, "1.0.0");
+		plugin_dir(&staging, "1.1.0");
+
+		plugin("1.0.0").swap_with(&target, &staging).unwrap();
+
+		assert_eq!(fs::read_to_string(target.join("Foo.dll")).unwrap(), "1.1.0");
+		assert!(!staging.exists());
+		assert!(!temp.path().join("Foo.old-1.0.0").exists());
+	}
+
+	#[test]
+	fn save_round_trip_is_byte_identical() {
+		let fixture = include_str!("../tests/fixtures/version.toml");
+		let temp = tempfile::tempdir().unwrap();
+		let ctx = Context::with_local_app_data(temp.path().to_path_buf(), None).unwrap();
+		fs::create_dir_all(&ctx.plugin_path).unwrap();
+		fs::write(&ctx.config_path, fixture).unwrap();
+		// an unchanged config isn't written again
+		let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+		fs::File::options()
+			.write(true)
+			.open(&ctx.config_path)
+			.unwrap()
+			.set_modified(old)
+			.unwrap();
+
+		let mut config: Config = read_config(&ctx.config_path).unwrap();
+		config.ctx = ctx.clone();
+		config.save().unwrap();
+		config.save().unwrap();
+
+		assert_eq!(fs::read_to_string(&ctx.config_path).unwrap(), fixture);
+		let modified = fs::metadata(&ctx.config_path).unwrap().modified().unwrap();
+		assert_eq!(modified, old);
+
+		// pins are written sorted whatever order they were added in
+		config.pin = Some(BTreeSet::from(["Foo".to_string(), "Bar".to_string()]));
+		config.save().unwrap();
+		assert_eq!(fs::read_to_string(&ctx.config_path).unwrap(), fixture);
+	}
+
+	#[test]
+	fn debug_masks_tokens() {
+		let mut config = config(
+			"Foo = { repo = \"owner/Foo\", version = \"1.0.0\", token = \"ghp_pluginsecret4321\" }\n",
+		);
+		config.token = Some(Token("ghp_configsecret1234".to_string()));
+		config.tokens = BTreeMap::from([(
+			"ghe.corp.com".to_string(),
+			Token("ghe_hostsecret5678".to_string()),
+		)]);
+		config.token_override = Some(Token("ghp_overridesecret9".to_string()));
+
+		let debug = format!("{config:?}");
+		for secret in [
+			"ghp_pluginsecret4321",
+			"ghp_configsecret1234",
+			"ghe_hostsecret5678",
+			"ghp_overridesecret9",
+		] {
+			assert!(!debug.contains(secret), "{secret} in {debug}");
+		}
+		assert!(debug.contains("****1234"));
+	}
+
+	#[test]
+	fn arch_serde_round_trip() {
+		#[derive(Serialize, Deserialize)]
+		struct Doc {
+			arch: Arch,
+		}
+		for (arch, name) in [(Arch::X64, "x64"), (Arch::ARM64, "arm64")] {
+			let content = toml::to_string(&Doc { arch: arch.clone() }).unwrap();
+			assert_eq!(content, format!("arch = \"{name}\"\n"));
+			assert_eq!(toml::from_str::<Doc>(&content).unwrap().arch, arch);
+		}
+		assert!(toml::from_str::<Doc>("arch = \"x86\"").is_err());
+	}
+
+	#[test]
+	fn import_keeps_recorded_arch() {
+		let temp = tempfile::tempdir().unwrap();
+		let ctx = Context::with_local_app_data(temp.path().to_path_buf(), None).unwrap();
+		let pt_path = temp.path().join("PowerToys.exe");
+		fs::write(&pt_path, "").unwrap();
+		// the arch the host doesn't have, so detection can't be what sets it
+		let host = Arch::detect().unwrap_or(Arch::X64);
+		let other = Arch::ALL.into_iter().find(|a| *a != host).unwrap();
+		fs::create_dir_all(&ctx.plugin_path).unwrap();
+		fs::write(
+			&ctx.config_path,
+			format!(
+				"pt_path = {}\narch = \"{other}\"\nadmin = true\nno_restart = true\n\
+				 token = \"ghp_secret\"\npin = [\"Foo\"]\n\n[plugins]\n",
+				toml::Value::String(pt_path.display().to_string())
+			),
+		)
+		.unwrap();
+
+		let config = Config::import(ctx.clone()).unwrap();
+		assert_eq!(config.arch, other);
+		assert_eq!(config.admin, Admin::Always);
+		assert!(config.no_restart);
+		assert_eq!(
+			config.token.as_ref().map(|t| t.0.as_str()),
+			Some("ghp_secret")
+		);
+		assert_eq!(config.pin, Some(BTreeSet::from(["Foo".to_string()])));
+
+		config.save().unwrap();
+		let saved: Config = read_config(&ctx.config_path).unwrap();
+		assert_eq!(saved.arch, other);
+	}
+
+	#[test]
+	fn read_config_missing_file() {
+		let temp = tempfile::tempdir().unwrap();
+		let path = temp.path().join("version.toml");
+		let error = read_config::<ImportConfig>(&path).unwrap_err().to_string();
+		assert!(
+			error.starts_with(&format!("Failed to read {}: ", path.display())),
+			"{error}"
+		);
+	}
+
+	#[test]
+	fn read_config_malformed() {
+		let temp = tempfile::tempdir().unwrap();
+		let path = temp.path().join("version.toml");
+		fs::write(&path, "arch = \"x64\"\n[plugins\nFoo = 1\n").unwrap();
+		let error = read_config::<ImportConfig>(&path).unwrap_err().to_string();
+		assert!(
+			error.starts_with(&format!("Failed to parse {}\n", path.display())),
+			"{error}"
+		);
+		assert!(error.contains("line 2"), "{error}");
+		assert!(error.contains("`ptr restore`"), "{error}");
+
+		// a value of the wrong type, with the backup suggested once there is one
+		fs::write(&path, "arch = \"x86\"\n").unwrap();
+		fs::write(path.with_extension("toml.bak"), "").unwrap();
+		let error = read_config::<ImportConfig>(&path).unwrap_err().to_string();
+		assert!(error.contains("line 1"), "{error}");
+		assert!(
+			error.contains(&format!(
+				"restore {}",
+				path.with_extension("toml.bak").display()
+			)),
+			"{error}"
+		);
+	}
+
+	#[test]
+	fn migrate_v0_round_trip() {
+		let temp = tempfile::tempdir().unwrap();
+		let ctx = Context::with_local_app_data(temp.path().to_path_buf(), None).unwrap();
+		fs::create_dir_all(&ctx.plugin_path).unwrap();
+		fs::write(
+			&ctx.config_path,
+			include_str!("../tests/fixtures/migrations/v0.toml"),
+		)
+		.unwrap();
+
+		let mut config: Config = read_config(&ctx.config_path).unwrap();
+		config.ctx = ctx.clone();
+		assert_eq!(config.schema, SCHEMA);
+		assert_eq!(config.admin, Admin::Always);
+		config.save().unwrap();
+		let saved = fs::read_to_string(&ctx.config_path).unwrap();
+		assert_eq!(saved, include_str!("../tests/fixtures/migrations/v1.toml"));
+
+		// the current schema is read back as it was written
+		let mut table: toml::Table = toml::from_str(&saved).unwrap();
+		assert!(!migrate(&mut table));
+		let mut config: Config = read_config(&ctx.config_path).unwrap();
+		config.ctx = ctx.clone();
+		config.save().unwrap();
+		assert_eq!(fs::read_to_string(&ctx.config_path).unwrap(), saved);
+	}
+
+	#[test]
+	fn migrate_v0_keeps_values() {
+		let mut table: toml::Table =
+			toml::from_str("admin = false\nfuture = \"kept\"\n[plugins]\n").unwrap();
+		assert!(migrate(&mut table));
+		assert_eq!(table["admin"].as_bool(), Some(false));
+		assert_eq!(table["future"].as_str(), Some("kept"));
+		assert_eq!(table["schema"].as_integer(), Some(i64::from(SCHEMA)));
+	}
+
+	#[test]
+	fn migrate_leaves_newer_schema() {
+		let content = format!("schema = {}\n[plugins]\n", SCHEMA + 1);
+		let mut table: toml::Table = toml::from_str(&content).unwrap();
+		assert!(!migrate(&mut table));
+		assert!(!table.contains_key("admin"));
+
+		let mut newer = config("");
+		newer.schema = SCHEMA + 1;
+		let error = newer.save().unwrap_err().to_string();
+		assert!(error.contains("nothing was written"), "{error}");
+	}
+
+	#[test]
+	fn admin_elevate_probe() {
+		let unused = || -> Result<bool> { panic!("probed") };
+		assert!(Admin::Always.elevate(unused));
+		assert!(!Admin::Never.elevate(unused));
+		assert!(Admin::Auto.elevate(|| Ok(true)));
+		assert!(!Admin::Auto.elevate(|| Ok(false)));
+		// elevating works whether it's needed or not
+		assert!(Admin::Auto.elevate(|| bail!("Access is denied")));
+	}
+
+	#[test]
+	fn init_then_update_ignores_v_prefix() {
+		for (installed, tag) in [("v1.2.3", "1.2.3"), ("1.2.3", "v1.2.3")] {
+			let temp = tempfile::tempdir().unwrap();
+			let ctx = Context::with_local_app_data(temp.path().to_path_buf(), None).unwrap();
+			let pt_path = temp.path().join("PowerToys.exe");
+			fs::write(&pt_path, "").unwrap();
+			let dir = ctx.plugin_path.join("Foo");
+			fs::create_dir_all(&dir).unwrap();
+			fs::write(
+				dir.join("plugin.json"),
+				format!(
+					r#"{{"Name": "Foo", "Version": "{installed}", "ExecuteFileName": "Foo.dll", "Website": "https://github.com/owner/Foo"}}"#
+				),
+			)
+			.unwrap();
+			fs::write(dir.join("Foo.dll"), "installed").unwrap();
+			fs::write(
+				&ctx.config_path,
+				format!(
+					"pt_path = {}\narch = \"x64\"\nno_kill = true\n[plugins]\n",
+					toml::Value::String(pt_path.display().to_string())
+				),
+			)
+			.unwrap();
+
+			let mut config = Config::init(ctx, false).unwrap();
+			assert_eq!(config.plugins["Foo"].version, installed);
+
+			let archive = crate::util::tests::zip(&[
+				(
+					"Foo/plugin.json",
+					r#"{"Name": "Foo", "ExecuteFileName": "Foo.dll"}"#,
+				),
+				("Foo/Foo.dll", "downloaded"),
+			]);
+			let opts = InstallOptions {
+				source: Box::new(crate::util::tests::FakeSource {
+					tag: tag.to_string(),
+					assets: vec![("Foo-x64.zip".to_string(), archive)],
+				}),
+				..config.install_options(false)
+			};
+			let plugin = config.plugins.get_mut("Foo").unwrap();
+			let outcome = plugin.sync("Foo", None, &opts);
+			// nothing is downloaded, the version is recorded as GitHub tags it
+			assert!(
+				matches!(&outcome, SyncOutcome::UpToDate { version } if version == tag),
+				"{installed} against {tag}: {outcome:?}"
+			);
+			assert_eq!(
+				fs::read_to_string(dir.join("Foo.dll")).unwrap(),
+				"installed"
+			);
+		}
+	}
+
+	#[test]
+	fn add_update_remove_events() {
+		let temp = tempfile::tempdir().unwrap();
+		let ctx = Context::with_local_app_data(temp.path().to_path_buf(), None).unwrap();
+		let pt_path = temp.path().join("PowerToys.exe");
+		fs::write(&pt_path, "").unwrap();
+		fs::create_dir_all(&ctx.plugin_path).unwrap();
+		fs::write(
+			&ctx.config_path,
+			format!(
+				"pt_path = {}\narch = \"x64\"\nno_kill = true\nskip_network_check = true\n[plugins]\n",
+				toml::Value::String(pt_path.display().to_string())
+			),
+		)
+		.unwrap();
+		let mut config = Config::new(ctx).unwrap();
+		// the free space of the plugin volume can only be checked on Windows
+		config.skip_space_check();
+		let opts = |config: &Config, tag: &str| {
+			let archive = crate::util::tests::zip(&[
+				(
+					"Foo/plugin.json",
+					r#"{"Name": "Foo", "ExecuteFileName": "Foo.dll"}"#,
+				),
+				("Foo/Foo.dll", tag),
+			]);
+			InstallOptions {
+				source: Box::new(crate::util::tests::FakeSource {
+					tag: tag.to_string(),
+					assets: vec![("Foo-x64.zip".to_string(), archive)],
+				}),
+				..config.install_options(false)
+			}
+		};
+		let not_restarted = Event::Warning {
+			message: crate::i18n::tr(Msg::NotRestarted).to_string(),
+		};
+		let events = Arc::new(reporter::Capture::default());
+		let _reporting = reporter::enter(events.clone());
+
+		let repo = "owner/Foo".to_string();
+		let add_opts = opts(&config, "v1.0.0");
+		config
+			.add_with("Foo", repo, None, None, None, Scope::User, &add_opts)
+			.unwrap();
+		assert_eq!(
+			events.take(),
+			[
+				Event::Added {
+					name: "Foo".to_string(),
+					version: "v1.0.0".to_string()
+				},
+				not_restarted.clone()
+			]
+		);
+
+		let update_opts = opts(&config, "v1.1.0");
+		config
+			.update_with(vec![("Foo".to_string(), None)], false, &update_opts)
+			.unwrap();
+		// an update is reported like an add, with the new version
+		assert_eq!(
+			events.take(),
+			[
+				Event::Progress {
+					index: 1,
+					total: 1,
+					name: "Foo".to_string()
+				},
+				Event::Added {
+					name: "Foo".to_string(),
+					version: "v1.1.0".to_string()
+				},
+				not_restarted.clone()
+			]
+		);
+		let dir = config.plugin_dir("Foo").unwrap();
+		assert_eq!(fs::read_to_string(dir.join("Foo.dll")).unwrap(), "v1.1.0");
+
+		config.remove(vec!["Foo".to_string()], false).unwrap();
+		assert!(!dir.exists());
+		assert_eq!(
+			events.take(),
+			[
+				Event::Removed {
+					name: "Foo".to_string()
+				},
+				not_restarted
+			]
+		);
+	}
+
+	#[test]
+	fn dir_outside_plugin_path_rejected() {
+		let temp = tempfile::tempdir().unwrap();
+		for dir in [
+			r"..\..\Something",
+			"../Something",
+			"..",
+			"C:",
+			" ",
+			"A%TEMP%B",
+		] {
+			let err = Plugin::resolve_dir(temp.path(), "Foo", Some(dir.to_string()), &[], false);
+			assert!(err.is_err(), "{dir}");
+		}
+		assert_eq!(
+			Plugin::resolve_dir(temp.path(), "Foo", Some("Bar".to_string()), &[], false).unwrap(),
+			"Bar"
+		);
+		// the plugin key is the directory without `dir`
+		assert!(Plugin::resolve_dir(temp.path(), "..", None, &[], false).is_err());
+
+		let ctx = Context::with_local_app_data(temp.path().to_path_buf(), None).unwrap();
+		let pt_path = temp.path().join("PowerToys.exe");
+		fs::write(&pt_path, "").unwrap();
+		fs::create_dir_all(&ctx.plugin_path).unwrap();
+		let header = format!(
+			"pt_path = {}\narch = \"x64\"\n[plugins]\n",
+			toml::Value::String(pt_path.display().to_string())
+		);
+		let escaping =
+			"Foo = { repo = \"owner/Foo\", version = \"1.0.0\", dir = \"../../Something\" }\n";
+		fs::write(&ctx.config_path, format!("{header}{escaping}")).unwrap();
+		let err = Config::new(ctx.clone()).err().unwrap();
+		assert!(
+			err.to_string().contains("Invalid directory of plugin Foo"),
+			"{err}"
+		);
+
+		fs::write(&ctx.config_path, &header).unwrap();
+		Config::new(ctx.clone()).unwrap();
+		let manifest = temp.path().join("plugins.toml");
+		fs::write(&manifest, format!("[plugins]\n{escaping}")).unwrap();
+		assert!(Config::import_file(ctx.clone(), &manifest, false).is_err());
+		let key = "\"..\" = { repo = \"owner/Foo\", version = \"1.0.0\" }\n";
+		fs::write(&manifest, format!("[plugins]\n{key}")).unwrap();
+		assert!(Config::import_file(ctx, &manifest, false).is_err());
+	}
+
+	#[test]
+	fn update_keeps_held_file() {
+		let temp = tempfile::tempdir().unwrap();
+		let ctx = Context::with_local_app_data(temp.path().to_path_buf(), None).unwrap();
+		let pt_path = temp.path().join("PowerToys.exe");
+		fs::write(&pt_path, "").unwrap();
+		let dir = ctx.plugin_path.join("Foo");
+		plugin_dir(&dir, "1.0.0");
+		fs::write(dir.join("patched.dll"), "patched").unwrap();
+		fs::write(
+			&ctx.config_path,
+			format!(
+				"pt_path = {}\narch = \"x64\"\nno_kill = true\n[plugins]\n\
+				 Foo = {{ repo = \"owner/Foo\", version = \"1.0.0\", hold = [\"patched.dll\"] }}\n",
+				toml::Value::String(pt_path.display().to_string())
+			),
+		)
+		.unwrap();
+		let mut config = Config::new(ctx).unwrap();
+		let archive = crate::util::tests::zip(&[
+			(
+				"Foo/plugin.json",
+				r#"{"Name": "Foo", "Version": "1.1.0", "ExecuteFileName": "Foo.dll"}"#,
+			),
+			("Foo/Foo.dll", "1.1.0"),
+			("Foo/patched.dll", "1.1.0"),
+		]);
+		let opts = InstallOptions {
+			source: Box::new(crate::util::tests::FakeSource {
+				tag: "v1.1.0".to_string(),
+				assets: vec![("Foo-x64.zip".to_string(), archive)],
+			}),
+			..config.install_options(false)
+		};
+		let events = Arc::new(reporter::Capture::default());
+		let _reporting = reporter::enter(events.clone());
+
+		let outcome = config
+			.plugins
+			.get_mut("Foo")
+			.unwrap()
+			.sync("Foo", None, &opts);
+
+		assert!(
+			matches!(outcome, SyncOutcome::Updated { .. }),
+			"{outcome:?}"
+		);
+		assert_eq!(fs::read_to_string(dir.join("Foo.dll")).unwrap(), "1.1.0");
+		assert_eq!(
+			fs::read_to_string(dir.join("patched.dll")).unwrap(),
+			"patched"
+		);
+		assert!(events.take().contains(&Event::Warning {
+			message: "Foo: keeping held file patched.dll, the new release changes it".to_string()
+		}));
+	}
+
+	#[test]
+	fn restart_plan_precedence() {
+		use RestartPlan::*;
+		// config no_kill, config no_restart, --no-kill
+		let table = [
+			(false, false, false, Restarted),
+			(false, true, false, Killed),
+			(true, false, false, Untouched),
+			(true, true, false, Untouched),
+			(false, false, true, Untouched),
+			(false, true, true, Untouched),
+			(true, false, true, Untouched),
+			(true, true, true, Untouched),
+		];
+		for (no_kill, no_restart, flag, expected) in table {
+			assert_eq!(
+				RestartPlan::new(no_kill || flag, no_restart),
+				expected,
+				"no_kill = {no_kill}, no_restart = {no_restart}, --no-kill = {flag}"
+			);
+		}
+		// --no-kill isn't set in tests, so the config decides alone
+		let mut config = config("");
+		config.no_kill = false;
+		config.no_restart = true;
+		assert_eq!(config.restart_plan(), Killed);
+	}
+
+	#[test]
+	fn download_into_staging() {
+		let temp = tempfile::tempdir().unwrap();
+		let archive = crate::util::tests::zip(&[
+			(
+				"Foo/plugin.json",
+				r#"{"Name": "Foo", "Version": "1.1.0", "ExecuteFileName": "Foo.dll"}"#,
+			),
+			("Foo/Foo.dll", "1.1.0"),
+		]);
+		let source = crate::util::tests::FakeSource {
+			tag: "v1.1.0".to_string(),
+			assets: vec![("Foo-x64.zip".to_string(), archive)],
+		};
+		let staging = staging_path(temp.path(), "Foo");
+
+		let downloaded = download_release(
+			&source,
+			&staging,
+			"owner/Foo",
+			None,
+			&Arch::X64,
+			None,
+			&AssetFilter::default(),
+			None,
+			&ExtractLimits::default(),
+		)
+		.unwrap();
+
+		assert_eq!(downloaded.asset.as_deref(), Some("Foo-x64.zip"));
+		assert_eq!(
+			fs::read_to_string(staging.join("Foo.dll")).unwrap(),
+			"1.1.0"
+		);
+		validate_plugin_dir(&staging).unwrap();
+		clean_staging(temp.path(), "Foo").unwrap();
+		assert!(!temp.path().join(STAGING_DIR).exists());
+	}
+
+	#[cfg(windows)]
+	#[test]
+	fn swap_with_marks_held_old_version() {
+		use windows::core::HSTRING;
+		use windows::Win32::System::LibraryLoader::{FreeLibrary, LoadLibraryW};
+
+		let temp = tempfile::tempdir().unwrap();
+		let (target, staging) = (temp.path().join("Foo"), temp.path().join("staging"));
+		plugin_dir(&target, "1.0.0");
+		plugin_dir(&staging, "1.1.0");
+		// loaded like PowerToys loads plugins, the directory can be renamed but not removed
+		let system = std::env::var("SystemRoot").unwrap();
+		let held = target.join("Held.dll");
+		fs::copy(Path::new(&system).join(r"System32\version.dll"), &held).unwrap();
+		let module = unsafe { LoadLibraryW(&HSTRING::from(held.as_path())) }.unwrap();
+
+		let result = plugin("1.0.0").swap_with(&target, &staging);
+
+		let old = temp.path().join("Foo.old-1.0.0");
+		let marked = is_swapped_dir(&old);
+		unsafe { FreeLibrary(module) }.unwrap();
+		result.unwrap();
+		assert_eq!(fs::read_to_string(target.join("Foo.dll")).unwrap(), "1.1.0");
+		assert!(marked, "{} isn't marked as swapped", old.display());
+	}
 }
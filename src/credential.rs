@@ -0,0 +1,71 @@
+//! Store the GitHub token in Windows Credential Manager.
+
+use anyhow::Result;
+#[cfg(windows)]
+use std::{iter, ptr, slice};
+#[cfg(windows)]
+use windows::core::{w, PWSTR};
+#[cfg(windows)]
+use windows::Win32::Security::Credentials::{
+	CredDeleteW, CredFree, CredReadW, CredWriteW, CREDENTIALW, CRED_PERSIST_LOCAL_MACHINE,
+	CRED_TYPE_GENERIC,
+};
+
+#[cfg(not(windows))]
+use crate::util::UNSUPPORTED;
+
+#[cfg(windows)]
+const TARGET: &str = "ptr:github";
+
+/// Read the token, None if it's not stored.
+#[cfg(windows)]
+pub fn read_token() -> Option<String> {
+	unsafe {
+		let mut cred: *mut CREDENTIALW = ptr::null_mut();
+		CredReadW(w!("ptr:github"), CRED_TYPE_GENERIC, None, &mut cred).ok()?;
+		let blob =
+			slice::from_raw_parts((*cred).CredentialBlob, (*cred).CredentialBlobSize as usize);
+		let token = String::from_utf8(blob.to_vec()).ok();
+		CredFree(cred as *const _);
+		token
+	}
+}
+
+/// Store the token, replacing the existing one.
+#[cfg(windows)]
+pub fn write_token(token: &str) -> Result<()> {
+	let mut target: Vec<u16> = TARGET.encode_utf16().chain(iter::once(0)).collect();
+	let mut blob = token.as_bytes().to_vec();
+	let cred = CREDENTIALW {
+		Type: CRED_TYPE_GENERIC,
+		TargetName: PWSTR(target.as_mut_ptr()),
+		CredentialBlobSize: blob.len() as u32,
+		CredentialBlob: blob.as_mut_ptr(),
+		Persist: CRED_PERSIST_LOCAL_MACHINE,
+		..Default::default()
+	};
+	unsafe { CredWriteW(&cred, 0)? };
+	Ok(())
+}
+
+/// Delete the stored token.
+#[cfg(windows)]
+pub fn delete_token() -> Result<()> {
+	unsafe { CredDeleteW(w!("ptr:github"), CRED_TYPE_GENERIC, None)? };
+	Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn read_token() -> Option<String> {
+	None
+}
+
+#[cfg(not(windows))]
+pub fn write_token(_token: &str) -> Result<()> {
+	anyhow::bail!(UNSUPPORTED)
+}
+
+#[cfg(not(windows))]
+pub fn delete_token() -> Result<()> {
+	anyhow::bail!(UNSUPPORTED)
+}
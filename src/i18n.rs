@@ -0,0 +1,476 @@
+//! Translations of the messages ptr prints.
+//!
+//! Every locale is an exhaustive match over [`Msg`], so a message without a translation doesn't
+//! compile. Arguments are positional, `{0}`, `{1}`, so a translation can reorder them.
+//!
+//! The language is `lang` in version.toml, then `LC_ALL`, `LC_MESSAGES` or `LANG`, then the
+//! Windows display language, English if none is supported.
+
+use std::env;
+use std::fmt::Display;
+use std::sync::atomic::{self, AtomicU8};
+
+use crate::{t, warning};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(u8)]
+pub enum Lang {
+	En = 1,
+	De,
+}
+
+impl Lang {
+	/// Language of a tag like `de`, `de-AT` or `de_DE.UTF-8`.
+	fn from_tag(tag: &str) -> Option<Self> {
+		let code = tag.split(['-', '_', '.']).next()?.to_lowercase();
+		match code.as_str() {
+			"en" => Some(Lang::En),
+			"de" => Some(Lang::De),
+			_ => None,
+		}
+	}
+}
+
+/// Chosen language, 0 until it's set or detected.
+static LANG: AtomicU8 = AtomicU8::new(0);
+
+/// Use the language from `lang` in version.toml.
+pub fn set_lang(tag: &str) {
+	match Lang::from_tag(tag) {
+		Some(lang) => LANG.store(lang as u8, atomic::Ordering::Relaxed),
+		None => warning!(t!(UnknownLang, tag)),
+	}
+}
+
+pub fn lang() -> Lang {
+	match LANG.load(atomic::Ordering::Relaxed) {
+		1 => Lang::En,
+		2 => Lang::De,
+		_ => {
+			let lang = detect().unwrap_or(Lang::En);
+			LANG.store(lang as u8, atomic::Ordering::Relaxed);
+			lang
+		}
+	}
+}
+
+fn detect() -> Option<Lang> {
+	["LC_ALL", "LC_MESSAGES", "LANG"]
+		.into_iter()
+		.filter_map(|var| env::var(var).ok().filter(|v| !v.is_empty()))
+		.find_map(|v| Lang::from_tag(&v))
+		.or_else(ui_lang)
+}
+
+/// Windows display language.
+#[cfg(windows)]
+fn ui_lang() -> Option<Lang> {
+	use windows::Win32::Globalization::GetUserDefaultUILanguage;
+
+	const LANG_ENGLISH: u16 = 0x09;
+	const LANG_GERMAN: u16 = 0x07;
+	// the primary language is the low 10 bits of the LANGID
+	match unsafe { GetUserDefaultUILanguage() } & 0x3ff {
+		LANG_ENGLISH => Some(Lang::En),
+		LANG_GERMAN => Some(Lang::De),
+		_ => None,
+	}
+}
+
+#[cfg(not(windows))]
+fn ui_lang() -> Option<Lang> {
+	None
+}
+
+/// Messages with translations, the English text is in [`en`].
+#[derive(Clone, Copy, Debug)]
+pub enum Msg {
+	Error,
+	Warning,
+	UnknownLang,
+	/// Hint after a question answered yes by default.
+	HintYes,
+	/// Hint after a question answered no by default.
+	HintNo,
+	/// Answers for yes, separated by `|`, English ones are always accepted.
+	AnswersYes,
+	AnswersNo,
+	InputRequired,
+	FailedToAdd,
+	FailedToUpdate,
+	FailedToImport,
+	FailedToRemove,
+	FailedToSaveConfig,
+	FailedToKill,
+	FailedToStart,
+	NotRunning,
+	AlreadyRunning,
+	NotRestarted,
+	PowerToysUpdating,
+	PinnedRemoveAnyway,
+	NoLongerInConfig,
+	RemoveThem,
+	BackedUp,
+	Restored,
+	CacheCleaned,
+	PluginUpdated,
+	PluginsUpdated,
+	UpdateFailed,
+	TokenSaved,
+	TokenMoved,
+	TokenRemoved,
+	RateLimit,
+	LoggedInAs,
+	OverwriteConfig,
+	OnboardIntro,
+	PowerToysFoundAt,
+	EnterPowerToysDir,
+	PowerToysNotFound,
+	NotPowerToys,
+	InstallForArch,
+	EnterArch,
+	TokenHint,
+	EnterToken,
+	AddPlugin,
+	UseFoundRepo,
+	EnterRepo,
+	ReposNotFound,
+	PinnedUpdateAnyway,
+	ChooseUpdates,
+	AllUpToDate,
+	ContinueAnyway,
+	UseNameAsDir,
+	AdoptExisting,
+	SelectAsset,
+	SelectPlugin,
+	DeleteUnmanaged,
+	ConfigMatches,
+	Exported,
+	NoPluginsToDisable,
+	SafeModeOn,
+	DoesItCrash,
+	SafeModeCulprit,
+	MoveToken,
+	Downgrade,
+	Uninstall,
+	UninstallKeepPlugins,
+	RemoveProfile,
+	RemoveProfileKeepPlugins,
+}
+
+/// The message in the current language.
+pub fn tr(msg: Msg) -> &'static str {
+	match lang() {
+		Lang::En => en(msg),
+		Lang::De => de(msg),
+	}
+}
+
+/// Replace `{0}`, `{1}`, ... in `template` with `args` in one pass, so braces in an argument
+/// are kept as they are. Other braces are kept too.
+pub fn fill(template: &str, args: &[&dyn Display]) -> String {
+	let mut filled = String::with_capacity(template.len());
+	let mut rest = template;
+	while let Some(start) = rest.find('{') {
+		filled.push_str(&rest[..start]);
+		rest = &rest[start + 1..];
+		let arg = rest.find('}').and_then(|end| {
+			let i: usize = rest[..end].parse().ok()?;
+			Some((end, args.get(i)?))
+		});
+		match arg {
+			Some((end, arg)) => {
+				filled.push_str(&arg.to_string());
+				rest = &rest[end + 1..];
+			}
+			None => filled.push('{'),
+		}
+	}
+	filled.push_str(rest);
+	filled
+}
+
+fn en(msg: Msg) -> &'static str {
+	match msg {
+		Msg::Error => "error:",
+		Msg::Warning => "warning:",
+		Msg::UnknownLang => "Unknown lang '{0}' in version.toml, expected en or de",
+		Msg::HintYes => "[Y/n]",
+		Msg::HintNo => "[y/N]",
+		Msg::AnswersYes => "y|yes",
+		Msg::AnswersNo => "n|no",
+		Msg::InputRequired => "Input required for \"{0}\", run without --yes",
+		Msg::FailedToAdd => "Failed to add {0}: {1}",
+		Msg::FailedToUpdate => "Failed to update {0}: {1}",
+		Msg::FailedToImport => "Failed to import {0}: {1}",
+		Msg::FailedToRemove => "Failed to remove {0}: {1}",
+		Msg::FailedToSaveConfig => "Failed to save config: {0}",
+		Msg::FailedToKill => "Failed to kill {0}: {1}",
+		Msg::FailedToStart => "Failed to start {0}: {1}",
+		Msg::NotRunning => "{0} is not running",
+		Msg::AlreadyRunning => "{0} is already running",
+		Msg::NotRestarted => "PowerToys was not restarted, run `ptr restart` to load the changes",
+		Msg::PowerToysUpdating => "PowerToys is updating itself, retry in a minute or pass --force",
+		Msg::PinnedRemoveAnyway => "{0} is pinned, remove anyway?",
+		Msg::NoLongerInConfig => "Plugins no longer in version.toml:",
+		Msg::RemoveThem => "Remove them?",
+		Msg::BackedUp => "Backed up {0} plugins to {1} ({2})",
+		Msg::Restored => "Restored {0} plugins from {1} ({2})",
+		Msg::CacheCleaned => "Removed {0} cached assets, {1} freed",
+		Msg::PluginUpdated => "1 plugin updated: {0}",
+		Msg::PluginsUpdated => "{0} plugins updated: {1}",
+		Msg::UpdateFailed => "Update failed for {0}",
+		Msg::TokenSaved => "Token saved to Credential Manager",
+		Msg::TokenMoved => "Token moved to Credential Manager",
+		Msg::TokenRemoved => "Token removed from Credential Manager",
+		Msg::RateLimit => "Rate limit remaining: {0}",
+		Msg::LoggedInAs => "Logged in as {0} with token {1} from {2}",
+		Msg::OverwriteConfig => "version.toml already exists, override?",
+		Msg::OnboardIntro => "No plugins installed yet, leave any answer empty to skip it.",
+		Msg::PowerToysFoundAt => "PowerToys found at {0}, is it right?",
+		Msg::EnterPowerToysDir => "Enter the PowerToys directory: ",
+		Msg::PowerToysNotFound => "PowerToys executable not found in any of the expected locations\nEnter path: ",
+		Msg::NotPowerToys => "Path doesn't exist or is not PowerToys.exe\nEnter path: ",
+		Msg::InstallForArch => "Install plugins for {0}?",
+		Msg::EnterArch => "Enter the arch, x64 or arm64: ",
+		Msg::TokenHint => "GitHub allows 60 API requests per hour without a token, updating many plugins needs one. A token without any scope is enough.",
+		Msg::EnterToken => "Enter GitHub token: ",
+		Msg::AddPlugin => "Add a plugin as `<NAME> <REPO>`: ",
+		Msg::UseFoundRepo => "Found {0} for {1}, use it?",
+		Msg::EnterRepo => "Enter repository for {0} or leave empty to skip: ",
+		Msg::ReposNotFound => "Repository not found for these plugins, add them with `ptr add`:",
+		Msg::PinnedUpdateAnyway => "{0} is pinned, update anyway?",
+		Msg::ChooseUpdates => "Numbers to switch between update, skip and pin, enter to apply, q to quit: ",
+		Msg::AllUpToDate => "All plugins are up to date",
+		Msg::ContinueAnyway => "Continue anyway?",
+		Msg::UseNameAsDir => "plugin.json of {0} names it {1}, install in directory {1}?",
+		Msg::AdoptExisting => "{0} is already installed in directory {1}, adopt and replace it?",
+		Msg::SelectAsset => "Fail to match assets, please select one: ",
+		Msg::SelectPlugin => "Multiple plugins found in the archive, please select one: ",
+		Msg::DeleteUnmanaged => "Delete unmanaged {0}?",
+		Msg::ConfigMatches => "Config matches the installed plugins",
+		Msg::Exported => "Exported {0} plugins to {1}",
+		Msg::NoPluginsToDisable => "No installed plugins to disable",
+		Msg::SafeModeOn => "{0} plugins disabled, `ptr safe-mode off` restores them",
+		Msg::DoesItCrash => "Enabled {0}, does PowerToys Run crash?",
+		Msg::SafeModeCulprit => "{0} makes PowerToys Run crash, it stays disabled and the other plugins are enabled. Run `ptr safe-mode off`, then `ptr remove {1}` or `ptr update {1} --version <VERSION>`",
+		Msg::MoveToken => "Move the token in version.toml to Credential Manager?",
+		Msg::Downgrade => "Downgrading ptr v{0} -> {1}, continue?",
+		Msg::Uninstall => "Remove ptr, its files and all plugins?",
+		Msg::UninstallKeepPlugins => "Remove ptr and its files, keeping the plugins?",
+		Msg::RemoveProfile => "Remove profile {0}?",
+		Msg::RemoveProfileKeepPlugins => "Remove profile {0}, plugins in {1} are kept?",
+	}
+}
+
+fn de(msg: Msg) -> &'static str {
+	match msg {
+		Msg::Error => "Fehler:",
+		Msg::Warning => "Warnung:",
+		Msg::UnknownLang => "Unbekannte Sprache '{0}' in version.toml, erwartet en oder de",
+		Msg::HintYes => "[J/n]",
+		Msg::HintNo => "[j/N]",
+		Msg::AnswersYes => "j|ja",
+		Msg::AnswersNo => "n|nein",
+		Msg::InputRequired => "Eingabe für \"{0}\" erforderlich, ohne --yes ausführen",
+		Msg::FailedToAdd => "{0} konnte nicht hinzugefügt werden: {1}",
+		Msg::FailedToUpdate => "{0} konnte nicht aktualisiert werden: {1}",
+		Msg::FailedToImport => "{0} konnte nicht importiert werden: {1}",
+		Msg::FailedToRemove => "{0} konnte nicht entfernt werden: {1}",
+		Msg::FailedToSaveConfig => "Konfiguration konnte nicht gespeichert werden: {0}",
+		Msg::FailedToKill => "{0} konnte nicht beendet werden: {1}",
+		Msg::FailedToStart => "{0} konnte nicht gestartet werden: {1}",
+		Msg::NotRunning => "{0} läuft nicht",
+		Msg::AlreadyRunning => "{0} läuft bereits",
+		Msg::NotRestarted => {
+			"PowerToys wurde nicht neu gestartet, `ptr restart` lädt die Änderungen"
+		}
+		Msg::PowerToysUpdating => {
+			"PowerToys aktualisiert sich gerade, in einer Minute erneut versuchen oder --force \
+			 angeben"
+		}
+		Msg::PinnedRemoveAnyway => "{0} ist angeheftet, trotzdem entfernen?",
+		Msg::NoLongerInConfig => "Plugins, die nicht mehr in version.toml stehen:",
+		Msg::RemoveThem => "Entfernen?",
+		Msg::BackedUp => "{0} Plugins in {1} gesichert ({2})",
+		Msg::Restored => "{0} Plugins aus {1} wiederhergestellt ({2})",
+		Msg::CacheCleaned => "{0} zwischengespeicherte Assets entfernt, {1} freigegeben",
+		Msg::PluginUpdated => "1 Plugin aktualisiert: {0}",
+		Msg::PluginsUpdated => "{0} Plugins aktualisiert: {1}",
+		Msg::UpdateFailed => "Aktualisierung fehlgeschlagen für {0}",
+		Msg::TokenSaved => "Token in der Anmeldeinformationsverwaltung gespeichert",
+		Msg::TokenMoved => "Token in die Anmeldeinformationsverwaltung verschoben",
+		Msg::TokenRemoved => "Token aus der Anmeldeinformationsverwaltung entfernt",
+		Msg::RateLimit => "Verbleibendes Rate-Limit: {0}",
+		Msg::LoggedInAs => "Angemeldet als {0} mit Token {1} aus {2}",
+		Msg::OverwriteConfig => "version.toml existiert bereits, überschreiben?",
+		Msg::OnboardIntro => "Noch keine Plugins installiert, eine leere Antwort überspringt die Frage.",
+		Msg::PowerToysFoundAt => "PowerToys in {0} gefunden, ist das richtig?",
+		Msg::EnterPowerToysDir => "PowerToys-Verzeichnis eingeben: ",
+		Msg::PowerToysNotFound => "PowerToys.exe wurde an keinem der erwarteten Orte gefunden\nPfad eingeben: ",
+		Msg::NotPowerToys => "Der Pfad existiert nicht oder ist nicht PowerToys.exe\nPfad eingeben: ",
+		Msg::InstallForArch => "Plugins für {0} installieren?",
+		Msg::EnterArch => "Architektur eingeben, x64 oder arm64: ",
+		Msg::TokenHint => "GitHub erlaubt ohne Token 60 API-Anfragen pro Stunde, für viele Plugins wird eines benötigt. Ein Token ohne Berechtigungen genügt.",
+		Msg::EnterToken => "GitHub-Token eingeben: ",
+		Msg::AddPlugin => "Plugin als `<NAME> <REPO>` hinzufügen: ",
+		Msg::UseFoundRepo => "{0} für {1} gefunden, verwenden?",
+		Msg::EnterRepo => "Repository für {0} eingeben oder leer lassen zum Überspringen: ",
+		Msg::ReposNotFound => "Kein Repository für diese Plugins gefunden, mit `ptr add` hinzufügen:",
+		Msg::PinnedUpdateAnyway => "{0} ist angeheftet, trotzdem aktualisieren?",
+		Msg::ChooseUpdates => "Nummern wechseln zwischen aktualisieren, überspringen und anheften, Enter übernimmt, q beendet: ",
+		Msg::AllUpToDate => "Alle Plugins sind aktuell",
+		Msg::ContinueAnyway => "Trotzdem fortfahren?",
+		Msg::UseNameAsDir => "plugin.json von {0} nennt es {1}, im Verzeichnis {1} installieren?",
+		Msg::AdoptExisting => "{0} ist bereits im Verzeichnis {1} installiert, übernehmen und ersetzen?",
+		Msg::SelectAsset => "Kein passendes Asset gefunden, bitte eines auswählen: ",
+		Msg::SelectPlugin => "Mehrere Plugins im Archiv gefunden, bitte eines auswählen: ",
+		Msg::DeleteUnmanaged => "Nicht verwaltetes {0} löschen?",
+		Msg::ConfigMatches => "Die Konfiguration stimmt mit den installierten Plugins überein",
+		Msg::Exported => "{0} Plugins nach {1} exportiert",
+		Msg::NoPluginsToDisable => "Keine installierten Plugins zum Deaktivieren",
+		Msg::SafeModeOn => "{0} Plugins deaktiviert, `ptr safe-mode off` stellt sie wieder her",
+		Msg::DoesItCrash => "{0} aktiviert, stürzt PowerToys Run ab?",
+		Msg::SafeModeCulprit => "{0} lässt PowerToys Run abstürzen, es bleibt deaktiviert und die anderen Plugins sind aktiviert. `ptr safe-mode off` ausführen, dann `ptr remove {1}` oder `ptr update {1} --version <VERSION>`",
+		Msg::MoveToken => "Token aus version.toml in die Anmeldeinformationsverwaltung verschieben?",
+		Msg::Downgrade => "ptr wird von v{0} auf {1} herabgestuft, fortfahren?",
+		Msg::Uninstall => "ptr, seine Dateien und alle Plugins entfernen?",
+		Msg::UninstallKeepPlugins => "ptr und seine Dateien entfernen und die Plugins behalten?",
+		Msg::RemoveProfile => "Profil {0} entfernen?",
+		Msg::RemoveProfileKeepPlugins => "Profil {0} entfernen, die Plugins in {1} bleiben erhalten?",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const ALL: [Msg; 68] = [
+		Msg::Error,
+		Msg::Warning,
+		Msg::UnknownLang,
+		Msg::HintYes,
+		Msg::HintNo,
+		Msg::AnswersYes,
+		Msg::AnswersNo,
+		Msg::InputRequired,
+		Msg::FailedToAdd,
+		Msg::FailedToUpdate,
+		Msg::FailedToImport,
+		Msg::FailedToRemove,
+		Msg::FailedToSaveConfig,
+		Msg::FailedToKill,
+		Msg::FailedToStart,
+		Msg::NotRunning,
+		Msg::AlreadyRunning,
+		Msg::NotRestarted,
+		Msg::PowerToysUpdating,
+		Msg::PinnedRemoveAnyway,
+		Msg::NoLongerInConfig,
+		Msg::RemoveThem,
+		Msg::BackedUp,
+		Msg::Restored,
+		Msg::CacheCleaned,
+		Msg::PluginUpdated,
+		Msg::PluginsUpdated,
+		Msg::UpdateFailed,
+		Msg::TokenSaved,
+		Msg::TokenMoved,
+		Msg::TokenRemoved,
+		Msg::RateLimit,
+		Msg::LoggedInAs,
+		Msg::OverwriteConfig,
+		Msg::OnboardIntro,
+		Msg::PowerToysFoundAt,
+		Msg::EnterPowerToysDir,
+		Msg::PowerToysNotFound,
+		Msg::NotPowerToys,
+		Msg::InstallForArch,
+		Msg::EnterArch,
+		Msg::TokenHint,
+		Msg::EnterToken,
+		Msg::AddPlugin,
+		Msg::UseFoundRepo,
+		Msg::EnterRepo,
+		Msg::ReposNotFound,
+		Msg::PinnedUpdateAnyway,
+		Msg::ChooseUpdates,
+		Msg::AllUpToDate,
+		Msg::ContinueAnyway,
+		Msg::UseNameAsDir,
+		Msg::AdoptExisting,
+		Msg::SelectAsset,
+		Msg::SelectPlugin,
+		Msg::DeleteUnmanaged,
+		Msg::ConfigMatches,
+		Msg::Exported,
+		Msg::NoPluginsToDisable,
+		Msg::SafeModeOn,
+		Msg::DoesItCrash,
+		Msg::SafeModeCulprit,
+		Msg::MoveToken,
+		Msg::Downgrade,
+		Msg::Uninstall,
+		Msg::UninstallKeepPlugins,
+		Msg::RemoveProfile,
+		Msg::RemoveProfileKeepPlugins,
+	];
+
+	/// `{0}`, `{1}`, ... used in `text`, sorted.
+	fn placeholders(text: &str) -> Vec<usize> {
+		let mut found: Vec<usize> = (0..10)
+			.filter(|i| text.contains(&format!("{{{i}}}")))
+			.collect();
+		found.sort();
+		found
+	}
+
+	/// Exhaustive over `Lang`, so a new locale has to be added here too.
+	fn text(lang: Lang, msg: Msg) -> &'static str {
+		match lang {
+			Lang::En => en(msg),
+			Lang::De => de(msg),
+		}
+	}
+
+	#[test]
+	fn every_message_in_every_locale() {
+		for msg in ALL {
+			let english = placeholders(en(msg));
+			for lang in [Lang::En, Lang::De] {
+				let text = text(lang, msg);
+				assert!(!text.trim().is_empty(), "{msg:?} is empty in {lang:?}");
+				assert_eq!(placeholders(text), english, "{msg:?} in {lang:?}");
+				let args: Vec<&dyn Display> =
+					english.iter().map(|_| &"x" as &dyn Display).collect();
+				let filled = fill(text, &args);
+				assert!(
+					placeholders(&filled).is_empty(),
+					"{msg:?} in {lang:?}: {filled}"
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn fill_in_one_pass() {
+		assert_eq!(fill("{0} and {1}", &[&r"\d{1}", &"x"]), r"\d{1} and x");
+		assert_eq!(fill("{1} {0} {1}", &[&"a", &"b"]), "b a b");
+		assert_eq!(fill("{2} {x} {0", &[&"a"]), "{2} {x} {0");
+		assert_eq!(fill("{0}}", &[&"{"]), "{}");
+	}
+
+	#[test]
+	fn lang_from_tag() {
+		for (tag, lang) in [
+			("en", Some(Lang::En)),
+			("de-AT", Some(Lang::De)),
+			("de_DE.UTF-8", Some(Lang::De)),
+			("EN_us", Some(Lang::En)),
+			("fr", None),
+			("", None),
+		] {
+			assert_eq!(Lang::from_tag(tag), lang, "{tag}");
+		}
+	}
+}
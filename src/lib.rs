@@ -0,0 +1,112 @@
+//! Manage PowerToys Run plugins installed from GitHub releases.
+//!
+//! The `ptr` binary is a command line interface over [`config::Config`].
+
+mod asset_cache;
+mod backup;
+mod cache;
+pub mod config;
+mod credential;
+pub mod i18n;
+mod lock;
+mod managed;
+mod notification;
+mod polling;
+pub mod profile;
+mod report;
+pub mod reporter;
+pub mod safe_mode;
+pub mod state;
+pub mod util;
+
+use anyhow::{anyhow, Result};
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Paths ptr works with, resolved once per run from `%LOCALAPPDATA%` and the selected profile,
+/// and how the run may interact with the user and the network.
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+	/// `%LOCALAPPDATA%`.
+	pub local_app_data: PathBuf,
+	/// The selected profile, None for the default version.toml.
+	pub profile: Option<String>,
+	/// Directory PowerToys Run loads plugins from, `plugin_path` of the profile if one is
+	/// selected.
+	pub plugin_path: PathBuf,
+	/// Plugin directory of the default PowerToys Run, which also holds the configs of profiles.
+	pub default_plugin_path: PathBuf,
+	/// Configuration file of installed plugins.
+	pub config_path: PathBuf,
+	/// PowerToys Run settings, which store whether plugins are disabled, next to the plugin
+	/// directory.
+	pub run_settings_path: PathBuf,
+	/// Files of ptr itself that can be rebuilt at any time, in `profiles\<name>` for a profile.
+	pub cache_path: PathBuf,
+	/// Answer yes to confirmations and fail on other prompts, set by `--yes`.
+	pub assume_yes: bool,
+	/// Fail network requests, set by `--offline`.
+	pub offline: bool,
+}
+
+impl Context {
+	/// Paths in `%LOCALAPPDATA%`, of `profile` if it's given, which must exist.
+	///
+	/// Set `LOCALAPPDATA` to use another directory, e.g. on other platforms during development.
+	pub fn new(profile: Option<&str>) -> Result<Self> {
+		let local_app_data =
+			env::var_os("LOCALAPPDATA").ok_or(anyhow!("LOCALAPPDATA is not set"))?;
+		Self::with_local_app_data(PathBuf::from(local_app_data), profile)
+	}
+
+	/// Paths in `local_app_data` instead of `%LOCALAPPDATA%`.
+	pub fn with_local_app_data(local_app_data: PathBuf, profile: Option<&str>) -> Result<Self> {
+		let default_plugin_path = local_app_data
+			.join("Microsoft")
+			.join("PowerToys")
+			.join("PowerToys Run")
+			.join("Plugins");
+		let context = Self {
+			profile: None,
+			plugin_path: default_plugin_path.clone(),
+			config_path: default_plugin_path.join("version.toml"),
+			run_settings_path: run_settings_path(&default_plugin_path),
+			cache_path: local_app_data.join("ptr"),
+			default_plugin_path,
+			local_app_data,
+			assume_yes: false,
+			offline: false,
+		};
+		match profile {
+			Some(name) => context.for_profile(name),
+			None => Ok(context),
+		}
+	}
+
+	/// The paths of profile `name`, which must exist.
+	pub fn for_profile(&self, name: &str) -> Result<Self> {
+		profile::check_exists(self, name)?;
+		let plugin_path = profile::plugin_path(self, name)?;
+		Ok(Self {
+			profile: Some(name.to_string()),
+			config_path: profile::config_path(self, name),
+			run_settings_path: run_settings_path(&plugin_path),
+			cache_path: self.profile_cache_path(name),
+			plugin_path,
+			..self.clone()
+		})
+	}
+
+	/// Cache directory of profile `name`.
+	pub(crate) fn profile_cache_path(&self, name: &str) -> PathBuf {
+		self.local_app_data.join("ptr").join("profiles").join(name)
+	}
+}
+
+/// settings.json of the PowerToys Run loading plugins from `plugin_path`.
+fn run_settings_path(plugin_path: &Path) -> PathBuf {
+	plugin_path
+		.parent()
+		.unwrap_or(plugin_path)
+		.join("settings.json")
+}
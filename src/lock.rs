@@ -0,0 +1,108 @@
+//! Find processes locking files with the Restart Manager.
+
+use std::path::Path;
+#[cfg(windows)]
+use std::path::PathBuf;
+#[cfg(windows)]
+use std::{fs, iter};
+#[cfg(windows)]
+use windows::core::{PCWSTR, PWSTR};
+#[cfg(windows)]
+use windows::Win32::Foundation::{ERROR_MORE_DATA, ERROR_SUCCESS};
+#[cfg(windows)]
+use windows::Win32::System::RestartManager::{
+	RmEndSession, RmGetList, RmRegisterResources, RmStartSession, CCH_RM_SESSION_KEY,
+	RM_PROCESS_INFO,
+};
+
+#[cfg(not(windows))]
+pub fn locking_processes(_path: &Path) -> Vec<String> {
+	vec![]
+}
+
+/// Processes holding files in `path` as `name (pid)`, empty if none is found or the
+/// Restart Manager fails.
+#[cfg(windows)]
+pub fn locking_processes(path: &Path) -> Vec<String> {
+	let mut files = vec![];
+	collect_files(path, &mut files);
+	let files: Vec<Vec<u16>> = files
+		.iter()
+		.map(|f| {
+			f.as_os_str()
+				.to_string_lossy()
+				.encode_utf16()
+				.chain(iter::once(0))
+				.collect()
+		})
+		.collect();
+	let files: Vec<PCWSTR> = files.iter().map(|f| PCWSTR(f.as_ptr())).collect();
+	if files.is_empty() {
+		return vec![];
+	}
+
+	unsafe {
+		let mut session = 0;
+		let mut key = [0u16; CCH_RM_SESSION_KEY as usize + 1];
+		if RmStartSession(&mut session, None, PWSTR(key.as_mut_ptr())) != ERROR_SUCCESS {
+			return vec![];
+		}
+		let processes = get_list(session, &files);
+		_ = RmEndSession(session);
+		processes
+	}
+}
+
+#[cfg(windows)]
+unsafe fn get_list(session: u32, files: &[PCWSTR]) -> Vec<String> {
+	if RmRegisterResources(session, Some(files), None, None) != ERROR_SUCCESS {
+		return vec![];
+	}
+	let (mut needed, mut count, mut reasons) = (0, 0, 0);
+	let mut infos: Vec<RM_PROCESS_INFO> = vec![];
+	loop {
+		let result = RmGetList(
+			session,
+			&mut needed,
+			&mut count,
+			Some(infos.as_mut_ptr()),
+			&mut reasons,
+		);
+		if result == ERROR_MORE_DATA {
+			infos.resize(needed as usize, RM_PROCESS_INFO::default());
+			count = needed;
+			continue;
+		}
+		if result != ERROR_SUCCESS {
+			return vec![];
+		}
+		break;
+	}
+	infos
+		.iter()
+		.take(count as usize)
+		.map(|info| {
+			let name = &info.strAppName;
+			let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+			format!(
+				"{} ({})",
+				String::from_utf16_lossy(&name[..len]),
+				info.Process.dwProcessId
+			)
+		})
+		.collect()
+}
+
+#[cfg(windows)]
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+	if path.is_file() {
+		files.push(path.to_path_buf());
+		return;
+	}
+	let Ok(entries) = fs::read_dir(path) else {
+		return;
+	};
+	for entry in entries.filter_map(|e| e.ok()) {
+		collect_files(&entry.path(), files);
+	}
+}
@@ -1,20 +1,32 @@
-mod config;
-mod polling;
-mod util;
-
-use clap::{builder::styling, CommandFactory, Parser, Subcommand};
+use clap::builder::{styling, PossibleValuesParser};
+use clap::{CommandFactory, Parser, Subcommand};
 use clap_complete::aot::PowerShell;
-use std::{env, io, path::PathBuf, sync::LazyLock};
-use util::self_update;
-
-static PLUGIN_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
-	PathBuf::from(&env::var("LOCALAPPDATA").unwrap())
-		.join(r"Microsoft\PowerToys\PowerToys Run\Plugins")
-});
-static CONFIG_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
-	PathBuf::from(&env::var("LOCALAPPDATA").unwrap())
-		.join(r"Microsoft\PowerToys\PowerToys Run\Plugins\version.toml")
-});
+use ptr::config::{Arch, DiskColumns, RepoStyle, Scope};
+use ptr::reporter;
+use ptr::safe_mode::SafeMode;
+use ptr::state::State;
+use ptr::util::{
+	completion_install, completion_uninstall, confirm, parse_repo, schedule_install,
+	schedule_remove, self_uninstall, self_update, shell_open, supports_hyperlinks,
+};
+use ptr::{config, error, profile, t, warning, Context};
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::{path::PathBuf, process};
+use terminal::{Format, Terminal};
+
+mod terminal;
+
+/// Print a error message to stderr and exit with code 0.
+macro_rules! exit {
+    ($($arg:tt)*) => {{
+        error!($($arg)*);
+        process::exit(0);
+    }};
+}
+
+/// Exit code of `self-update --check` when an update is available.
+const UPDATE_AVAILABLE: i32 = 10;
 
 #[derive(Parser)]
 #[clap(
@@ -27,6 +39,38 @@ static CONFIG_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
 struct App {
 	#[clap(subcommand)]
 	cmd: TopCommand,
+
+	#[clap(short, long, global = true)]
+	/// Answer yes to all confirmations, and fail instead of asking for other input.
+	yes: bool,
+
+	#[clap(long, global = true)]
+	/// Fail commands that need the network without changing anything.
+	offline: bool,
+
+	#[clap(long, global = true)]
+	/// Don't kill or restart PowerToys, same as `no_kill` in config.
+	no_kill: bool,
+
+	#[clap(long, global = true)]
+	/// Don't run the `post_install` and `pre_remove` hooks of plugins.
+	no_hooks: bool,
+
+	#[clap(long, global = true)]
+	/// Don't check the free disk space before changing plugins.
+	skip_space_check: bool,
+
+	#[clap(long, global = true)]
+	/// GitHub token used for this run instead of the configured ones, never saved.
+	token: Option<String>,
+
+	#[clap(long, global = true, value_name = "NAME")]
+	/// Use `version.<NAME>.toml` and its PowerToys and plugin directory, see `ptr profile`.
+	profile: Option<String>,
+
+	#[clap(long, global = true, value_name = "FORMAT", default_value_t = Format::Text)]
+	/// Print progress and results as colored text, plain text or JSON lines, text, plain or json.
+	output: Format,
 }
 
 #[derive(Subcommand)]
@@ -36,38 +80,170 @@ enum TopCommand {
 	Add {
 		/// The name of the plugin, can be anything.
 		name: String,
-		/// The GitHub repository identifier or URL of the plugin.
+		/// The GitHub repository identifier or URL of the plugin, `<REPO>@<VERSION>` for a version.
 		repo: String,
 		#[clap(short, long)]
 		/// The target version of the plugin.
 		version: Option<String>,
+		#[clap(long)]
+		/// The directory to install to, defaults to the name in plugin.json.
+		dir_name: Option<String>,
+		#[clap(short, long)]
+		/// Regex matched against asset names, defaults to matching the arch.
+		pattern: Option<String>,
+		#[clap(long)]
+		/// Skip checking the extracted plugin for plugin.json and .dll files.
+		skip_validation: bool,
+		#[clap(long)]
+		/// Install even if the plugin DLL is built for another arch, e.g. AnyCPU plugins with
+		/// native stubs.
+		skip_arch_check: bool,
+		#[clap(long, value_name = "MB")]
+		/// Allow archives to extract to this many MB this time, for very large plugins.
+		max_extract_size: Option<u64>,
+		#[clap(long)]
+		/// Install the asset of this arch instead of the one in config, x64 or arm64.
+		arch: Option<Arch>,
+		#[clap(long, default_value = "user")]
+		/// Install for the current user, or for all users in the machine plugin directory, user
+		/// or machine.
+		scope: Scope,
+		#[clap(long)]
+		/// Print what would be installed without changing anything, exit with code 10 if
+		/// anything would change.
+		dry_run: bool,
+		#[clap(long)]
+		/// Proceed even if PowerToys is updating itself.
+		force: bool,
 	},
 
 	#[clap(visible_alias = "u", arg_required_else_help = true)]
 	/// Update plugins.
 	Update {
 		#[clap(num_args = 1..)]
-		/// The name of the plugins to update.
+		/// The name of the plugins to update, `<NAME>@<VERSION>` to update to a version.
 		name: Vec<String>,
 		#[clap(short, long)]
 		/// Update all plugins.
 		all: bool,
+		#[clap(long, num_args = 1.., requires = "all")]
+		/// Skip these plugins with `--all` this time, can be a glob pattern like `Git*`.
+		exclude: Vec<String>,
+		#[clap(long, conflicts_with = "all")]
+		/// Update the named plugins even if they are pinned, without asking.
+		force_pinned: bool,
+		#[clap(short, long, requires = "all", conflicts_with = "dry_run")]
+		/// Choose to update, skip or pin each outdated plugin with `--all` before updating.
+		interactive: bool,
 		#[clap(short, long)]
-		/// Version to update to.
+		/// Version to update to, deprecated in favor of `<NAME>@<VERSION>`.
 		version: Option<Vec<String>>,
+		#[clap(long)]
+		/// Skip checking the extracted plugin for plugin.json and .dll files.
+		skip_validation: bool,
+		#[clap(long)]
+		/// Install even if the plugin DLL is built for another arch, e.g. AnyCPU plugins with
+		/// native stubs.
+		skip_arch_check: bool,
+		#[clap(long, value_name = "MB")]
+		/// Allow archives to extract to this many MB this time, for very large plugins.
+		max_extract_size: Option<u64>,
+		#[clap(long)]
+		/// Show a toast notification summarizing the update.
+		notify: bool,
+		#[clap(long)]
+		/// Print files changed by the update.
+		verbose: bool,
+		#[clap(long)]
+		/// Install the asset of this arch instead of the one in config, x64 or arm64.
+		arch: Option<Arch>,
+		#[clap(long)]
+		/// Print what would be installed without changing anything, exit with code 10 if
+		/// anything would change.
+		dry_run: bool,
+		#[clap(long, value_name = "PATH", conflicts_with = "dry_run")]
+		/// Write a JSON report of the changed plugins to this file.
+		report: Option<PathBuf>,
+		#[clap(long)]
+		/// Proceed even if PowerToys is updating itself.
+		force: bool,
 	},
 
 	#[clap(visible_alias = "r", arg_required_else_help = true)]
 	/// Remove plugins.
 	Remove {
-		#[clap(num_args = 1..)]
+		#[clap(num_args = 1.., required_unless_present = "all")]
 		/// The name of the plugins to remove.
 		name: Vec<String>,
+		#[clap(short, long, conflicts_with = "name")]
+		/// Remove all plugins.
+		all: bool,
+		#[clap(long)]
+		/// Proceed even if PowerToys is updating itself.
+		force: bool,
 	},
 
 	#[clap(visible_alias = "l")]
 	/// List all installed plugins.
-	List,
+	List {
+		#[clap(short, long)]
+		/// Show the installed asset of each plugin.
+		verbose: bool,
+		#[clap(long)]
+		/// Show the latest version cached by updates.
+		latest: bool,
+		#[clap(long, requires = "latest")]
+		/// Fetch the latest version of every plugin before listing.
+		refresh: bool,
+		#[clap(long)]
+		/// Show the full URL of repositories instead of `owner/repo`.
+		repo_url: bool,
+		#[clap(long)]
+		/// Link repositories to their GitHub page even if the terminal isn't known to support
+		/// hyperlinks.
+		hyperlinks: bool,
+		#[clap(long)]
+		/// Show the version in plugin.json next to the recorded one, highlighted if they differ.
+		installed: bool,
+		#[clap(long)]
+		/// Show the release date of the installed version, and the author and description in
+		/// plugin.json.
+		long: bool,
+	},
+
+	#[clap()]
+	/// Fetch the latest version of every plugin and list the outdated ones, exit with code 10
+	/// if any is.
+	Check {
+		#[clap(long)]
+		/// Also write the counts for `ptr state`, later updates keep them fresh.
+		write_state: bool,
+	},
+
+	#[clap()]
+	/// Print the result of the last check instantly for shell prompts, nothing if it's stale or
+	/// nothing is outdated.
+	State {
+		#[clap(long, default_value = "{outdated}")]
+		/// Output with `{outdated}`, `{plugins}`, `{failed}` and `{checked_at}` replaced.
+		format: String,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Enable plugins in PowerToys Run.
+	Enable {
+		#[clap(num_args = 1..)]
+		/// The name of the plugins to enable.
+		name: Vec<String>,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Disable plugins in PowerToys Run without removing them.
+	Disable {
+		#[clap(num_args = 1..)]
+		/// The name of the plugins to disable.
+		name: Vec<String>,
+	},
 
 	#[clap(visible_alias = "p", arg_required_else_help = true)]
 	/// Pin plugins so it's not updated with `update --all`.
@@ -82,19 +258,234 @@ enum TopCommand {
 		#[clap(short, long)]
 		/// Update the configuration file without downloading the plugin.
 		dry_run: bool,
+		#[clap(long, value_name = "PATH", conflicts_with = "dry_run")]
+		/// Write a JSON report of the imported plugins to this file.
+		report: Option<PathBuf>,
+		#[clap(long, conflicts_with = "dry_run")]
+		/// Remove plugins installed by ptr that are no longer in the configuration file.
+		prune: bool,
+		#[clap(long)]
+		/// Install even if the plugin DLL is built for another arch, e.g. AnyCPU plugins with
+		/// native stubs.
+		skip_arch_check: bool,
+		#[clap(long, value_name = "MB")]
+		/// Allow archives to extract to this many MB this time, for very large plugins.
+		max_extract_size: Option<u64>,
+		#[clap(long, value_name = "PATH")]
+		/// Merge the plugins in a file written by `export` into the configuration first.
+		file: Option<PathBuf>,
+		#[clap(long, requires = "file")]
+		/// Don't merge the pins in the file.
+		no_pins: bool,
+		#[clap(long)]
+		/// Proceed even if PowerToys is updating itself.
+		force: bool,
+	},
+
+	#[clap()]
+	/// Initialize configuration from installed plugins.
+	Init {
+		#[clap(long)]
+		/// Replace the configuration file instead of merging into it.
+		overwrite: bool,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Manage the GitHub token in Windows Credential Manager.
+	Auth {
+		#[clap(subcommand)]
+		cmd: AuthSubcommand,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Print release notes from the installed version to the latest.
+	Changelog {
+		/// The name of the plugin, or `owner/repo` for a plugin not installed.
+		name: String,
+
+		#[clap(long)]
+		/// Print release notes up to this tag instead of the latest.
+		to: Option<String>,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Show the assets of a release and which one would be installed, to test `--pattern`.
+	Assets {
+		/// The GitHub repository identifier or URL, `<REPO>@<VERSION>` for a version.
+		repo: String,
+		#[clap(short, long)]
+		/// The release tag, defaults to the latest.
+		version: Option<String>,
+		#[clap(short, long)]
+		/// Regex matched against asset names, defaults to matching the arch.
+		pattern: Option<String>,
+		#[clap(long)]
+		/// Choose the asset for this arch instead of the one in config, x64 or arm64.
+		arch: Option<Arch>,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Print the directory of a plugin, or other paths used by ptr, for scripts.
+	Which {
+		#[clap(required_unless_present_any = ["config", "plugins_dir"])]
+		/// The name of the plugin.
+		name: Option<String>,
+
+		#[clap(long, conflicts_with_all = ["name", "plugins_dir"])]
+		/// Print the path of version.toml.
+		config: bool,
+
+		#[clap(long, conflicts_with = "name")]
+		/// Print the directory plugins are installed to.
+		plugins_dir: bool,
+	},
+
+	#[clap()]
+	/// Open version.toml in the default editor, or Notepad if there is none.
+	Edit,
+
+	#[clap(arg_required_else_help = true)]
+	/// Open the directory of a plugin in File Explorer.
+	Open {
+		/// The name of the plugin.
+		name: String,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Save version.toml and all installed plugins into a zip archive.
+	Backup {
+		/// The archive to write.
+		file: PathBuf,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Write the plugins and pins to a file for `import --file` on another machine.
+	Export {
+		/// The file to write.
+		file: PathBuf,
+
+		#[clap(long)]
+		/// Leave out the pins.
+		no_pins: bool,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Restore version.toml and plugins from an archive written by `backup`.
+	Restore {
+		/// The archive to restore from.
+		file: PathBuf,
+
+		#[clap(long)]
+		/// Restore even if version.toml is newer than the archive.
+		force: bool,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Check why an installed plugin doesn't show in PowerToys Run, exit with 1 if a check fails.
+	Explain {
+		/// The name of the plugin.
+		name: String,
+	},
+
+	/// Compare version.toml with the installed plugins, exit with 1 if they differ.
+	Diff {
+		#[clap(long)]
+		/// Write the versions in plugin.json into version.toml, rename directories that differ
+		/// by case or spaces and offer to delete unmanaged copies of plugins, nothing is
+		/// downloaded.
+		fix: bool,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Remove files ptr keeps to save work.
+	Clean {
+		#[clap(long)]
+		/// Prune the release assets in `asset_cache`.
+		cache: bool,
+		#[clap(long)]
+		/// Remove old plugin directories left by `swap_in_place` updates.
+		swapped: bool,
+		#[clap(long, value_name = "DAYS", default_value_t = 90)]
+		/// Remove cached assets not used for this many days.
+		max_age: u64,
+		#[clap(long, value_name = "MB")]
+		/// Then remove the least recently used assets until the cache fits in this size.
+		max_size: Option<u64>,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Disable all plugins at once, e.g. when PowerToys Run crashes on startup after an update.
+	SafeMode {
+		#[clap(subcommand)]
+		cmd: SafeModeSubcommand,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Manage the scheduled task updating all plugins weekly.
+	Schedule {
+		#[clap(subcommand)]
+		cmd: ScheduleSubcommand,
 	},
 
 	#[clap()]
 	/// Restart PowerToys.
-	Restart,
+	Restart {
+		#[clap(long)]
+		/// Wait until PowerToys is running again before exiting.
+		wait: bool,
+		#[clap(long)]
+		/// Proceed even if PowerToys is updating itself.
+		force: bool,
+	},
+
+	#[clap()]
+	/// Kill PowerToys without changing plugins.
+	Kill,
+
+	#[clap()]
+	/// Start PowerToys if it's not running.
+	Start,
+
+	#[clap()]
+	/// Remove ptr, its config, cache, scheduled task and stored token, and all plugins.
+	SelfUninstall {
+		#[clap(long)]
+		/// Keep the plugins and remove only the files of ptr.
+		keep_plugins: bool,
+	},
 
 	#[clap()]
 	/// Self update to latest.
-	SelfUpdate,
+	SelfUpdate {
+		#[clap(short, long)]
+		/// The target version, can be older than the current version.
+		version: Option<String>,
+		#[clap(short, long)]
+		/// Only check for update, exit with code 10 if an update is available.
+		check: bool,
+		#[clap(short, long)]
+		/// Update even if ptr is installed by a package manager.
+		force: bool,
+	},
+
+	#[clap(arg_required_else_help = true)]
+	/// Manage profiles for other PowerToys installs, selected with `--profile`.
+	Profile {
+		#[clap(subcommand)]
+		cmd: ProfileSubcommand,
+	},
 
 	#[clap()]
 	/// Generate shell completion (PowerShell).
-	Completion,
+	Completion {
+		#[clap(long, conflicts_with = "uninstall")]
+		/// Write the script to `%LOCALAPPDATA%\ptr\completion.ps1` and load it from the
+		/// PowerShell profiles.
+		install: bool,
+		#[clap(long)]
+		/// Remove the script and the lines loading it from the PowerShell profiles.
+		uninstall: bool,
+	},
 }
 
 #[derive(Subcommand)]
@@ -102,24 +493,118 @@ enum PinSubcommand {
 	#[clap(visible_alias = "a")]
 	/// Add pins.
 	Add {
-		#[clap(num_args = 1..)]
-		/// The name of the plugins to pin.
+		#[clap(num_args = 1.., required_unless_present = "all")]
+		/// The name of the plugins to pin, can be a glob pattern like `Git*`.
 		name: Vec<String>,
+		#[clap(short, long, conflicts_with = "name")]
+		/// Pin all plugins.
+		all: bool,
 	},
 	#[clap(visible_alias = "r")]
 	/// Remove pins
 	Remove {
 		#[clap(num_args = 1..)]
-		/// The name of the plugins to pin.
+		/// The name of the plugins to pin, can be a glob pattern like `Git*`.
 		name: Vec<String>,
 	},
 	#[clap(visible_alias = "l")]
 	/// List pins.
 	List,
+	/// Remove pins of plugins that are not installed.
+	Prune,
 	/// Clear all pins.
 	Reset,
 }
 
+#[derive(Subcommand)]
+enum AuthSubcommand {
+	/// Store a token, or move the token in version.toml.
+	Login,
+	/// Remove the stored token.
+	Logout,
+	/// Validate the token and show the rate limit.
+	Status,
+}
+
+#[derive(Subcommand)]
+enum ProfileSubcommand {
+	#[clap(visible_alias = "l")]
+	/// List profiles and their plugin directory.
+	List,
+	#[clap(arg_required_else_help = true)]
+	/// Create a profile, its plugin directory can't overlap another profile's.
+	Create {
+		/// The name of the profile.
+		name: String,
+		#[clap(long, value_name = "DIR")]
+		/// Directory the PowerToys Run of this install loads plugins from.
+		plugin_path: PathBuf,
+		#[clap(long, value_name = "PATH")]
+		/// PowerToys.exe of this install, detected if not given.
+		pt_path: Option<PathBuf>,
+	},
+	#[clap(visible_alias = "r", arg_required_else_help = true)]
+	/// Remove a profile, keeping its plugins.
+	Remove {
+		/// The name of the profile.
+		name: String,
+	},
+}
+
+#[derive(Subcommand)]
+enum SafeModeSubcommand {
+	/// Kill PowerToys and move every installed plugin aside as `<dir>.disabled`.
+	On,
+	/// Restore the plugins disabled by `on`.
+	Off,
+	/// Enable half of the disabled plugins at a time to find the one that crashes PowerToys Run.
+	Bisect,
+}
+
+#[derive(Subcommand)]
+enum ScheduleSubcommand {
+	/// Register the task running `ptr update --all --notify` weekly.
+	Install,
+	/// Remove the task.
+	Remove,
+}
+
+/// Split `name@version` into the name and the version.
+fn split_version(s: &str) -> (String, Option<String>) {
+	match s.split_once('@') {
+		Some((name, version)) => (name.to_string(), Some(version.to_string())),
+		None => (s.to_string(), None),
+	}
+}
+
+/// The repository from a URL or `owner/repo@version`, and the version from either it or
+/// `--version`.
+fn repo_version(repo: &str, version: Option<String>) -> (String, Option<String>) {
+	let repo = parse_repo(repo);
+	match (split_version(repo), version) {
+		((_, Some(_)), Some(_)) => exit!("Version is given both in repo and --version"),
+		((repo, v), version) => (repo, v.or(version)),
+	}
+}
+
+/// Pair plugin names with versions from `name@version`, or from `--version` in order.
+fn update_targets(
+	names: Vec<String>,
+	versions: Option<Vec<String>>,
+) -> Vec<(String, Option<String>)> {
+	let mut versions = versions.unwrap_or_default().into_iter();
+	if versions.len() > 0 {
+		warning!("--version is deprecated, use `ptr update <NAME>@<VERSION>` instead");
+	}
+	names
+		.iter()
+		.map(|n| {
+			let (name, version) = split_version(n);
+			(name, version.or_else(|| versions.next()))
+		})
+		.collect()
+}
+
 fn get_styles() -> clap::builder::Styles {
 	clap::builder::Styles::default()
 		.usage(styling::AnsiColor::BrightGreen.on_default())
@@ -131,61 +616,447 @@ fn get_styles() -> clap::builder::Styles {
 		.placeholder(styling::AnsiColor::Cyan.on_default())
 }
 
+/// PowerShell completion script.
+fn completion_script(ctx: &Context) -> Vec<u8> {
+	let mut script = vec![];
+	clap_complete::generate(
+		PowerShell,
+		// profile names are completed as of generating the script, leaked since clap needs
+		// static names and the process ends right after
+		&mut App::command().mut_arg("profile", |a| {
+			a.value_parser(PossibleValuesParser::new(
+				profile::names(ctx).into_iter().map(|n| &*n.leak()),
+			))
+		}),
+		"ptr",
+		&mut script,
+	);
+	script
+}
+
+/// Whether `cmd` changes plugin directories, refused while safe mode keeps disabled copies of
+/// them. Dry runs only read.
+fn changes_plugin_dirs(cmd: &TopCommand) -> bool {
+	matches!(
+		cmd,
+		TopCommand::Add { dry_run: false, .. }
+			| TopCommand::Update { dry_run: false, .. }
+			| TopCommand::Remove { .. }
+			| TopCommand::Import { dry_run: false, .. }
+			| TopCommand::Restore { .. }
+			| TopCommand::Diff { fix: true }
+			| TopCommand::Enable { .. }
+			| TopCommand::Disable { .. }
+			| TopCommand::SelfUninstall { .. }
+	)
+}
+
 fn main() {
 	let args = App::parse();
+	let _reporting = reporter::enter(Arc::new(Terminal::new(args.output)));
+	let mut ctx = Context::new(args.profile.as_deref()).unwrap_or_else(|e| exit!(e));
+	ctx.assume_yes = args.yes;
+	ctx.offline = args.offline;
+	if changes_plugin_dirs(&args.cmd) && SafeMode::is_on(&ctx.cache_path) {
+		exit!("Safe mode is on, run `ptr safe-mode off` before changing plugins");
+	}
+	let ignore_updating = matches!(
+		args.cmd,
+		TopCommand::Add { force: true, .. }
+			| TopCommand::Update { force: true, .. }
+			| TopCommand::Remove { force: true, .. }
+			| TopCommand::Import { force: true, .. }
+			| TopCommand::Restart { force: true, .. }
+	);
+	let override_run = |config: &mut config::Config| {
+		if args.no_kill {
+			config.override_no_kill();
+		}
+		if args.no_hooks {
+			config.skip_hooks();
+		}
+		if args.skip_space_check {
+			config.skip_space_check();
+		}
+		if ignore_updating {
+			config.ignore_updating();
+		}
+	};
 	match args.cmd {
-		TopCommand::Import { dry_run } => match config::Config::import() {
-			Ok(mut config) => {
-				if dry_run {
-					config.save().unwrap_or_else(|e| exit!(e));
-				} else {
-					config.import_plugins();
-				}
+		TopCommand::Import {
+			dry_run,
+			report,
+			prune,
+			skip_arch_check,
+			max_extract_size,
+			file,
+			no_pins,
+			..
+		} => {
+			let config = match file {
+				Some(file) => config::Config::import_file(ctx, &file, !no_pins),
+				None => config::Config::import(ctx),
+			};
+			let mut config = config.unwrap_or_else(|e| exit!(e));
+			override_run(&mut config);
+			if let Some(token) = args.token {
+				config.override_token(token);
+			}
+			if skip_arch_check {
+				config.skip_arch_check();
+			}
+			if let Some(mb) = max_extract_size {
+				config.override_max_extract_size(mb);
+			}
+			if let Some(report) = report {
+				config.report_to(report);
+			}
+			if dry_run {
+				config.save().unwrap_or_else(|e| exit!(e));
+			} else {
+				config.import_plugins(prune).unwrap_or_else(|e| exit!(e));
+			}
+		}
+		TopCommand::Profile { cmd } => match cmd {
+			ProfileSubcommand::List => profile::list(&ctx),
+			ProfileSubcommand::Create {
+				name,
+				plugin_path,
+				pt_path,
+			} => profile::create(&ctx, &name, &plugin_path, pt_path.as_deref()),
+			ProfileSubcommand::Remove { name } => profile::remove(&ctx, &name),
+		}
+		.unwrap_or_else(|e| exit!(e)),
+		TopCommand::Which { config: true, .. } => println!("{}", ctx.config_path.display()),
+		TopCommand::State { format } => {
+			if let Some(state) = State::load(&ctx.cache_path).filter(|s| s.outdated > 0) {
+				println!("{}", state.format(&format));
+			}
+		}
+		TopCommand::Which {
+			plugins_dir: true, ..
+		} => println!("{}", ctx.plugin_path.display()),
+		TopCommand::Edit => {
+			if !ctx.config_path.exists() {
+				exit!(
+					"{} not found, run `ptr init` first",
+					ctx.config_path.display()
+				);
 			}
+			shell_open(&ctx.config_path, "edit", "notepad.exe").unwrap_or_else(|e| exit!(e));
+		}
+		TopCommand::Init { overwrite } => match config::Config::init(ctx, overwrite) {
+			Ok(config) => print!("{}", config),
 			Err(e) => exit!(e),
 		},
-		TopCommand::SelfUpdate => self_update().unwrap_or_else(|e| exit!(e)),
-		_ => match config::Config::new() {
-			Ok(mut config) => match args.cmd {
-				TopCommand::Add {
-					name,
-					repo,
-					version,
-				} => config
-					.add(
-						&name,
-						if let Some(repo) = repo.strip_prefix("https://github.com/") {
-							repo.to_string()
+		TopCommand::SelfUpdate {
+			version,
+			check,
+			force,
+		} => match self_update(&ctx, version.as_deref(), check, force) {
+			Ok(true) if check => process::exit(UPDATE_AVAILABLE),
+			Ok(_) => {}
+			Err(e) => exit!(e),
+		},
+		TopCommand::SelfUninstall { keep_plugins } => {
+			let question = if keep_plugins {
+				t!(UninstallKeepPlugins)
+			} else {
+				t!(Uninstall)
+			};
+			if !confirm(&question, false, ctx.assume_yes).unwrap_or_else(|e| exit!(e)) {
+				exit!("Cancelled");
+			}
+			if !keep_plugins && ctx.config_path.exists() {
+				match config::Config::new(ctx.clone()) {
+					Ok(mut config) => {
+						override_run(&mut config);
+						config.remove(vec![], true).unwrap_or_else(|e| exit!(e))
+					}
+					Err(e) => exit!(e),
+				}
+			}
+			self_uninstall(&ctx).unwrap_or_else(|e| exit!(e));
+		}
+		TopCommand::Completion { install, uninstall } => {
+			let result = if install {
+				completion_install(&ctx, &completion_script(&ctx))
+			} else if uninstall {
+				completion_uninstall(&ctx)
+			} else {
+				io::stdout()
+					.write_all(&completion_script(&ctx))
+					.map_err(Into::into)
+			};
+			result.unwrap_or_else(|e| exit!(e));
+		}
+		TopCommand::Schedule { cmd } => match cmd {
+			ScheduleSubcommand::Install => schedule_install(),
+			ScheduleSubcommand::Remove => schedule_remove(),
+		}
+		.unwrap_or_else(|e| exit!(e)),
+		_ => match config::Config::new(ctx) {
+			Ok(mut config) => {
+				override_run(&mut config);
+				if let Some(token) = args.token {
+					config.override_token(token);
+				}
+				if !matches!(args.cmd, TopCommand::Update { .. }) {
+					config.stale_hint();
+				}
+				match args.cmd {
+					TopCommand::Add {
+						name,
+						repo,
+						version,
+						dir_name,
+						pattern,
+						skip_validation,
+						skip_arch_check,
+						max_extract_size,
+						arch,
+						scope,
+						dry_run,
+						..
+					} => {
+						if let Some(arch) = arch {
+							config.override_arch(arch);
+						}
+						if skip_arch_check {
+							config.skip_arch_check();
+						}
+						if let Some(mb) = max_extract_size {
+							config.override_max_extract_size(mb);
+						}
+						let (repo, version) = repo_version(&repo, version);
+						if dry_run {
+							let changes = config
+								.add_dry_run(
+									&name,
+									&repo,
+									version.as_deref(),
+									dir_name.as_deref(),
+									pattern.as_deref(),
+									scope,
+								)
+								.unwrap_or_else(|e| exit!(e));
+							if changes {
+								process::exit(UPDATE_AVAILABLE);
+							}
 						} else {
-							repo
-						},
+							config
+								.add(
+									&name,
+									repo,
+									version,
+									dir_name,
+									pattern,
+									scope,
+									skip_validation,
+								)
+								.unwrap_or_else(|e| exit!(e))
+						}
+					}
+					TopCommand::Update {
+						name,
+						all,
+						exclude,
+						force_pinned,
+						interactive,
 						version,
-					)
+						skip_validation,
+						skip_arch_check,
+						max_extract_size,
+						notify,
+						verbose,
+						arch,
+						dry_run,
+						report,
+						..
+					} => {
+						if let Some(arch) = arch {
+							config.override_arch(arch);
+						}
+						if skip_arch_check {
+							config.skip_arch_check();
+						}
+						if let Some(mb) = max_extract_size {
+							config.override_max_extract_size(mb);
+						}
+						if let Some(report) = report {
+							config.report_to(report);
+						}
+						if dry_run {
+							let targets = if all {
+								config
+									.all_targets(&exclude)
+									.into_iter()
+									.map(|name| (name, None))
+									.collect()
+							} else {
+								config
+									.filter_pinned(update_targets(name, version), force_pinned)
+									.unwrap_or_else(|e| exit!(e))
+							};
+							if config.update_dry_run(targets) {
+								process::exit(UPDATE_AVAILABLE);
+							}
+						} else {
+							let result = if interactive {
+								config.update_interactive(
+									&exclude,
+									skip_validation,
+									notify,
+									verbose,
+								)
+							} else if all {
+								config.update_all(&exclude, skip_validation, notify, verbose)
+							} else {
+								config
+									.filter_pinned(update_targets(name, version), force_pinned)
+									.and_then(|targets| {
+										config.update(targets, skip_validation, notify, verbose)
+									})
+							};
+							result.unwrap_or_else(|e| exit!(e));
+						}
+					}
+					TopCommand::Remove { name, all, .. } => {
+						config.remove(name, all).unwrap_or_else(|e| exit!(e))
+					}
+					TopCommand::SafeMode { cmd } => match cmd {
+						SafeModeSubcommand::On => config.safe_mode_on(),
+						SafeModeSubcommand::Off => config.safe_mode_off(),
+						SafeModeSubcommand::Bisect => config.safe_mode_bisect(),
+					}
+					.unwrap_or_else(|e| exit!(e)),
+					TopCommand::Pin { cmd } => match cmd {
+						PinSubcommand::Add { name, all } => config.pin_add(name, all),
+						PinSubcommand::List => {
+							config.pin_list();
+							Ok(())
+						}
+						PinSubcommand::Remove { name } => config.pin_remove(name),
+						PinSubcommand::Prune => config.pin_prune(),
+						PinSubcommand::Reset => config.pin_reset(),
+					}
 					.unwrap_or_else(|e| exit!(e)),
-				TopCommand::Update { name, all, version } => {
-					if all {
-						config.update_all();
-					} else {
-						config.update(name, version);
+					TopCommand::Auth { cmd } => match cmd {
+						AuthSubcommand::Login => config.auth_login(),
+						AuthSubcommand::Logout => config.auth_logout(),
+						AuthSubcommand::Status => config.auth_status(),
+					}
+					.unwrap_or_else(|e| exit!(e)),
+					TopCommand::List {
+						verbose,
+						latest,
+						refresh,
+						repo_url,
+						hyperlinks,
+						installed,
+						long,
+					} => {
+						let repo = RepoStyle {
+							url: repo_url,
+							hyperlinks: hyperlinks || supports_hyperlinks(),
+						};
+						config
+							.list(
+								verbose,
+								latest,
+								refresh,
+								repo,
+								DiskColumns { installed, long },
+							)
+							.unwrap_or_else(|e| exit!(e))
+					}
+					TopCommand::Enable { name } => config
+						.set_disabled(name, false)
+						.unwrap_or_else(|e| exit!(e)),
+					TopCommand::Disable { name } => {
+						config.set_disabled(name, true).unwrap_or_else(|e| exit!(e))
+					}
+					TopCommand::Changelog { name, to } => config
+						.changelog(&name, to.as_deref())
+						.unwrap_or_else(|e| exit!(e)),
+					TopCommand::Assets {
+						repo,
+						version,
+						pattern,
+						arch,
+					} => {
+						if let Some(arch) = arch {
+							config.override_arch(arch);
+						}
+						let (repo, version) = repo_version(&repo, version);
+						config
+							.assets(&repo, version.as_deref(), pattern.as_deref())
+							.unwrap_or_else(|e| exit!(e))
 					}
+					TopCommand::Open { name } => match config.plugin_dir(&name) {
+						Ok(dir) => {
+							shell_open(&dir, "open", "explorer.exe").unwrap_or_else(|e| exit!(e))
+						}
+						Err(e) => exit!(e),
+					},
+					TopCommand::Which { name, .. } => {
+						match config.plugin_dir(&name.unwrap_or_default()) {
+							Ok(dir) => println!("{}", dir.display()),
+							Err(e) => {
+								error!(e);
+								process::exit(1);
+							}
+						}
+					}
+					TopCommand::Check { write_state } => {
+						if config.check(write_state).unwrap_or_else(|e| exit!(e)) {
+							process::exit(UPDATE_AVAILABLE);
+						}
+					}
+					TopCommand::Backup { file } => {
+						config.backup(&file).unwrap_or_else(|e| exit!(e))
+					}
+					TopCommand::Export { file, no_pins } => {
+						config.export(&file, !no_pins).unwrap_or_else(|e| exit!(e))
+					}
+					TopCommand::Restore { file, force } => {
+						config.restore(&file, force).unwrap_or_else(|e| exit!(e))
+					}
+					TopCommand::Explain { name } => {
+						if config.explain(&name).unwrap_or_else(|e| exit!(e)) {
+							process::exit(1);
+						}
+					}
+					TopCommand::Diff { fix } => {
+						if config.diff(fix).unwrap_or_else(|e| exit!(e)) {
+							process::exit(1);
+						}
+					}
+					TopCommand::Clean {
+						cache,
+						swapped,
+						max_age,
+						max_size,
+					} => {
+						if swapped {
+							config.clean_swapped(true);
+						}
+						if cache {
+							config
+								.clean_cache(Some(max_age), max_size)
+								.unwrap_or_else(|e| exit!(e));
+						} else if !swapped {
+							exit!("Nothing to clean, pass --cache or --swapped");
+						}
+					}
+					TopCommand::Restart { wait, .. } => {
+						config.restart(wait).unwrap_or_else(|e| exit!(e))
+					}
+					TopCommand::Kill => config.kill_now().unwrap_or_else(|e| exit!(e)),
+					TopCommand::Start => config.start_now().unwrap_or_else(|e| exit!(e)),
+					_ => unreachable!(),
 				}
-				TopCommand::Remove { name } => config.remove(name),
-				TopCommand::Pin { cmd } => match cmd {
-					PinSubcommand::Add { name } => config.pin_add(name),
-					PinSubcommand::List => config.pin_list(),
-					PinSubcommand::Remove { name } => config.pin_remove(name),
-					PinSubcommand::Reset => config.pin_reset(),
-				},
-				TopCommand::List => print!("{}", config),
-				TopCommand::Restart => config.restart(),
-				TopCommand::Completion => clap_complete::generate(
-					PowerShell,
-					&mut App::command(),
-					"ptr",
-					&mut io::stdout(),
-				),
-				_ => unreachable!(),
-			},
+			}
 			Err(e) => exit!(e),
 		},
 	}
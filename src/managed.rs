@@ -0,0 +1,52 @@
+//! Plugins ptr installed as of the last saved config, so `import --prune` only removes plugins
+//! it manages even after version.toml was replaced.
+//!
+//! The record is never required, nothing is pruned if it's missing.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Managed {
+	#[serde(default)]
+	pub plugins: BTreeMap<String, ManagedPlugin>,
+}
+
+/// Enough of a plugin entry to find and remove its directory, tokens are never written here.
+#[derive(Serialize, Deserialize)]
+pub struct ManagedPlugin {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub dir: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub root: Option<PathBuf>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub pre_remove: Option<String>,
+}
+
+impl Managed {
+	fn path(dir: &Path) -> PathBuf {
+		dir.join("managed.toml")
+	}
+
+	/// Load the record, empty if it doesn't exist or can't be read.
+	pub fn load(dir: &Path) -> Self {
+		fs::read_to_string(Self::path(dir))
+			.ok()
+			.and_then(|s| toml::from_str(&s).ok())
+			.unwrap_or_default()
+	}
+
+	/// Write the record, skipped if the file is the same.
+	pub fn save(&self, dir: &Path) -> Result<()> {
+		let content = toml::to_string(self)?;
+		if fs::read_to_string(Self::path(dir)).is_ok_and(|c| c == content) {
+			return Ok(());
+		}
+		fs::create_dir_all(dir)?;
+		fs::write(Self::path(dir), content)?;
+		Ok(())
+	}
+}
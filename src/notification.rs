@@ -0,0 +1,78 @@
+//! Summarize batch operations in a Windows toast notification.
+
+use crate::t;
+#[cfg(windows)]
+use windows::core::{Result, HSTRING};
+#[cfg(windows)]
+use windows::Data::Xml::Dom::XmlDocument;
+#[cfg(windows)]
+use windows::UI::Notifications::{ToastNotification, ToastNotificationManager};
+
+/// Toasts are only available on Windows.
+#[cfg(not(windows))]
+fn show(_title: &str, _lines: &[String]) -> anyhow::Result<()> {
+	anyhow::bail!(crate::util::UNSUPPORTED)
+}
+
+/// Windows PowerShell's AppUserModelID, which is registered on every system so unpackaged apps
+/// can show toasts with it.
+#[cfg(windows)]
+const APP_ID: &str =
+	r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+#[derive(Default, Debug, PartialEq)]
+pub struct Summary {
+	updated: Vec<String>,
+	failed: Vec<String>,
+}
+
+impl Summary {
+	pub fn updated(&mut self, name: &str, version: &str) {
+		self.updated.push(format!("{name} {version}"));
+	}
+
+	pub fn failed(&mut self, name: &str) {
+		self.failed.push(name.to_string());
+	}
+
+	/// Show the summary, nothing is shown if no plugin changed.
+	/// Errors are ignored since the toast APIs may not be available.
+	pub fn notify(&self) {
+		let mut lines = vec![];
+		match self.updated.len() {
+			0 => {}
+			1 => lines.push(t!(PluginUpdated, self.updated[0])),
+			n => lines.push(t!(PluginsUpdated, n, self.updated.join(", "))),
+		}
+		if !self.failed.is_empty() {
+			lines.push(t!(UpdateFailed, self.failed.join(", ")));
+		}
+		if !lines.is_empty() {
+			_ = show("ptr", &lines);
+		}
+	}
+}
+
+#[cfg(windows)]
+fn show(title: &str, lines: &[String]) -> Result<()> {
+	let text: String = lines
+		.iter()
+		.map(|l| format!("<text>{}</text>", escape(l)))
+		.collect();
+	let xml = XmlDocument::new()?;
+	xml.LoadXml(&HSTRING::from(format!(
+		r#"<toast><visual><binding template="ToastGeneric"><text>{}</text>{}</binding></visual></toast>"#,
+		escape(title),
+		text
+	)))?;
+	let toast = ToastNotification::CreateToastNotification(&xml)?;
+	ToastNotificationManager::CreateToastNotifierWithId(&HSTRING::from(APP_ID))?.Show(&toast)
+}
+
+#[cfg(windows)]
+fn escape(s: &str) -> String {
+	s.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
@@ -1,32 +1,74 @@
 //! Retry IO operations on error.
 
+use std::fs::{self, File};
+use std::io;
 use std::io::{Read, Write};
 use std::path::Path;
+use std::sync::atomic::{self, AtomicU64};
 use std::thread;
-use std::time::Duration;
-use std::{fs, io};
+use std::time::{Duration, Instant};
 
-const MAX_RETRIES: u32 = 10;
-const RETRY_DELAY: Duration = Duration::from_millis(50);
+use crate::lock;
+use crate::util::long_path;
 
-fn retry<F, T, E>(mut operation: F) -> Result<T, io::Error>
+const INITIAL_DELAY: Duration = Duration::from_millis(50);
+const MAX_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+
+/// Total time to keep retrying, see [`set_timeout`].
+static TIMEOUT_MS: AtomicU64 = AtomicU64::new(DEFAULT_TIMEOUT_MS);
+
+/// Set the total time to keep retrying an operation.
+pub fn set_timeout(timeout: Duration) {
+	TIMEOUT_MS.store(timeout.as_millis() as u64, atomic::Ordering::Relaxed);
+}
+
+/// Whether the error may go away, e.g. a file is being scanned by antivirus.
+fn is_transient(err: &io::Error) -> bool {
+	const ERROR_ACCESS_DENIED: i32 = 5;
+	const ERROR_SHARING_VIOLATION: i32 = 32;
+	const ERROR_LOCK_VIOLATION: i32 = 33;
+	matches!(
+		err.raw_os_error(),
+		Some(ERROR_ACCESS_DENIED | ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)
+	) || matches!(
+		err.kind(),
+		io::ErrorKind::PermissionDenied | io::ErrorKind::Interrupted
+	)
+}
+
+/// Retry transient errors with exponential backoff until the timeout, other errors are returned
+/// immediately.
+fn retry<F, T, E>(operation: F) -> Result<T, io::Error>
 where
 	F: FnMut() -> Result<T, E>,
 	E: Into<io::Error>,
 {
-	let mut last_error = None;
+	let timeout = Duration::from_millis(TIMEOUT_MS.load(atomic::Ordering::Relaxed));
+	retry_within(timeout, operation)
+}
 
-	for _ in 0..MAX_RETRIES {
+/// `retry` with its own timeout.
+fn retry_within<F, T, E>(timeout: Duration, mut operation: F) -> Result<T, io::Error>
+where
+	F: FnMut() -> Result<T, E>,
+	E: Into<io::Error>,
+{
+	let start = Instant::now();
+	let mut delay = INITIAL_DELAY;
+	loop {
 		match operation() {
 			Ok(result) => return Ok(result),
 			Err(err) => {
-				last_error = Some(err.into());
-				thread::sleep(RETRY_DELAY);
+				let err = err.into();
+				if !is_transient(&err) || start.elapsed() + delay > timeout {
+					return Err(err);
+				}
+				thread::sleep(delay);
+				delay = (delay * 2).min(MAX_DELAY);
 			}
 		}
 	}
-
-	Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::Other, "Max retries reached")))
 }
 
 /// Wrapper around `io::copy` that retries on errors.
@@ -40,7 +82,126 @@ where
 	retry(|| io::copy(reader, writer))
 }
 
-/// Wrapper around `fs::remove_dir_all` that retries on errors.
+/// Wrapper around `File::create` that retries on errors.
+/// An existing read-only file is made writable first.
+pub fn create<P: AsRef<Path>>(path: P) -> io::Result<File> {
+	let path = path.as_ref();
+	if path.exists() {
+		set_writable(path)?;
+	}
+	retry(|| File::create(path))
+}
+
+/// Wrapper around `fs::remove_dir_all` that retries on errors, supporting long paths
+/// and read-only files.
 pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
-	retry(|| fs::remove_dir_all(path.as_ref()))
+	let path = long_path(path.as_ref());
+	_ = set_writable_all(&path);
+	retry(|| fs::remove_dir_all(&path)).map_err(|e| with_lock_info(e, &path))
+}
+
+/// Clear the read-only attribute.
+#[allow(clippy::permissions_set_readonly_false)]
+fn set_writable(path: &Path) -> io::Result<()> {
+	let mut permissions = fs::metadata(path)?.permissions();
+	if permissions.readonly() {
+		permissions.set_readonly(false);
+		fs::set_permissions(path, permissions)?;
+	}
+	Ok(())
+}
+
+/// Clear the read-only attribute of all files in `dir` recursively.
+fn set_writable_all(dir: &Path) -> io::Result<()> {
+	for entry in fs::read_dir(dir)? {
+		let path = entry?.path();
+		if path.is_dir() {
+			set_writable_all(&path)?;
+		} else {
+			set_writable(&path)?;
+		}
+	}
+	Ok(())
+}
+
+/// Wrapper around `fs::rename` that retries on errors.
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+	retry(|| fs::rename(from.as_ref(), to.as_ref())).map_err(|e| with_lock_info(e, from.as_ref()))
+}
+
+/// Add the processes locking `path` to sharing violation and access denied errors.
+fn with_lock_info(err: io::Error, path: &Path) -> io::Error {
+	const ERROR_ACCESS_DENIED: i32 = 5;
+	const ERROR_SHARING_VIOLATION: i32 = 32;
+	if !matches!(
+		err.raw_os_error(),
+		Some(ERROR_ACCESS_DENIED | ERROR_SHARING_VIOLATION)
+	) {
+		return err;
+	}
+	let processes = lock::locking_processes(path);
+	if processes.is_empty() {
+		return err;
+	}
+	io::Error::new(
+		err.kind(),
+		format!("{}, locked by: {}", err, processes.join(", ")),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::Cell;
+	use std::rc::Rc;
+
+	/// An operation failing with `kind` `failures` times before it succeeds, and its call count.
+	fn flaky(
+		kind: io::ErrorKind,
+		failures: usize,
+	) -> (impl FnMut() -> io::Result<&'static str>, Rc<Cell<usize>>) {
+		let calls = Rc::new(Cell::new(0));
+		let counter = calls.clone();
+		let operation = move || {
+			counter.set(counter.get() + 1);
+			if counter.get() <= failures {
+				Err(io::Error::from(kind))
+			} else {
+				Ok("done")
+			}
+		};
+		(operation, calls)
+	}
+
+	#[test]
+	fn retry_transient_until_success() {
+		let (operation, calls) = flaky(io::ErrorKind::PermissionDenied, 2);
+		let start = Instant::now();
+		assert_eq!(
+			retry_within(Duration::from_secs(10), operation).unwrap(),
+			"done"
+		);
+		assert_eq!(calls.get(), 3);
+		// backoff of 50 ms, then 100 ms
+		assert!(start.elapsed() >= Duration::from_millis(150));
+	}
+
+	#[test]
+	fn retry_fails_fast_on_permanent_error() {
+		let (operation, calls) = flaky(io::ErrorKind::NotFound, 1);
+		let start = Instant::now();
+		let err = retry_within(Duration::from_secs(10), operation).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::NotFound);
+		assert_eq!(calls.get(), 1);
+		assert!(start.elapsed() < INITIAL_DELAY);
+	}
+
+	#[test]
+	fn retry_gives_up_after_timeout() {
+		let (operation, calls) = flaky(io::ErrorKind::PermissionDenied, usize::MAX);
+		let err = retry_within(Duration::from_millis(400), operation).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+		// retried after 50, 100 and 200 ms, the next 400 ms would go over the timeout
+		assert_eq!(calls.get(), 4);
+	}
 }
@@ -0,0 +1,208 @@
+//! Profiles select `version.<name>.toml` next to the default version.toml instead of it, each
+//! with its own `pt_path` and `plugin_path`, e.g. for a preview build of PowerToys installed
+//! next to the stable one.
+//!
+//! A profile is chosen with `--profile` when the `Context` of the run is created, its paths
+//! then point into it.
+
+use anyhow::{anyhow, bail, Result};
+use colored::Colorize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+use tabwriter::TabWriter;
+
+use crate::config::Config;
+use crate::util::confirm;
+use crate::{info, print_message, remove, t, Context};
+
+/// Fail if the profile doesn't exist.
+pub(crate) fn check_exists(ctx: &Context, name: &str) -> Result<()> {
+	validate_name(name)?;
+	if !config_path(ctx, name).exists() {
+		bail!(
+			"Profile {} doesn't exist, create it with `ptr profile create {} --plugin-path <DIR>`",
+			name,
+			name
+		);
+	}
+	Ok(())
+}
+
+/// Config file of the profile.
+pub fn config_path(ctx: &Context, name: &str) -> PathBuf {
+	ctx.default_plugin_path.join(format!("version.{name}.toml"))
+}
+
+/// Names of the existing profiles, sorted.
+pub fn names(ctx: &Context) -> Vec<String> {
+	let Ok(entries) = fs::read_dir(&ctx.default_plugin_path) else {
+		return vec![];
+	};
+	let mut names: Vec<String> = entries
+		.flatten()
+		.filter_map(|e| {
+			let file = e.file_name().to_string_lossy().into_owned();
+			let name = file.strip_prefix("version.")?.strip_suffix(".toml")?;
+			validate_name(name).is_ok().then(|| name.to_string())
+		})
+		.collect();
+	names.sort();
+	names
+}
+
+/// `plugin_path` of the profile, checked not to overlap the default plugin directory or the one
+/// of another profile.
+pub fn plugin_path(ctx: &Context, name: &str) -> Result<PathBuf> {
+	let path = read_plugin_path(ctx, name)?;
+	check_overlap(ctx, name, &path)?;
+	Ok(path)
+}
+
+fn read_plugin_path(ctx: &Context, name: &str) -> Result<PathBuf> {
+	let path = config_path(ctx, name);
+	let content = fs::read_to_string(&path)
+		.map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+	let table: toml::Table = toml::from_str(&content)
+		.map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+	table
+		.get("plugin_path")
+		.and_then(|v| v.as_str())
+		.map(PathBuf::from)
+		.ok_or_else(|| anyhow!("Set plugin_path in {}", path.display()))
+}
+
+/// Fail if `path` is, or is inside or around, the plugin directory of the default config or
+/// another profile, so profiles never manage the same plugins.
+fn check_overlap(ctx: &Context, name: &str, path: &Path) -> Result<()> {
+	let others = names(ctx)
+		.into_iter()
+		.filter(|n| n != name)
+		.filter_map(|n| read_plugin_path(ctx, &n).ok().map(|p| (n, p)))
+		.chain([("default".to_string(), ctx.default_plugin_path.clone())]);
+	for (other, other_path) in others {
+		if overlaps(path, &other_path) {
+			bail!(
+				"plugin_path {} of profile {} overlaps {} of profile {}",
+				path.display(),
+				name,
+				other_path.display(),
+				other
+			);
+		}
+	}
+	Ok(())
+}
+
+/// Whether one path is the other or inside it, ignoring case like Windows.
+fn overlaps(a: &Path, b: &Path) -> bool {
+	let normalize = |p: &Path| {
+		let p = fs::canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+		PathBuf::from(p.to_string_lossy().to_lowercase())
+	};
+	let (a, b) = (normalize(a), normalize(b));
+	a.starts_with(&b) || b.starts_with(&a)
+}
+
+/// Profile names end up in file names, so they're limited to letters, digits, `-` and `_`.
+fn validate_name(name: &str) -> Result<()> {
+	if name.is_empty()
+		|| name.eq_ignore_ascii_case("default")
+		|| !name
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+	{
+		bail!(
+			"Invalid profile name '{}', use letters, digits, - and _, and not 'default'",
+			name
+		);
+	}
+	Ok(())
+}
+
+/// Print the profiles with their plugin directory, marking the selected one.
+pub fn list(ctx: &Context) -> Result<()> {
+	let mut tw = TabWriter::new(vec![]);
+	let mark = |selected: bool| if selected { "*" } else { " " };
+	writeln!(
+		&mut tw,
+		"{} {}\t{}",
+		mark(ctx.profile.is_none()),
+		"default".bright_cyan(),
+		ctx.default_plugin_path.display()
+	)?;
+	for name in names(ctx) {
+		let path = match read_plugin_path(ctx, &name) {
+			Ok(path) => path.display().to_string(),
+			Err(e) => e.to_string().bright_red().to_string(),
+		};
+		writeln!(
+			&mut tw,
+			"{} {}\t{}",
+			mark(ctx.profile.as_ref() == Some(&name)),
+			name.bright_cyan(),
+			path
+		)?;
+	}
+	info!(String::from_utf8(tw.into_inner()?)?.trim_end_matches('\n'));
+	Ok(())
+}
+
+/// Write the config of a new profile, detecting `pt_path` if it's not given.
+pub fn create(ctx: &Context, name: &str, plugin_path: &Path, pt_path: Option<&Path>) -> Result<()> {
+	validate_name(name)?;
+	let path = config_path(ctx, name);
+	if path.exists() {
+		bail!("Profile {} already exists at {}", name, path.display());
+	}
+	let plugin_path = env::current_dir()?.join(plugin_path);
+	check_overlap(ctx, name, &plugin_path)?;
+
+	let mut table = toml::Table::new();
+	table.insert(
+		"plugin_path".to_string(),
+		plugin_path.to_string_lossy().into_owned().into(),
+	);
+	if let Some(pt_path) = pt_path {
+		table.insert(
+			"pt_path".to_string(),
+			pt_path.to_string_lossy().into_owned().into(),
+		);
+	}
+	table.insert("plugins".to_string(), toml::Table::new().into());
+	fs::create_dir_all(&ctx.default_plugin_path)?;
+	fs::write(&path, toml::to_string(&table)?)?;
+	// filled in like `import` does, so missing fields are detected
+	let result = ctx
+		.for_profile(name)
+		.and_then(|ctx| Config::import(ctx)?.save());
+	if let Err(e) = result {
+		_ = fs::remove_file(&path);
+		return Err(e);
+	}
+	print_message!("+", bright_green, "{} {}", name, plugin_path.display());
+	Ok(())
+}
+
+/// Remove the config of a profile, its plugins are kept.
+pub fn remove(ctx: &Context, name: &str) -> Result<()> {
+	validate_name(name)?;
+	let path = config_path(ctx, name);
+	if !path.exists() {
+		bail!("Profile {} doesn't exist", name);
+	}
+	let question = match read_plugin_path(ctx, name).ok() {
+		Some(plugins) => t!(RemoveProfileKeepPlugins, name, plugins.display()),
+		None => t!(RemoveProfile, name),
+	};
+	if !confirm(&question, false, ctx.assume_yes)? {
+		bail!("Cancelled");
+	}
+	fs::remove_file(&path)?;
+	let cache = ctx.profile_cache_path(name);
+	if cache.exists() {
+		fs::remove_dir_all(cache)?;
+	}
+	remove!(name);
+	Ok(())
+}
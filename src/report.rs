@@ -0,0 +1,187 @@
+//! JSON report of the plugins changed by one run, written by `--report`.
+//!
+//! Fields are only ever added, so scripts reading the report keep working.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Debug)]
+pub struct Report {
+	#[serde(skip)]
+	path: Option<PathBuf>,
+	ptr_version: &'static str,
+	/// Unix time the run started.
+	started_at: u64,
+	/// Unix time the report was written.
+	finished_at: u64,
+	plugins: Vec<Entry>,
+}
+
+#[derive(Serialize, Debug)]
+struct Entry {
+	name: String,
+	previous_version: Option<String>,
+	version: Option<String>,
+	/// When the release of `version` was published.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	released: Option<String>,
+	action: Action,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	message: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+	Installed,
+	Updated,
+	UpToDate,
+	SkippedPinned,
+	SkippedExcluded,
+	Removed,
+	Failed,
+}
+
+impl Default for Report {
+	fn default() -> Self {
+		Self {
+			path: None,
+			ptr_version: env!("CARGO_PKG_VERSION"),
+			started_at: now(),
+			finished_at: 0,
+			plugins: vec![],
+		}
+	}
+}
+
+impl Report {
+	/// Write the report to `path` at the end of the run.
+	pub fn write_to(&mut self, path: PathBuf) {
+		self.path = Some(path);
+	}
+
+	/// Record `action` on the plugin, `version` is the version after the action.
+	pub fn record(
+		&mut self,
+		name: &str,
+		previous_version: Option<&str>,
+		version: Option<&str>,
+		action: Action,
+	) {
+		self.plugins.push(Entry {
+			name: name.to_string(),
+			previous_version: previous_version.map(String::from),
+			version: version.map(String::from),
+			released: None,
+			action,
+			message: None,
+		});
+	}
+
+	pub fn failed(&mut self, name: &str, previous_version: Option<&str>, message: String) {
+		self.plugins.push(Entry {
+			name: name.to_string(),
+			previous_version: previous_version.map(String::from),
+			version: None,
+			released: None,
+			action: Action::Failed,
+			message: Some(message),
+		});
+	}
+
+	/// Fill in when the release each plugin is at was published, by name.
+	pub fn fill_released(&mut self, released: impl Fn(&str) -> Option<String>) {
+		for entry in self.plugins.iter_mut().filter(|e| e.version.is_some()) {
+			entry.released = released(&entry.name);
+		}
+	}
+
+	/// Write the report if a path is set.
+	pub fn write(&mut self) -> Result<()> {
+		let Some(path) = self.path.clone() else {
+			return Ok(());
+		};
+		self.finished_at = now();
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::write(path, serde_json::to_string_pretty(self)?)?;
+		Ok(())
+	}
+}
+
+fn now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn serialization_schema() {
+		let mut report = Report {
+			started_at: 1_700_000_000,
+			finished_at: 1_700_000_060,
+			..Report::default()
+		};
+		report.record("A", None, Some("1.0.0"), Action::Installed);
+		report.record("B", Some("1.0.0"), Some("1.1.0"), Action::Updated);
+		report.record("C", Some("2.0.0"), Some("2.0.0"), Action::UpToDate);
+		report.record("D", Some("1.0.0"), Some("1.0.0"), Action::SkippedPinned);
+		report.record("E", Some("1.0.0"), Some("1.0.0"), Action::SkippedExcluded);
+		report.record("F", Some("1.0.0"), None, Action::Removed);
+		report.failed("G", Some("1.0.0"), "Failed to download".to_string());
+		report.fill_released(|name| (name == "B").then(|| "2024-05-01T00:00:00Z".to_string()));
+
+		let entry = |name: &str, previous: Option<&str>, version: Option<&str>, action: &str| {
+			serde_json::json!({
+				"name": name,
+				"previous_version": previous,
+				"version": version,
+				"action": action,
+			})
+		};
+		let mut updated = entry("B", Some("1.0.0"), Some("1.1.0"), "updated");
+		updated["released"] = "2024-05-01T00:00:00Z".into();
+		let mut failed = entry("G", Some("1.0.0"), None, "failed");
+		failed["message"] = "Failed to download".into();
+		let expected = serde_json::json!({
+			"ptr_version": env!("CARGO_PKG_VERSION"),
+			"started_at": 1_700_000_000,
+			"finished_at": 1_700_000_060,
+			"plugins": [
+				entry("A", None, Some("1.0.0"), "installed"),
+				updated,
+				entry("C", Some("2.0.0"), Some("2.0.0"), "up-to-date"),
+				entry("D", Some("1.0.0"), Some("1.0.0"), "skipped-pinned"),
+				entry("E", Some("1.0.0"), Some("1.0.0"), "skipped-excluded"),
+				entry("F", Some("1.0.0"), None, "removed"),
+				failed,
+			],
+		});
+		assert_eq!(serde_json::to_value(&report).unwrap(), expected);
+	}
+
+	#[test]
+	fn write_only_with_path() {
+		let temp = tempfile::tempdir().unwrap();
+		let mut report = Report::default();
+		report.failed("A", None, "Not found".to_string());
+		report.write().unwrap();
+		assert_eq!(fs::read_dir(temp.path()).unwrap().count(), 0);
+
+		let path = temp.path().join("reports").join("update.json");
+		report.write_to(path.clone());
+		report.write().unwrap();
+		let written: serde_json::Value =
+			serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+		assert!(written["finished_at"].as_u64() >= written["started_at"].as_u64());
+		assert_eq!(written["plugins"][0]["action"], "failed");
+	}
+}
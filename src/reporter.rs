@@ -0,0 +1,128 @@
+//! User-facing events of a run, rendered by one `Reporter` so lines from several threads never
+//! interleave and the output format doesn't depend on the call sites.
+//!
+//! The macros in `util` report through the reporter entered on the current thread with
+//! `enter`, events are dropped if none is. The library never prints, the `ptr` binary enters a
+//! reporter rendering events in its terminal for the whole run.
+
+use colored::Color;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+	Added {
+		name: String,
+		version: String,
+	},
+	UpToDate {
+		name: String,
+		version: String,
+	},
+	Removed {
+		name: String,
+	},
+	/// The item about to be processed, with its position in all items.
+	Progress {
+		index: usize,
+		total: usize,
+		name: String,
+	},
+	/// Any other line starting with a symbol, e.g. `~ Foo skipped`.
+	Message {
+		symbol: String,
+		#[serde(skip)]
+		color: Option<Color>,
+		text: String,
+	},
+	Warning {
+		message: String,
+	},
+	Error {
+		message: String,
+	},
+	/// Any other line of output, already formatted, e.g. a table.
+	Info {
+		text: String,
+	},
+	/// A note about the run kept apart from the output, e.g. that plugins haven't been
+	/// checked for a while.
+	Hint {
+		text: String,
+	},
+}
+
+/// Receives every event of the run.
+pub trait Reporter: Send + Sync {
+	fn report(&self, event: Event);
+
+	/// Ask the user `question` and return the answer, None if nobody can answer.
+	fn prompt(&self, _question: &str) -> Option<String> {
+		None
+	}
+
+	/// Whether a user reads the events as they're reported and can answer prompts.
+	fn is_interactive(&self) -> bool {
+		false
+	}
+}
+
+/// Keeps the events instead of printing them, e.g. to check what a command reported.
+#[derive(Default)]
+pub struct Capture(Mutex<Vec<Event>>);
+
+impl Capture {
+	/// The events reported so far, leaving none.
+	pub fn take(&self) -> Vec<Event> {
+		std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+	}
+}
+
+impl Reporter for Capture {
+	fn report(&self, event: Event) {
+		self.0.lock().unwrap_or_else(|e| e.into_inner()).push(event);
+	}
+}
+
+thread_local! {
+	static CURRENT: RefCell<Option<Arc<dyn Reporter>>> = const { RefCell::new(None) };
+}
+
+/// Report the events of the current thread to `reporter` until the guard is dropped, e.g. one
+/// reporter for each configuration a GUI runs on its own thread.
+#[must_use = "the reporter is left as soon as the guard is dropped"]
+pub fn enter(reporter: Arc<dyn Reporter>) -> Entered {
+	Entered(CURRENT.with(|c| c.replace(Some(reporter))))
+}
+
+/// Restores the reporter entered before on the thread when dropped, see `enter`.
+pub struct Entered(Option<Arc<dyn Reporter>>);
+
+impl Drop for Entered {
+	fn drop(&mut self) {
+		CURRENT.with(|c| *c.borrow_mut() = self.0.take());
+	}
+}
+
+/// The reporter of the current thread, cloned so it can prompt without holding the cell.
+fn current() -> Option<Arc<dyn Reporter>> {
+	CURRENT.with(|c| c.borrow().clone())
+}
+
+pub fn report(event: Event) {
+	if let Some(reporter) = current() {
+		reporter.report(event);
+	}
+}
+
+/// Ask the user through the reporter, None if there is none or it can't ask.
+pub fn prompt(question: &str) -> Option<String> {
+	current().and_then(|r| r.prompt(question))
+}
+
+/// Whether the reporter has a user who can answer prompts.
+pub fn is_interactive() -> bool {
+	current().is_some_and(|r| r.is_interactive())
+}
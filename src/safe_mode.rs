@@ -0,0 +1,101 @@
+//! Plugin directories moved aside by `ptr safe-mode on`, so `off` restores exactly those, also
+//! after a reboot.
+//!
+//! The record exists only while safe mode is on.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::polling;
+
+/// plugin.json of a disabled directory is renamed to this, PowerToys Run loads every directory
+/// with a plugin.json whatever its name.
+const DISABLED_MARKER: &str = "plugin.json.disabled";
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct SafeMode {
+	/// Directories of the disabled plugins before they were moved, by plugin name.
+	#[serde(default)]
+	pub dirs: BTreeMap<String, PathBuf>,
+}
+
+impl SafeMode {
+	fn path(dir: &Path) -> PathBuf {
+		dir.join("safe-mode.toml")
+	}
+
+	/// Whether safe mode is on, plugins shouldn't be changed then.
+	pub fn is_on(dir: &Path) -> bool {
+		Self::path(dir).exists()
+	}
+
+	/// Load the record, None if safe mode is off.
+	pub fn load(dir: &Path) -> Result<Option<Self>> {
+		match fs::read_to_string(Self::path(dir)) {
+			Ok(content) => Ok(Some(toml::from_str(&content)?)),
+			Err(_) => Ok(None),
+		}
+	}
+
+	/// Write the record, or remove it once no directory is disabled.
+	pub fn save(&self, dir: &Path) -> Result<()> {
+		if self.dirs.is_empty() {
+			if Self::is_on(dir) {
+				fs::remove_file(Self::path(dir))?;
+			}
+			return Ok(());
+		}
+		fs::create_dir_all(dir)?;
+		fs::write(Self::path(dir), toml::to_string(self)?)?;
+		Ok(())
+	}
+}
+
+/// Where `dir` is moved while it's disabled, `<dir>.disabled`.
+pub fn disabled_path(dir: &Path) -> PathBuf {
+	let mut path = OsString::from(dir.as_os_str());
+	path.push(".disabled");
+	PathBuf::from(path)
+}
+
+/// Whether the directory was moved aside by `disable`.
+pub fn is_disabled(path: &Path) -> bool {
+	path.file_name()
+		.is_some_and(|n| n.to_string_lossy().ends_with(".disabled"))
+		&& path.join(DISABLED_MARKER).is_file()
+}
+
+/// Move `dir` to `disabled_path`, renaming its plugin.json so PowerToys Run skips it.
+pub fn disable(dir: &Path) -> Result<()> {
+	polling::rename(dir.join("plugin.json"), dir.join(DISABLED_MARKER))?;
+	if let Err(e) = polling::rename(dir, disabled_path(dir)) {
+		_ = polling::rename(dir.join(DISABLED_MARKER), dir.join("plugin.json"));
+		return Err(e.into());
+	}
+	Ok(())
+}
+
+/// Move a directory disabled by `disable` back to `dir`, nothing to do if it's already there.
+pub fn enable(dir: &Path) -> Result<()> {
+	let disabled = disabled_path(dir);
+	if !disabled.exists() {
+		if dir.exists() {
+			return Ok(());
+		}
+		bail!("{} is missing", disabled.display());
+	}
+	if dir.exists() {
+		bail!(
+			"{} exists, keeping it and {} as they are",
+			dir.display(),
+			disabled.display()
+		);
+	}
+	polling::rename(&disabled, dir)?;
+	polling::rename(dir.join(DISABLED_MARKER), dir.join("plugin.json"))?;
+	Ok(())
+}
@@ -0,0 +1,61 @@
+//! Result of the last `check --write-state`, read by `ptr state` for shell prompts without
+//! touching the network.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize)]
+pub struct State {
+	/// Unix time of the check.
+	pub checked_at: u64,
+	/// Seconds after which the state is no longer shown, 0 for never.
+	#[serde(default)]
+	pub stale_after: u64,
+	pub plugins: usize,
+	pub outdated: usize,
+	/// Plugins whose latest version couldn't be fetched.
+	#[serde(default)]
+	pub failed: usize,
+}
+
+impl State {
+	fn path(dir: &Path) -> PathBuf {
+		dir.join("state.json")
+	}
+
+	/// Whether `check --write-state` was ever run, later updates only refresh it then.
+	pub fn exists(dir: &Path) -> bool {
+		Self::path(dir).exists()
+	}
+
+	/// Load the state, None if it doesn't exist, can't be read or is stale.
+	pub fn load(dir: &Path) -> Option<Self> {
+		let state: Self = serde_json::from_str(&fs::read_to_string(Self::path(dir)).ok()?).ok()?;
+		let age = now().saturating_sub(state.checked_at);
+		(state.stale_after == 0 || age < state.stale_after).then_some(state)
+	}
+
+	pub fn save(&self, dir: &Path) -> Result<()> {
+		fs::create_dir_all(dir)?;
+		fs::write(Self::path(dir), serde_json::to_string(self)?)?;
+		Ok(())
+	}
+
+	/// Replace `{outdated}`, `{plugins}`, `{failed}` and `{checked_at}` in `format`.
+	pub fn format(&self, format: &str) -> String {
+		format
+			.replace("{outdated}", &self.outdated.to_string())
+			.replace("{plugins}", &self.plugins.to_string())
+			.replace("{failed}", &self.failed.to_string())
+			.replace("{checked_at}", &self.checked_at.to_string())
+	}
+}
+
+fn now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |d| d.as_secs())
+}
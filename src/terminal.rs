@@ -0,0 +1,131 @@
+//! Renders the events of the library in the terminal, see `ptr::reporter`.
+
+use colored::{Color, Colorize};
+use ptr::reporter::{Event, Reporter};
+use ptr::t;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// How `Terminal` renders events.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Format {
+	/// Colored text, unless colors are disabled, e.g. by `NO_COLOR`.
+	#[default]
+	Text,
+	/// Text without colors.
+	Plain,
+	/// One JSON object per line.
+	Json,
+}
+
+impl FromStr for Format {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_lowercase().as_str() {
+			"text" => Ok(Format::Text),
+			"plain" => Ok(Format::Plain),
+			"json" => Ok(Format::Json),
+			_ => Err(format!(
+				"unknown format '{s}', expected text, plain or json"
+			)),
+		}
+	}
+}
+
+impl fmt::Display for Format {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Format::Text => write!(f, "text"),
+			Format::Plain => write!(f, "plain"),
+			Format::Json => write!(f, "json"),
+		}
+	}
+}
+
+/// Prints events to stdout, warnings, errors and hints to stderr, and asks prompts on stdin.
+pub struct Terminal {
+	format: Format,
+	lock: Mutex<()>,
+}
+
+impl Terminal {
+	/// Printer for `format`, colors of all other output are turned off for plain text and
+	/// JSON.
+	pub fn new(format: Format) -> Self {
+		if format != Format::Text {
+			colored::control::set_override(false);
+		}
+		Self {
+			format,
+			lock: Mutex::new(()),
+		}
+	}
+
+	fn render(event: &Event) -> String {
+		let symbol = |s: &str, color: Color| s.color(color).bold();
+		match event {
+			Event::Added { name, version } => {
+				format!("{} {}@{}", symbol("+", Color::BrightGreen), name, version)
+			}
+			Event::UpToDate { name, version } => {
+				format!("{} {}@{}", symbol("=", Color::BrightBlue), name, version)
+			}
+			Event::Removed { name } => format!("{} {}", symbol("-", Color::BrightRed), name),
+			Event::Progress { index, total, name } => {
+				format!("{} {}", format!("[{index}/{total}]").dimmed(), name)
+			}
+			Event::Message {
+				symbol: s,
+				color,
+				text,
+			} => match color {
+				Some(color) => format!("{} {}", symbol(s, *color), text),
+				None => format!("{} {}", s.bold(), text),
+			},
+			Event::Warning { message } => {
+				format!("{} {}", t!(Warning).bright_yellow().bold(), message)
+			}
+			Event::Error { message } => {
+				format!("{} {}", t!(Error).bright_red().bold(), message)
+			}
+			Event::Info { text } => text.clone(),
+			Event::Hint { text } => text.dimmed().to_string(),
+		}
+	}
+}
+
+impl Default for Terminal {
+	fn default() -> Self {
+		Self::new(Format::Text)
+	}
+}
+
+impl Reporter for Terminal {
+	fn report(&self, event: Event) {
+		let line = match self.format {
+			Format::Json => serde_json::to_string(&event).unwrap_or_default(),
+			Format::Text | Format::Plain => Self::render(&event),
+		};
+		let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+		match event {
+			Event::Warning { .. } | Event::Error { .. } | Event::Hint { .. } => eprintln!("{line}"),
+			_ => println!("{line}"),
+		}
+	}
+
+	fn prompt(&self, question: &str) -> Option<String> {
+		let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+		print!("{question}");
+		io::stdout().flush().ok()?;
+		let mut input = String::new();
+		io::stdin().read_line(&mut input).ok()?;
+		Some(input)
+	}
+
+	fn is_interactive(&self) -> bool {
+		io::stdin().is_terminal() && io::stdout().is_terminal()
+	}
+}
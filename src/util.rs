@@ -1,332 +1,3742 @@
 use anyhow::{anyhow, bail, Ok, Result};
 use colored::Colorize;
-use reqwest::blocking::Client;
-use reqwest::header::{HeaderMap, ACCEPT, USER_AGENT};
+use regex::Regex;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{HeaderMap, ACCEPT, AUTHORIZATION, USER_AGENT};
 use serde::Deserialize;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::{self, Write};
-use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::{env, mem};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{self, AtomicUsize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, mem, thread};
 use zip::ZipArchive;
 
-use crate::config::Arch;
-use crate::polling;
-use crate::PLUGIN_PATH;
+use crate::config::{Arch, RestartScope};
+use crate::reporter;
+use crate::{asset_cache, polling};
+use crate::{info, t, warning, Context};
 
-#[derive(Deserialize)]
-struct ApiResponse {
-	tag_name: String,
-	assets: Box<[Assets]>,
+/// Error for Windows APIs called on other platforms.
+#[cfg(not(windows))]
+pub(crate) const UNSUPPORTED: &str = "Unsupported platform, plugins can only be managed on Windows";
+
+#[derive(Deserialize, Clone)]
+pub struct Release {
+	pub tag_name: String,
+	/// Page of the release, under the current name of the repository.
+	#[serde(default)]
+	html_url: Option<String>,
+	pub published_at: Option<String>,
+	pub body: Option<String>,
+	#[serde(default)]
+	draft: bool,
+	#[serde(default)]
+	prerelease: bool,
+	#[serde(default)]
+	assets: Vec<Assets>,
 }
 
-#[derive(Deserialize)]
-struct Assets {
+#[derive(Deserialize, Clone)]
+pub struct Assets {
 	name: String,
 	browser_download_url: String,
+	#[serde(default)]
+	size: u64,
 }
 
 impl Assets {
-	/// Currently match for upper and lower case arch names.
-	///
+	/// Match for any name of the arch in any case, e.g. `x64`, `X64` or `x86_64`.
 	fn is_arch(&self, arch: &Arch) -> bool {
-		let arch = &arch.to_string();
-		(self.name.contains(arch) || self.name.contains(&arch.to_uppercase()))
-			&& self.name.ends_with(".zip")
+		let name = self.name.to_lowercase();
+		arch.aliases().iter().any(|a| name.contains(a)) && self.is_archive()
 	}
-}
 
-#[macro_export]
-macro_rules! gh_dl {
-	($root_name:expr, $repo:expr, $version:expr, $arch:expr) => {
-		$crate::util::gh_dl($root_name, $repo, $version, $arch, None)
-	};
-	($root_name:expr, $repo:expr, $version:expr, $arch:expr, $current_version:expr) => {
-		$crate::util::gh_dl($root_name, $repo, $version, $arch, Some($current_version))
-	};
+	/// Whether ptr can extract the asset.
+	fn is_archive(&self) -> bool {
+		let name = self.name.to_lowercase();
+		ARCHIVE_EXTENSIONS.iter().any(|e| name.ends_with(e))
+	}
 }
 
-/// Download a file from a GitHub repository.
-///
-/// # Arguments
-///
-/// * `repo` - The repository to download from.
-/// * `version` - The tagged version of the repository to download.
-/// * `arch` - The architecture of the system, either x64 or arm64.
-/// * `current_version` - The current version of the repository that is installed.
-///
-/// # Returns
-/// The version of the repository that was downloaded.
-pub fn gh_dl(
-	root_name: &str,
-	repo: &str,
-	version: Option<&str>,
-	arch: &Arch,
-	current_version: Option<&str>,
-) -> Result<String> {
-	let url = if let Some(version) = version {
-		format!("https://api.github.com/repos/{repo}/releases/tags/{version}")
-	} else {
-		format!("https://api.github.com/repos/{repo}/releases/latest")
-	};
-	let mut headers = HeaderMap::new();
-	headers.insert(USER_AGENT, "reqwest".parse().unwrap());
-	headers.insert(ACCEPT, "application/vnd.github+json".parse().unwrap());
-	headers.insert("X-GitHub-Api-Version", "2022-11-28".parse().unwrap());
-	let res = Client::new().get(&url).headers(headers).send()?;
-	if !res.status().is_success() {
-		bail!(
-			"Failed to fetch {}: {}",
-			version.unwrap_or("latest"),
-			res.status().canonical_reason().unwrap_or("Unknown"),
-		);
+/// Extensions of the assets ptr can extract.
+const ARCHIVE_EXTENSIONS: [&str; 1] = [".zip"];
+
+/// Extensions of the files installed from a release without an archive.
+const LOOSE_EXTENSIONS: [&str; 2] = [".dll", ".json"];
+
+/// Indices of the plugin files of a release that publishes plugin.json and its DLLs as
+/// separate assets instead of an archive, empty if it doesn't.
+fn loose_files(assets: &[Assets]) -> Vec<usize> {
+	let lower = |a: &Assets| a.name.to_lowercase();
+	if !assets.iter().any(|a| lower(a) == "plugin.json")
+		|| !assets.iter().any(|a| lower(a).ends_with(".dll"))
+	{
+		return vec![];
 	}
-	let res: ApiResponse = res.json()?;
-	let tag = res.tag_name;
-	if let Some(current_version) = current_version {
-		if tag == current_version {
-			return Ok(current_version.to_string());
-		}
+	(0..assets.len())
+		.filter(|&i| {
+			LOOSE_EXTENSIONS
+				.iter()
+				.any(|e| lower(&assets[i]).ends_with(e))
+		})
+		.collect()
+}
+
+/// Where releases and their assets are fetched from.
+pub trait ReleaseSource {
+	/// Get the latest release that is not a draft or prerelease.
+	fn latest_release(&self, repo: &str) -> Result<Release>;
+
+	/// Get the release of a tag.
+	fn release_by_tag(&self, repo: &str, tag: &str) -> Result<Release>;
+
+	/// Download an asset into `writer`.
+	fn download(&self, url: &str, writer: &mut dyn Write) -> Result<()>;
+
+	/// Whether the repository is archived or gone, None if it's fine or unknown.
+	fn repo_state(&self, _repo: &str) -> Option<RepoState> {
+		None
 	}
+}
 
-	let assets = res.assets;
-	let asset = match assets.iter().find(|a| a.is_arch(arch)) {
-		Some(asset) => asset,
-		None => manual_select(&assets)?,
-	};
-	let (url, name) = (&asset.browser_download_url, &asset.name);
-	let res = Client::new().get(url).send()?;
+/// Why a repository doesn't get new releases.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RepoState {
+	Archived,
+	/// Deleted, or private without a token that can read it.
+	Unavailable,
+}
 
-	let file_path = PLUGIN_PATH.join(name);
-	let mut file = File::create(&file_path)?;
-	file.write_all(&res.bytes()?)?;
+#[derive(Deserialize)]
+struct RepoResponse {
+	#[serde(default)]
+	archived: bool,
+}
 
-	extract_zip(&file_path, root_name)?;
-	fs::remove_file(&file_path)?;
+/// How long `GitHub::probe` waits for each host.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
 
-	Ok(tag)
+/// The GitHub REST API.
+pub struct GitHub {
+	base_url: String,
+	client: Client,
+	token: Option<String>,
+	/// Tokens of GitHub Enterprise Server hosts, the github.com token is never sent to them.
+	hosts: HashMap<String, String>,
+	/// Send `token` to any host, for the token of one plugin.
+	any_host: bool,
+	/// Releases fetched in this run, so checking a release before downloading it costs one
+	/// request.
+	releases: RefCell<HashMap<String, Release>>,
+	/// Fail every request, see `Context::offline`.
+	offline: bool,
 }
 
-fn manual_select(assets: &[Assets]) -> Result<&Assets> {
-	if assets.len() == 1 {
-		return Ok(&assets[0]);
+impl GitHub {
+	pub fn new(token: Option<&str>) -> Self {
+		Self {
+			base_url: "https://api.github.com".to_string(),
+			client: Client::new(),
+			token: token.map(str::to_string),
+			hosts: HashMap::new(),
+			any_host: false,
+			releases: RefCell::default(),
+			offline: false,
+		}
 	}
 
-	for (i, asset) in assets.iter().enumerate() {
-		println!("{}: {}", i.to_string().bright_yellow(), asset.name);
+	/// Client for a plugin with its own token, sent whichever host the plugin is on.
+	pub fn for_plugin(token: &str) -> Self {
+		Self {
+			any_host: true,
+			..Self::new(Some(token))
+		}
 	}
-	let index: usize = prompt("Fail to match assets, please select one: ")?.parse()?;
-	assets.get(index).ok_or(anyhow!("Invalid index"))
-}
 
-fn extract_zip(zip_path: &Path, root_name: &str) -> Result<()> {
-	let mut archive = ZipArchive::new(File::open(zip_path)?)?;
-	env::set_current_dir(&*PLUGIN_PATH)?;
+	/// Use `hosts` as the tokens of GitHub Enterprise Server hosts.
+	pub fn with_hosts(self, hosts: HashMap<String, String>) -> Self {
+		Self { hosts, ..self }
+	}
 
-	// locate for .dll file and find it's parent
-	let dll = archive
-		.file_names()
-		.find(|f| f.ends_with(".dll"))
-		.ok_or(anyhow!("No .dll file found"))?
-		.to_owned();
-	let parent = Path::new(&dll).parent().unwrap_or(Path::new(""));
+	/// Fail every request if `offline`.
+	pub fn offline(self, offline: bool) -> Self {
+		Self { offline, ..self }
+	}
 
-	// extract all files and keep the directory structure
-	let root = PathBuf::from(root_name);
-	for i in 0..archive.len() {
-		let mut file = archive.by_index(i)?;
-		let out_path = root.join(Path::new(file.name()).strip_prefix(parent)?);
+	fn check_online(&self) -> Result<()> {
+		if self.offline {
+			bail!("Network is disabled by --offline, nothing was changed");
+		}
+		Ok(())
+	}
 
-		if file.is_dir() {
-			fs::create_dir_all(out_path)?;
-		} else {
-			if let Some(p) = out_path.parent() {
-				fs::create_dir_all(p)?;
+	/// API base URL, `owner/repo` and token of `repo`, which starts with the host for GitHub
+	/// Enterprise Server, e.g. `ghe.corp.com/team/plugin`.
+	fn endpoint<'a>(&'a self, repo: &'a str) -> (String, &'a str, Option<&'a str>) {
+		match split_host(repo) {
+			(Some(host), slug) => {
+				let token = self
+					.hosts
+					.get(&host.to_lowercase())
+					.map(String::as_str)
+					.or(self.token.as_deref().filter(|_| self.any_host));
+				(format!("https://{host}/api/v3"), slug, token)
 			}
-			let mut out_file = File::create(out_path)?;
-			polling::copy(&mut file, &mut out_file)?;
+			(None, slug) => (self.base_url.clone(), slug, self.token.as_deref()),
 		}
 	}
 
-	Ok(())
-}
+	/// Check that the API of each host of `repos` is reachable before anything is changed.
+	pub fn probe<'a>(&self, repos: impl IntoIterator<Item = &'a str>) -> Result<()> {
+		self.check_online()?;
+		let mut urls: Vec<String> = repos.into_iter().map(|r| self.endpoint(r).0).collect();
+		urls.sort();
+		urls.dedup();
+		for url in urls {
+			self.client
+				.head(&url)
+				.timeout(PROBE_TIMEOUT)
+				.send()
+				.map_err(|_| anyhow!("No network connectivity to {}, nothing was changed", url))?;
+		}
+		Ok(())
+	}
 
-fn run_process(program: &str, args: &str, admin: bool) -> Result<()> {
-	use windows::core::{w, HSTRING, PCWSTR};
-	use windows::Win32::Foundation::CloseHandle;
-	use windows::Win32::System::Threading::{GetExitCodeProcess, WaitForSingleObject, INFINITE};
-	use windows::Win32::UI::Shell::{ShellExecuteExW, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW};
+	/// Requests left for the token, `/rate_limit` itself doesn't count against them.
+	pub fn rate_limit(&self) -> Result<RateLimit> {
+		let res: RateLimitResponse = self
+			.get("/rate_limit", &[], "Failed to get the rate limit")?
+			.json()?;
+		Ok(res.resources.core)
+	}
 
-	let mut sei: SHELLEXECUTEINFOW = unsafe { mem::zeroed() };
-	sei.cbSize = mem::size_of::<SHELLEXECUTEINFOW>() as u32;
-	sei.fMask = SEE_MASK_NOCLOSEPROCESS;
-	if admin {
-		sei.lpVerb = w!("runas");
+	/// Send a GET request to `path` of the API with the shared headers.
+	/// A failed response becomes an error starting with `context`.
+	fn get(&self, path: &str, query: &[(&str, &str)], context: &str) -> Result<Response> {
+		self.send(&self.base_url, self.token.as_deref(), path, query, context)
 	}
-	let h_file = HSTRING::from(program);
-	sei.lpFile = PCWSTR(h_file.as_ptr());
-	sei.nShow = 0; // SW_HIDE
-	let h_args = HSTRING::from(args);
-	sei.lpParameters = PCWSTR(h_args.as_ptr());
 
-	unsafe {
-		ShellExecuteExW(&mut sei)?;
-		let process = sei.hProcess;
-		_ = WaitForSingleObject(process, INFINITE);
-		let mut exit_code = 0;
-		GetExitCodeProcess(process, &mut exit_code)?;
-		CloseHandle(process)?;
+	/// Send a GET request to `path` under `/repos/<owner>/<repo>` on the host of `repo`.
+	fn get_repo(
+		&self,
+		repo: &str,
+		path: &str,
+		query: &[(&str, &str)],
+		context: &str,
+	) -> Result<Response> {
+		let (base_url, slug, token) = self.endpoint(repo);
+		self.send(
+			&base_url,
+			token,
+			&format!("/repos/{slug}{path}"),
+			query,
+			context,
+		)
+	}
 
-		if exit_code == 0 {
-			Ok(())
-		} else {
-			Err(io::Error::from_raw_os_error(exit_code as i32).into())
+	fn send(
+		&self,
+		base_url: &str,
+		token: Option<&str>,
+		path: &str,
+		query: &[(&str, &str)],
+		context: &str,
+	) -> Result<Response> {
+		self.check_online()?;
+		let res = self
+			.client
+			.get(format!("{base_url}{path}"))
+			.query(query)
+			.headers(headers(token))
+			.send()?;
+		check_response(res, context)
+	}
+
+	fn get_release(&self, repo: &str, path: &str, name: &str) -> Result<Release> {
+		let key = format!("{repo}/releases/{path}");
+		if let Some(release) = self.releases.borrow().get(&key) {
+			return Ok(release.clone());
 		}
+		let context = format!("Failed to fetch {name} for {repo}");
+		let release: Release = self
+			.get_repo(repo, &format!("/releases/{path}"), &[], &context)?
+			.json()?;
+		self.releases.borrow_mut().insert(key, release.clone());
+		Ok(release)
 	}
 }
 
-pub fn kill_ptr(admin: bool) -> Result<()> {
-	run_process("taskkill.exe", "/F /FI \"IMAGENAME eq PowerToys*\"", admin)?;
-	Ok(())
+#[derive(Deserialize)]
+struct ErrorResponse {
+	message: String,
 }
 
-pub fn start_ptr(powertoys_path: &Path) -> Result<()> {
-	let c = Command::new(powertoys_path).spawn()?;
-	mem::forget(c);
-	Ok(())
+/// Turn a failed response into an error with the status, the `message` in the JSON body if any,
+/// and the requested URL.
+fn check_response(res: Response, context: &str) -> Result<Response> {
+	let status = res.status();
+	if status.is_success() {
+		return Ok(res);
+	}
+	let url = res.url().to_string();
+	let reason = status.canonical_reason().unwrap_or("Unknown");
+	match res.json::<ErrorResponse>().ok() {
+		Some(body) => bail!("{}: {}, {} ({})", context, reason, body.message, url),
+		None => bail!("{}: {} ({})", context, reason, url),
+	}
 }
 
-pub fn get_powertoys_path() -> Result<PathBuf> {
-	let possible_paths = [
-		PathBuf::from(env::var("ProgramFiles").unwrap_or_default()),
-		PathBuf::from(env::var("LOCALAPPDATA").unwrap_or_default()),
-	]
-	.map(|p| p.join(r"PowerToys\PowerToys.exe"));
-	for path in possible_paths {
-		if path.exists() {
-			return Ok(path);
+impl ReleaseSource for GitHub {
+	fn latest_release(&self, repo: &str) -> Result<Release> {
+		self.get_release(repo, "latest", "latest")
+	}
+
+	fn release_by_tag(&self, repo: &str, tag: &str) -> Result<Release> {
+		self.get_release(repo, &format!("tags/{tag}"), tag)
+	}
+
+	fn repo_state(&self, repo: &str) -> Option<RepoState> {
+		self.check_online().ok()?;
+		let (base_url, slug, token) = self.endpoint(repo);
+		let res = self
+			.client
+			.get(format!("{base_url}/repos/{slug}"))
+			.headers(headers(token))
+			.send()
+			.ok()?;
+		match res.status().as_u16() {
+			404 | 410 => Some(RepoState::Unavailable),
+			_ if res.status().is_success() => res
+				.json::<RepoResponse>()
+				.ok()?
+				.archived
+				.then_some(RepoState::Archived),
+			_ => None,
+		}
+	}
+
+	fn download(&self, url: &str, writer: &mut dyn Write) -> Result<()> {
+		self.check_online()?;
+		let mut res = check_response(self.client.get(url).send()?, "Failed to download asset")?;
+		let expected = res.content_length();
+		let written = res.copy_to(writer)?;
+		if expected.is_some_and(|e| e != written) {
+			bail!("Download of {} is truncated", url);
 		}
+		Ok(())
 	}
-	prompt("PowerToys executable not found in any of the expected locations\nEnter path: ")
-		.map(|s| s.into())
 }
 
-/// Prompt the user for string input.
-fn prompt(msg: &str) -> Result<String> {
-	let mut input = String::new();
-	print!("{msg}");
-	io::stdout().flush()?;
-	io::stdin().read_line(&mut input)?;
-	Ok(input.trim().to_string())
+/// Download a release with `download_release` into the directory `root`, authenticated with
+/// `default_token`.
+#[macro_export]
+macro_rules! gh_dl {
+	($root:expr, $repo:expr, $version:expr, $arch:expr) => {
+		$crate::util::download_release(
+			&$crate::util::GitHub::new($crate::util::default_token().as_deref()),
+			$root,
+			$repo,
+			$version,
+			$arch,
+			None,
+			&$crate::util::AssetFilter::default(),
+			None,
+			&$crate::util::ExtractLimits::default(),
+		)
+	};
+	($root:expr, $repo:expr, $version:expr, $arch:expr, $current_version:expr) => {
+		$crate::util::download_release(
+			&$crate::util::GitHub::new($crate::util::default_token().as_deref()),
+			$root,
+			$repo,
+			$version,
+			$arch,
+			Some($current_version),
+			&$crate::util::AssetFilter::default(),
+			None,
+			&$crate::util::ExtractLimits::default(),
+		)
+	};
 }
 
-pub fn self_update() -> Result<()> {
-	use crate::{add, up_to_date};
+/// GitHub token from Windows Credential Manager or `GITHUB_TOKEN`, for requests outside of a
+/// config.
+pub fn default_token() -> Option<String> {
+	crate::credential::read_token()
+		.or_else(|| env::var("GITHUB_TOKEN").ok().filter(|t| !t.is_empty()))
+}
 
-	// download asset
-	let current_version = env!("CARGO_PKG_VERSION");
-	let url = "https://api.github.com/repos/8LWXpg/ptr/releases/latest";
-	let mut headers = HeaderMap::new();
-	headers.insert(USER_AGENT, "reqwest".parse().unwrap());
-	headers.insert(ACCEPT, "application/vnd.github+json".parse().unwrap());
-	headers.insert("X-GitHub-Api-Version", "2022-11-28".parse().unwrap());
-	let res = Client::new().get(url).headers(headers).send()?;
-	if !res.status().is_success() {
-		bail!(
-			"Failed to fetch latest: {}",
-			res.status().canonical_reason().unwrap_or("Unknown"),
-		);
+/// Bounds of what an archive may extract to, so a zip bomb fails before the disk fills.
+pub struct ExtractLimits {
+	/// Total uncompressed size in bytes, also the limit of every entry and nested archive.
+	pub max_size: u64,
+	pub max_files: usize,
+}
+
+impl ExtractLimits {
+	/// Default `max_size` in MB.
+	pub const DEFAULT_MAX_SIZE_MB: u64 = 512;
+
+	/// Limits with `max_size` in MB.
+	pub fn with_max_size_mb(mb: u64) -> Self {
+		Self {
+			max_size: mb.saturating_mul(1024 * 1024),
+			..Self::default()
+		}
 	}
-	let res: ApiResponse = res.json()?;
-	let tag = res.tag_name;
-	if tag == format!("v{current_version}") {
-		up_to_date!("ptr", current_version);
-		return Ok(());
+
+	fn exceeded(&self, entry: &str) -> anyhow::Error {
+		anyhow!(
+			"Archive exceeds safety limits, extracting {} goes over {}, use --max-extract-size \
+			 <MB> if the plugin really is this large",
+			entry,
+			format_size(self.max_size)
+		)
 	}
+}
 
-	let assets = res.assets;
-	let asset = assets
-		.iter()
-		.find(|a| a.name.contains(std::env::consts::ARCH))
-		.unwrap();
-	let (url, name) = (&asset.browser_download_url, &asset.name);
-	let res = Client::new().get(url).send()?;
+impl Default for ExtractLimits {
+	fn default() -> Self {
+		Self {
+			max_size: Self::DEFAULT_MAX_SIZE_MB * 1024 * 1024,
+			max_files: 10_000,
+		}
+	}
+}
 
-	let file_path = env::temp_dir().join(name);
-	File::create(&file_path)?.write_all(&res.bytes()?)?;
+/// How to choose the asset of a release.
+#[derive(Default)]
+pub struct AssetFilter<'a> {
+	/// Plugin name substituted for `{name}` in `pattern`.
+	pub name: Option<&'a str>,
+	/// Regex matched against asset names, the arch is matched if it's not set.
+	/// See `expand_pattern` for the variables it can contain.
+	pub pattern: Option<&'a str>,
+	/// Tag and name of the asset installed before, preferred if the same tag is downloaded.
+	pub installed: Option<(&'a str, &'a str)>,
+	/// Pick the best matching asset and plugin of the archive instead of asking, failing if
+	/// none clearly matches, see `Context::assume_yes`.
+	pub assume_yes: bool,
+}
 
-	// extract and self replace
-	let mut archive = ZipArchive::new(File::open(&file_path)?)?;
-	let out_path = env::temp_dir().join("ptr.exe");
-	let mut out_file = File::create(&out_path)?;
-	io::copy(&mut archive.by_name("ptr.exe")?, &mut out_file)?;
-	self_replace::self_replace(&out_path)?;
-	fs::remove_file(&file_path)?;
-	fs::remove_file(&out_path)?;
-	add!("ptr", tag);
+/// Replace the variables in an asset pattern with regexes matching them:
+/// `{arch}` any name of `arch`, `{version}` the tag with or without a leading `v`, and `{name}`
+/// the plugin name, or the repository name if it's not known.
+///
+/// Other braces are kept, so quantifiers like `{2}` still work, and `\{arch}` is a literal
+/// `{arch}`.
+pub fn expand_pattern(pattern: &str, arch: &Arch, tag: &str, name: Option<&str>) -> String {
+	let arch = format!(
+		"(?:{})",
+		arch.aliases()
+			.iter()
+			.map(|a| regex::escape(a))
+			.collect::<Vec<_>>()
+			.join("|")
+	);
+	let version = format!("v?{}", regex::escape(tag.trim_start_matches(['v', 'V'])));
+	let name = name.map(regex::escape);
+	let variables = [
+		("{arch}", Some(&arch)),
+		("{version}", Some(&version)),
+		("{name}", name.as_ref()),
+	];
+
+	let mut expanded = String::new();
+	let mut rest = pattern;
+	while let Some(i) = rest.find('{') {
+		expanded.push_str(&rest[..i]);
+		rest = &rest[i..];
+		let variable = variables
+			.iter()
+			.find(|(key, value)| value.is_some() && rest.starts_with(key));
+		match variable {
+			Some((key, Some(value))) if !expanded.ends_with('\\') => {
+				expanded.push_str(value);
+				rest = &rest[key.len()..];
+			}
+			_ => {
+				expanded.push('{');
+				rest = &rest[1..];
+			}
+		}
+	}
+	expanded.push_str(rest);
+	expanded
+}
+
+/// Compile an asset pattern.
+pub fn compile_pattern(pattern: &str) -> Result<Regex> {
+	Regex::new(pattern).map_err(|e| anyhow!("Invalid pattern {}: {}", pattern, e))
+}
+
+/// The first asset matching `pattern`, or the arch if it's not set.
+fn match_asset<'a>(
+	assets: &'a [Assets],
+	arch: &Arch,
+	pattern: Option<&Regex>,
+) -> Option<&'a Assets> {
+	match pattern {
+		Some(pattern) => assets.iter().find(|a| pattern.is_match(&a.name)),
+		None => assets.iter().find(|a| a.is_arch(arch)),
+	}
+}
+
+/// Print the assets of a release with their size, marking the one chosen for `arch` and the
+/// ones matching `pattern`.
+pub fn print_assets(
+	source: &dyn ReleaseSource,
+	repo: &str,
+	version: Option<&str>,
+	arch: &Arch,
+	pattern: Option<&str>,
+) -> Result<()> {
+	let release = match version {
+		Some(version) => source.release_by_tag(repo, version)?,
+		None => source.latest_release(repo)?,
+	};
+	let name = repo.rsplit('/').next();
+	let pattern = pattern
+		.map(|p| compile_pattern(&expand_pattern(p, arch, &release.tag_name, name)))
+		.transpose()?;
+	info!("{} {}", repo.bright_cyan(), release.tag_name);
+	if release.assets.is_empty() {
+		bail!("No assets in {}", release.tag_name);
+	}
+	let chosen = match_asset(&release.assets, arch, pattern.as_ref()).map(|a| &a.name);
+	let loose = if chosen.is_none() {
+		loose_files(&release.assets)
+	} else {
+		vec![]
+	};
+	let width = release
+		.assets
+		.iter()
+		.map(|a| a.name.len())
+		.max()
+		.unwrap_or(0);
+	for (i, asset) in release.assets.iter().enumerate() {
+		let mut marks = vec![];
+		if pattern.as_ref().is_some_and(|p| p.is_match(&asset.name)) {
+			marks.push("matches pattern".bright_yellow());
+		}
+		if chosen == Some(&asset.name) {
+			marks.push("installed by ptr".bright_green());
+		}
+		if loose.contains(&i) {
+			marks.push("installed by ptr as a loose file".bright_green());
+		}
+		let marks: Vec<String> = marks.iter().map(ToString::to_string).collect();
+		info!(
+			"  {:width$}  {:>10}  {}",
+			asset.name,
+			format_size(asset.size),
+			marks.join(", ")
+		);
+	}
+	if chosen.is_none() && loose.is_empty() {
+		warning!("No asset matches, ptr would ask which one to install");
+	}
 	Ok(())
 }
 
-// region: macro
-#[macro_export]
-macro_rules! print_message {
-    ($symbol:expr, $color:ident, $msg:expr) => {
-        println!("{} {}", $symbol.$color().bold(), $msg)
-    };
-    ($symbol:expr, $color:ident, $fmt:expr, $($arg:tt)*) => {
-        println!("{} {}", $symbol.$color().bold(), format!($fmt, $($arg)*))
-    };
+/// A release and the asset chosen from it.
+pub struct Selection {
+	pub tag: String,
+	/// `owner/repo` the release is published under, differs from the requested one if the
+	/// repository was renamed or transferred.
+	pub repo: Option<String>,
+	/// Whether `current_version` is already the release, no asset is chosen then.
+	pub up_to_date: bool,
+	/// When the release was published, `published_at` of the GitHub API.
+	pub released: Option<String>,
+	assets: Vec<Assets>,
+	/// Index in `assets`, None if no asset matches and the user has to choose.
+	chosen: Option<usize>,
+	/// Indices of the plugin files in `assets`, installed as they are if no archive matches.
+	loose: Vec<usize>,
+}
+
+impl Selection {
+	/// Name and size of the chosen asset, or of all loose files together.
+	pub fn asset(&self) -> Option<(String, u64)> {
+		if let Some(i) = self.chosen {
+			return Some((self.assets[i].name.clone(), self.assets[i].size));
+		}
+		if self.loose.is_empty() {
+			return None;
+		}
+		let size = self.loose.iter().map(|&i| self.assets[i].size).sum();
+		Some((format!("{} (loose files)", self.loose_names()), size))
+	}
+
+	/// Names of the loose files, as recorded for the installed plugin.
+	fn loose_names(&self) -> String {
+		self.loose
+			.iter()
+			.map(|&i| self.assets[i].name.as_str())
+			.collect::<Vec<_>>()
+			.join(", ")
+	}
 }
 
-/// Print message for adding an item.
+/// Find the release and choose the asset to install, without downloading anything.
 ///
-/// Works like `println!`.
-#[macro_export]
-macro_rules! add {
-	($name:expr, $version:expr) => {
-		$crate::print_message!("+", bright_green, "{}@{}", $name, $version)
+/// See `download_release` for the arguments.
+pub fn select_release(
+	source: &dyn ReleaseSource,
+	repo: &str,
+	version: Option<&str>,
+	arch: &Arch,
+	current_version: Option<&str>,
+	filter: &AssetFilter,
+) -> Result<Selection> {
+	let res = match version {
+		Some(version) => source.release_by_tag(repo, version)?,
+		None => source.latest_release(repo)?,
+	};
+	let tag = res.tag_name;
+	let released = res.published_at;
+	let name = filter.name.or(repo.rsplit('/').next());
+	let pattern = filter
+		.pattern
+		.map(|p| compile_pattern(&expand_pattern(p, arch, &tag, name)))
+		.transpose()?;
+	let repo = res.html_url.as_deref().and_then(parse_github_repo);
+	if current_version.is_some_and(|v| same_version(v, &tag)) {
+		return Ok(Selection {
+			tag,
+			repo,
+			up_to_date: true,
+			released,
+			assets: vec![],
+			chosen: None,
+			loose: vec![],
+		});
+	}
+
+	let assets = res.assets;
+	let installed = filter
+		.installed
+		.filter(|(installed_tag, _)| same_version(installed_tag, &tag))
+		.and_then(|(_, name)| {
+			let other_arch = Arch::ALL
+				.iter()
+				.filter(|a| *a != arch)
+				.flat_map(|a| a.aliases())
+				.any(|a| name.to_lowercase().contains(a));
+			if other_arch {
+				warning!("Installed asset {} is not for {}, ignored", name, arch);
+				return None;
+			}
+			// loose files are recorded as a list, never an asset name
+			assets.iter().find(|a| a.name == name && a.is_archive())
+		});
+	let chosen = installed
+		.or_else(|| match_asset(&assets, arch, pattern.as_ref()))
+		.and_then(|chosen| assets.iter().position(|a| a.name == chosen.name));
+	let loose = if chosen.is_none() {
+		loose_files(&assets)
+	} else {
+		vec![]
 	};
+	Ok(Selection {
+		tag,
+		repo,
+		up_to_date: false,
+		released,
+		assets,
+		chosen,
+		loose,
+	})
+}
+
+/// A release extracted by `download_release`.
+pub struct Downloaded {
+	pub tag: String,
+	/// Name of the asset, None if it's already up to date.
+	pub asset: Option<String>,
+	/// See `Selection::repo`.
+	pub repo: Option<String>,
+	/// See `Selection::released`.
+	pub released: Option<String>,
 }
 
-/// print message for item that is up to date.
+/// Download and extract a release from a repository.
 ///
-/// Works like `println!`.
-#[macro_export]
-macro_rules! up_to_date {
-	($name:expr, $version:expr) => {
-		$crate::print_message!("=", bright_blue, "{}@{}", $name, $version)
+/// # Arguments
+///
+/// * `source` - Where the release is fetched from.
+/// * `root` - The directory to extract the plugin to.
+/// * `repo` - The repository to download from.
+/// * `version` - The tagged version of the repository to download.
+/// * `arch` - The architecture of the system, either x64 or arm64.
+/// * `current_version` - The current version of the repository that is installed.
+/// * `filter` - How to choose the asset.
+/// * `cache` - Directory of `asset_cache`, checked before downloading and filled afterwards.
+/// * `limits` - Bounds of the extracted files.
+///
+/// # Returns
+/// The version of the repository that was downloaded, and the asset.
+#[allow(clippy::too_many_arguments)]
+pub fn download_release(
+	source: &dyn ReleaseSource,
+	root: &Path,
+	repo: &str,
+	version: Option<&str>,
+	arch: &Arch,
+	current_version: Option<&str>,
+	filter: &AssetFilter,
+	cache: Option<&Path>,
+	limits: &ExtractLimits,
+) -> Result<Downloaded> {
+	let selection = select_release(source, repo, version, arch, current_version, filter)?;
+	if selection.up_to_date {
+		return Ok(Downloaded {
+			tag: selection.tag,
+			asset: None,
+			repo: selection.repo,
+			released: selection.released,
+		});
+	}
+	if selection.chosen.is_none() && !selection.loose.is_empty() {
+		return download_loose(source, root, selection);
+	}
+	let asset = match selection.chosen {
+		Some(i) => &selection.assets[i],
+		None => manual_select(&selection.assets, arch, filter.assume_yes)?,
+	};
+	let url = &asset.browser_download_url;
+	let name = asset_file_name(&asset.name)?;
+
+	let tag = &selection.tag;
+	let file_path = download_path();
+	let _temp = TempFiles(vec![file_path.clone()]);
+	let download = || -> Result<()> {
+		let mut file = File::create(&file_path)?;
+		source.download(url, &mut file)
 	};
+	let cached =
+		cache.is_some_and(|dir| asset_cache::get(dir, repo, tag, name, asset.size, &file_path));
+	if !cached {
+		download()?;
+	}
+
+	match (
+		extract_zip(&file_path, name, root, arch, limits, filter.assume_yes),
+		cache,
+	) {
+		(Err(e), Some(dir)) if cached => {
+			warning!("Cached {} is damaged ({}), downloading it again", name, e);
+			asset_cache::remove(dir, repo, tag, name);
+			download()?;
+			extract_zip(&file_path, name, root, arch, limits, filter.assume_yes)?;
+			asset_cache::put(dir, repo, tag, name, &file_path);
+		}
+		(result, Some(dir)) if !cached => {
+			result?;
+			asset_cache::put(dir, repo, tag, name, &file_path);
+		}
+		(result, _) => result?,
+	}
+
+	Ok(Downloaded {
+		asset: Some(asset.name.clone()),
+		tag: selection.tag,
+		repo: selection.repo,
+		released: selection.released,
+	})
+}
+
+/// Download the loose files of a release into the plugin directory, like an archive with
+/// them at its root. They aren't cached.
+fn download_loose(
+	source: &dyn ReleaseSource,
+	root: &Path,
+	selection: Selection,
+) -> Result<Downloaded> {
+	let root = long_path(root);
+	fs::create_dir_all(&root)?;
+	for &i in &selection.loose {
+		let asset = &selection.assets[i];
+		let mut file = File::create(root.join(asset_file_name(&asset.name)?))?;
+		source.download(&asset.browser_download_url, &mut file)?;
+	}
+	Ok(Downloaded {
+		asset: Some(selection.loose_names()),
+		tag: selection.tag,
+		repo: selection.repo,
+		released: selection.released,
+	})
+}
+
+/// The file name of an asset without path components, the API allows names like
+/// `releases/Foo.zip`.
+fn asset_file_name(name: &str) -> Result<&str> {
+	let file = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+	if file.is_empty() || file == "." || file == ".." {
+		bail!("Invalid asset name '{}'", name);
+	}
+	Ok(file)
+}
+
+/// A new file in the temp directory to download an asset to, the asset name is never used
+/// as a path.
+fn download_path() -> PathBuf {
+	static COUNT: AtomicUsize = AtomicUsize::new(0);
+	let n = COUNT.fetch_add(1, atomic::Ordering::Relaxed);
+	env::temp_dir().join(format!("ptr-{}-{}.zip", process::id(), n))
+}
+
+/// Headers for GitHub API requests.
+fn headers(token: Option<&str>) -> HeaderMap {
+	let mut headers = HeaderMap::new();
+	headers.insert(USER_AGENT, "reqwest".parse().unwrap());
+	headers.insert(ACCEPT, "application/vnd.github+json".parse().unwrap());
+	headers.insert("X-GitHub-Api-Version", "2022-11-28".parse().unwrap());
+	if let Some(token) = token {
+		headers.insert(AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+	}
+	headers
 }
 
-/// print message for removing an item.
+/// Get releases between `from` (exclusive) and `to` (inclusive), newest first.
 ///
-/// Works like `println!`.
-#[macro_export]
-macro_rules! remove {
-	($name:expr) => {
-		$crate::print_message!("-", bright_red, $name)
-	};
+/// Without `to`, start from the latest release that is not a draft or prerelease.
+pub fn get_releases(
+	github: &GitHub,
+	repo: &str,
+	from: Option<&str>,
+	to: Option<&str>,
+) -> Result<Vec<Release>> {
+	const PER_PAGE: usize = 100;
+	let context = format!("Failed to fetch releases of {repo}");
+	let mut releases = Vec::new();
+	let mut started = false;
+	for page in 1.. {
+		let page: Vec<Release> = github
+			.get_repo(
+				repo,
+				"/releases",
+				&[
+					("per_page", &PER_PAGE.to_string()),
+					("page", &page.to_string()),
+				],
+				&context,
+			)?
+			.json()?;
+		let len = page.len();
+		for release in page {
+			if from.is_some_and(|f| f == release.tag_name) {
+				return Ok(releases);
+			}
+			if !started {
+				started = match to {
+					Some(to) => to == release.tag_name,
+					None => !release.draft && !release.prerelease,
+				};
+			}
+			if started && !release.draft {
+				releases.push(release);
+			}
+		}
+		if len < PER_PAGE {
+			break;
+		}
+	}
+	if !started {
+		bail!("Release {} not found in {}", to.unwrap_or("latest"), repo);
+	}
+	Ok(releases)
 }
 
-/// Print an error message to stderr.
-#[macro_export]
-macro_rules! error {
-    ($msg:expr) => {{
-        use colored::Colorize;
-        eprintln!("{} {}", "error:".bright_red().bold(), $msg)
-    }};
-    ($fmt:expr, $($arg:tt)*) => {{
-        use colored::Colorize;
-        eprintln!("{} {}", "error:".bright_red().bold(), format!($fmt, $($arg)*))
-    }};
+/// Render markdown as plain text, headers become indented text and lists are kept.
+pub fn render_markdown(markdown: &str) -> String {
+	let mut out = String::new();
+	for line in markdown.lines() {
+		let trimmed = line.trim_start();
+		let level = trimmed.chars().take_while(|&c| c == '#').count();
+		let line = if level > 0 && trimmed[level..].starts_with(' ') {
+			format!("{}{}", "  ".repeat(level - 1), trimmed[level..].trim())
+		} else if let Some(item) = trimmed.strip_prefix("* ").or(trimmed.strip_prefix("+ ")) {
+			format!("{}- {}", &line[..line.len() - trimmed.len()], item)
+		} else {
+			line.trim_end().to_string()
+		};
+		out.push_str(&line.replace("**", "").replace("__", ""));
+		out.push('\n');
+	}
+	out
 }
 
-/// Print a error message to stderr and exit with code 0.
-#[macro_export]
-macro_rules! exit {
-    ($($arg:tt)*) => {{
-        $crate::error!($($arg)*);
-        std::process::exit(0);
-    }};
+#[derive(Deserialize)]
+struct CodeSearchResponse {
+	items: Vec<CodeSearchItem>,
+}
+
+#[derive(Deserialize)]
+struct CodeSearchItem {
+	repository: CodeSearchRepository,
+}
+
+#[derive(Deserialize)]
+struct CodeSearchRepository {
+	full_name: String,
+}
+
+/// Search GitHub for a repository with plugin.json containing the plugin name.
+///
+/// Code search requires a token.
+pub fn search_plugin_repo(github: &GitHub, name: &str) -> Result<Option<String>> {
+	let res: CodeSearchResponse = github
+		.get(
+			"/search/code",
+			&[("q", &format!("\"{name}\" filename:plugin.json"))],
+			&format!("Failed to search for {name}"),
+		)?
+		.json()?;
+	Ok(res.items.into_iter().next().map(|i| i.repository.full_name))
+}
+
+#[derive(Deserialize)]
+struct RateLimitResponse {
+	resources: RateLimitResources,
+}
+
+#[derive(Deserialize)]
+struct RateLimitResources {
+	core: RateLimit,
+}
+
+/// Budget of REST API requests.
+#[derive(Deserialize)]
+pub struct RateLimit {
+	pub limit: u64,
+	pub remaining: u64,
+	/// Unix time the budget is refilled.
+	pub reset: u64,
+}
+
+impl RateLimit {
+	/// Minutes until the budget is refilled, at least 1.
+	pub fn minutes_to_reset(&self) -> u64 {
+		let now = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_or(0, |d| d.as_secs());
+		self.reset.saturating_sub(now).div_ceil(60).max(1)
+	}
+}
+
+#[derive(Deserialize)]
+struct UserResponse {
+	login: String,
+}
+
+/// Get the login of the owner of the token of `github`, and the remaining rate limit.
+pub fn get_user(github: &GitHub) -> Result<(String, Option<String>)> {
+	let res = github.get("/user", &[], "Failed to validate token")?;
+	let remaining = res
+		.headers()
+		.get("x-ratelimit-remaining")
+		.and_then(|v| v.to_str().ok())
+		.map(String::from);
+	let user: UserResponse = res.json()?;
+	Ok((user.login, remaining))
+}
+
+fn manual_select<'a>(assets: &'a [Assets], arch: &Arch, assume_yes: bool) -> Result<&'a Assets> {
+	if let [asset] = assets {
+		if !asset.is_archive() {
+			bail!(
+				"Only asset '{}' is not an installable archive, this plugin may not support ptr",
+				asset.name
+			);
+		}
+		return Ok(asset);
+	}
+	if assume_yes {
+		return best_asset(assets, arch)
+			.filter(|a| a.is_archive())
+			.ok_or(anyhow!(
+				"No asset clearly matches {}, select one without --yes or use --pattern",
+				arch
+			));
+	}
+
+	for (i, asset) in assets.iter().enumerate() {
+		if asset.is_archive() {
+			info!("{}: {}", i.to_string().bright_yellow(), asset.name);
+		} else {
+			info!(
+				"{}: {} {}",
+				i.to_string().bright_yellow(),
+				asset.name.dimmed(),
+				"(unsupported)".dimmed()
+			);
+		}
+	}
+	let index: usize = prompt(&t!(SelectAsset), false)?.parse()?;
+	let asset = assets.get(index).ok_or(anyhow!("Invalid index"))?;
+	if !asset.is_archive() {
+		bail!(
+			"{} is not an archive ptr can install, supported: {}",
+			asset.name,
+			ARCHIVE_EXTENSIONS.join(", ")
+		);
+	}
+	Ok(asset)
+}
+
+/// The asset scoring highest for being a .zip for `arch`, None if there's no single winner.
+fn best_asset<'a>(assets: &'a [Assets], arch: &Arch) -> Option<&'a Assets> {
+	let score = |asset: &Assets| {
+		let name = asset.name.to_lowercase();
+		let has_arch = |a: &Arch| a.aliases().iter().any(|alias| name.contains(alias));
+		i32::from(name.ends_with(".zip")) + 2 * i32::from(has_arch(arch))
+			- 2 * i32::from(Arch::ALL.iter().any(|a| a != arch && has_arch(a)))
+	};
+	let best = assets.iter().map(score).max()?;
+	let mut winners = assets.iter().filter(|a| score(a) == best);
+	match (winners.next(), winners.next()) {
+		(Some(winner), None) if best > 0 => Some(winner),
+		_ => None,
+	}
+}
+
+/// Extract the archive at `zip_path`, downloaded from the asset `name`.
+fn extract_zip(
+	zip_path: &Path,
+	name: &str,
+	root: &Path,
+	arch: &Arch,
+	limits: &ExtractLimits,
+	assume_yes: bool,
+) -> Result<()> {
+	let mut archive = ZipArchive::new(File::open(zip_path)?)?;
+	let Some(inner) = nested_archive(archive.file_names()) else {
+		return extract_archive(archive, root, arch, limits, assume_yes);
+	};
+
+	// only one level of nesting, the inner archive is extracted as is
+	warning!("{} is inside {}, extracting the inner archive", inner, name);
+	let file = archive.by_name(&inner)?;
+	if file.size() > limits.max_size {
+		return Err(limits.exceeded(&inner));
+	}
+	// held in memory, so the declared size isn't trusted
+	let mut buf = Vec::new();
+	file.take(limits.max_size + 1).read_to_end(&mut buf)?;
+	if buf.len() as u64 > limits.max_size {
+		return Err(limits.exceeded(&inner));
+	}
+	extract_archive(
+		ZipArchive::new(io::Cursor::new(buf))?,
+		root,
+		arch,
+		limits,
+		assume_yes,
+	)
+}
+
+/// The only zip in an archive without any plugin file.
+fn nested_archive<'a>(names: impl Iterator<Item = &'a str>) -> Option<String> {
+	let mut zips = Vec::new();
+	for name in names {
+		let lower = name.to_lowercase();
+		if lower.ends_with(".dll") || lower.ends_with("plugin.json") {
+			return None;
+		}
+		if lower.ends_with(".zip") {
+			zips.push(name);
+		}
+	}
+	match zips.as_slice() {
+		[zip] => Some(zip.to_string()),
+		_ => None,
+	}
+}
+
+fn extract_archive<R: io::Read + io::Seek>(
+	mut archive: ZipArchive<R>,
+	root: &Path,
+	arch: &Arch,
+	limits: &ExtractLimits,
+	assume_yes: bool,
+) -> Result<()> {
+	if is_source_archive(archive.file_names()) {
+		bail!("Asset appears to be source code, not a built plugin");
+	}
+	if archive.len() > limits.max_files {
+		bail!(
+			"Archive exceeds safety limits, it has {} entries, more than {}",
+			archive.len(),
+			limits.max_files
+		);
+	}
+
+	let parent = plugin_root(&archive, arch, assume_yes)?;
+	let parent = Path::new(&parent);
+
+	// extract all files under the plugin root and keep the directory structure
+	let root_path = long_path(root);
+	let mut extracted = 0;
+	for i in 0..archive.len() {
+		let mut file = archive.by_index(i)?;
+		let Some(out_path) = Path::new(file.name())
+			.strip_prefix(parent)
+			.ok()
+			.map(|p| long_path(&root.join(p)))
+		else {
+			continue;
+		};
+		// the entry of the plugin root itself
+		if out_path == root_path && file.is_dir() {
+			continue;
+		}
+		// never write beside version.toml or into other plugins
+		if out_path == root_path || !out_path.starts_with(&root_path) {
+			bail!(
+				"Archive entry {} would be extracted outside of the plugin directory",
+				file.name()
+			);
+		}
+
+		if file.is_dir() {
+			fs::create_dir_all(out_path)?;
+		} else {
+			if let Some(p) = out_path.parent() {
+				fs::create_dir_all(p)?;
+			}
+			if file.size() > limits.max_size - extracted {
+				return Err(limits.exceeded(file.name()));
+			}
+			let modified = file.last_modified().and_then(zip_time);
+			let mut out_file = polling::create(out_path)?;
+			// the declared size can be a lie, stop at the remaining budget
+			let budget = limits.max_size - extracted;
+			let name = file.name().to_string();
+			extracted += polling::copy(&mut (&mut file).take(budget + 1), &mut out_file)?;
+			if extracted > limits.max_size {
+				return Err(limits.exceeded(&name));
+			}
+			if let Some(modified) = modified {
+				out_file.set_modified(modified)?;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+/// Prefix an absolute path with `\\?\` so it can exceed `MAX_PATH` without the
+/// LongPathsEnabled registry setting.
+///
+/// Verbatim paths are passed to the file system as is, so the path is normalized first.
+/// Other platforms have no such prefix, the path is only normalized.
+pub fn long_path(path: &Path) -> PathBuf {
+	let mut normalized = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir => {
+				normalized.pop();
+			}
+			c => normalized.push(c),
+		}
+	}
+	if cfg!(not(windows)) && path.is_absolute() {
+		return normalized;
+	}
+	let normalized = normalized.to_string_lossy();
+	if !path.is_absolute() || normalized.starts_with(r"\\?\") {
+		return path.to_path_buf();
+	}
+	match normalized.strip_prefix(r"\\") {
+		Some(unc) => PathBuf::from(format!(r"\\?\UNC\{unc}")),
+		None => PathBuf::from(format!(r"\\?\{normalized}")),
+	}
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar, see
+/// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+	// March-based so the leap day ends the year
+	let year = year - i64::from(month <= 2);
+	let era = year.div_euclid(400);
+	let yoe = year - era * 400;
+	let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+	era * 146_097 + doe - 719_468
+}
+
+/// Convert the modified time of a zip entry to `SystemTime`.
+///
+/// Zip stores local time without a time zone, it's treated as UTC so it's stable across updates.
+fn zip_time(time: zip::DateTime) -> Option<SystemTime> {
+	let days = days_from_civil(
+		i64::from(time.year()),
+		i64::from(time.month()),
+		i64::from(time.day()),
+	);
+	let secs = days * 86400
+		+ i64::from(time.hour()) * 3600
+		+ i64::from(time.minute()) * 60
+		+ i64::from(time.second());
+	UNIX_EPOCH.checked_add(Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+/// Find the directory of the plugin in the archive.
+///
+/// When several directories contain plugin.json, e.g. builds for each arch in one archive,
+/// choose the one named with `arch`, or ask the user unless `assume_yes`.
+fn plugin_root<R: io::Read + io::Seek>(
+	archive: &ZipArchive<R>,
+	arch: &Arch,
+	assume_yes: bool,
+) -> Result<String> {
+	let parent = |f: &str| {
+		Path::new(f)
+			.parent()
+			.unwrap_or(Path::new(""))
+			.to_string_lossy()
+			.into_owned()
+	};
+	let roots: Vec<String> = archive
+		.file_names()
+		.filter(|f| f.to_lowercase().ends_with("plugin.json"))
+		.map(parent)
+		.collect();
+	match roots.as_slice() {
+		[] => {
+			// locate for .dll file and find it's parent
+			let dll = archive
+				.file_names()
+				.find(|f| f.ends_with(".dll"))
+				.ok_or(anyhow!("No .dll file found"))?;
+			return Ok(parent(dll));
+		}
+		[root] => return Ok(root.clone()),
+		_ => {}
+	}
+
+	let matched: Vec<&String> = roots
+		.iter()
+		.filter(|r| {
+			let r = r.to_lowercase();
+			arch.aliases().iter().any(|a| r.contains(a))
+		})
+		.collect();
+	if let [root] = matched.as_slice() {
+		return Ok(root.to_string());
+	}
+	for (i, root) in roots.iter().enumerate() {
+		info!("{}: {}", i.to_string().bright_yellow(), root);
+	}
+	let index: usize = prompt(&t!(SelectPlugin), assume_yes)?.parse()?;
+	roots.get(index).cloned().ok_or(anyhow!("Invalid index"))
+}
+
+/// Whether the archive has no .dll and plugin.json, or its top level looks like a source tree.
+/// GitHub's "Source code" archives wrap the tree in one directory, so two levels are checked.
+fn is_source_archive<'a>(names: impl Iterator<Item = &'a str>) -> bool {
+	let (mut has_dll, mut has_metadata, mut has_source) = (false, false, false);
+	for name in names {
+		let lower = name.to_lowercase();
+		has_dll |= lower.ends_with(".dll");
+		has_metadata |= lower.ends_with("plugin.json");
+		let top: Vec<_> = lower.split('/').take(2).collect();
+		has_source |= top.iter().any(|c| *c == "src" || *c == ".github")
+			|| (lower.matches('/').count() <= 1
+				&& (lower.ends_with(".csproj") || lower.ends_with(".sln")));
+	}
+	(!has_dll && !has_metadata) || has_source
+}
+
+/// Time before a hook is killed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Run `command` with `cmd /C` in `dir` and return its output.
+/// Fail with the output if it exits with an error or doesn't finish in `HOOK_TIMEOUT`.
+pub fn run_hook(command: &str, dir: &Path) -> Result<String> {
+	let mut child = Command::new("cmd")
+		.args(["/C", command])
+		.current_dir(dir)
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()?;
+	// read both pipes while waiting so a chatty hook doesn't block on a full pipe
+	let read = |mut pipe: Box<dyn Read + Send>| {
+		thread::spawn(move || {
+			let mut buf = Vec::new();
+			_ = pipe.read_to_end(&mut buf);
+			String::from_utf8_lossy(&buf).into_owned()
+		})
+	};
+	let stdout = read(Box::new(child.stdout.take().unwrap()));
+	let stderr = read(Box::new(child.stderr.take().unwrap()));
+
+	let start = SystemTime::now();
+	let status = loop {
+		if let Some(status) = child.try_wait()? {
+			break Some(status);
+		}
+		if start.elapsed().unwrap_or_default() > HOOK_TIMEOUT {
+			_ = child.kill();
+			_ = child.wait();
+			break None;
+		}
+		thread::sleep(Duration::from_millis(100));
+	};
+	let output = [stdout, stderr]
+		.into_iter()
+		.filter_map(|t| t.join().ok())
+		.collect::<Vec<_>>()
+		.concat();
+	match status {
+		Some(status) if status.success() => Ok(output),
+		Some(status) => bail!("{}\n{}", status, output.trim_end()),
+		None => bail!(
+			"killed after {}s\n{}",
+			HOOK_TIMEOUT.as_secs(),
+			output.trim_end()
+		),
+	}
+}
+
+/// Start `file` with `params` through the shell, using `verb` such as `runas` or `edit` if
+/// set, and return the process handle if `mask` has `SEE_MASK_NOCLOSEPROCESS`.
+///
+/// `params` are passed to the process as is, never through cmd.
+#[cfg(windows)]
+fn shell_execute(
+	verb: Option<&str>,
+	file: &std::ffi::OsStr,
+	params: &std::ffi::OsStr,
+	show: i32,
+	mask: u32,
+) -> Result<windows::Win32::Foundation::HANDLE> {
+	use windows::core::{HSTRING, PCWSTR};
+	use windows::Win32::UI::Shell::{ShellExecuteExW, SHELLEXECUTEINFOW};
+
+	let mut sei: SHELLEXECUTEINFOW = unsafe { mem::zeroed() };
+	sei.cbSize = mem::size_of::<SHELLEXECUTEINFOW>() as u32;
+	sei.fMask = mask;
+	let h_verb = verb.map(HSTRING::from);
+	if let Some(h_verb) = &h_verb {
+		sei.lpVerb = PCWSTR(h_verb.as_ptr());
+	}
+	let h_file = HSTRING::from(file);
+	sei.lpFile = PCWSTR(h_file.as_ptr());
+	sei.nShow = show;
+	let h_params = HSTRING::from(params);
+	sei.lpParameters = PCWSTR(h_params.as_ptr());
+
+	unsafe { ShellExecuteExW(&mut sei)? };
+	Ok(sei.hProcess)
+}
+
+#[cfg(windows)]
+fn run_process(program: &str, args: &str, admin: bool) -> Result<()> {
+	use windows::Win32::Foundation::CloseHandle;
+	use windows::Win32::System::Threading::{GetExitCodeProcess, WaitForSingleObject, INFINITE};
+	use windows::Win32::UI::Shell::SEE_MASK_NOCLOSEPROCESS;
+
+	let process = shell_execute(
+		admin.then_some("runas"),
+		program.as_ref(),
+		args.as_ref(),
+		0, // SW_HIDE
+		SEE_MASK_NOCLOSEPROCESS,
+	)?;
+	unsafe {
+		_ = WaitForSingleObject(process, INFINITE);
+		let mut exit_code = 0;
+		GetExitCodeProcess(process, &mut exit_code)?;
+		CloseHandle(process)?;
+
+		if exit_code == 0 {
+			Ok(())
+		} else {
+			Err(io::Error::from_raw_os_error(exit_code as i32).into())
+		}
+	}
+}
+
+#[cfg(not(windows))]
+fn run_process(_program: &str, _args: &str, _admin: bool) -> Result<()> {
+	bail!(UNSUPPORTED)
+}
+
+/// Open `path` with the app associated with `verb`, e.g. `edit` for a text editor, or with
+/// `fallback` like notepad.exe if nothing is associated, without waiting for it to exit.
+#[cfg(windows)]
+pub fn shell_open(path: &Path, verb: &str, fallback: &str) -> Result<()> {
+	use windows::Win32::UI::Shell::SEE_MASK_FLAG_NO_UI;
+
+	const SW_SHOWNORMAL: i32 = 1;
+	let opened = shell_execute(
+		Some(verb),
+		path.as_os_str(),
+		"".as_ref(),
+		SW_SHOWNORMAL,
+		SEE_MASK_FLAG_NO_UI,
+	);
+	if opened.is_ok() {
+		return Ok(());
+	}
+	shell_execute(None, fallback.as_ref(), &quote_arg(path), SW_SHOWNORMAL, 0)
+		.map(drop)
+		.map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))
+}
+
+/// `path` as one quoted parameter, so spaces and `&` in the path stay part of it. Trailing
+/// backslashes are doubled so the closing quote isn't escaped.
+#[cfg(windows)]
+fn quote_arg(path: &Path) -> std::ffi::OsString {
+	let trailing = path
+		.to_string_lossy()
+		.chars()
+		.rev()
+		.take_while(|c| *c == '\\')
+		.count();
+	let mut arg = std::ffi::OsString::from("\"");
+	arg.push(path);
+	arg.push("\\".repeat(trailing));
+	arg.push("\"");
+	arg
+}
+
+#[cfg(not(windows))]
+pub fn shell_open(_path: &Path, _verb: &str, _fallback: &str) -> Result<()> {
+	bail!(UNSUPPORTED)
+}
+
+/// Whether files can be created in `dir`, which is created if it doesn't exist.
+pub fn is_writable(dir: &Path) -> bool {
+	let probe = dir.join(format!(".ptr-{}", process::id()));
+	let writable = fs::create_dir_all(dir).is_ok() && File::create(&probe).is_ok();
+	_ = fs::remove_file(&probe);
+	writable
+}
+
+/// `path` quoted for a `cmd.exe /S /C` script. Fail if it contains `%`, which expands
+/// variables even inside quotes and can't be escaped on the command line, or a quote.
+fn cmd_quoted(path: &Path) -> Result<String> {
+	let path = path.display().to_string();
+	if path.contains(['%', '"']) {
+		bail!(
+			"{} contains % or \", it can't be changed as administrator",
+			path
+		);
+	}
+	Ok(format!("\"{path}\""))
+}
+
+/// Replace `target` with `source` as administrator, moving the old directory to `backup` and
+/// back if the swap fails.
+pub fn replace_dir_elevated(target: &Path, source: &Path, backup: &Path) -> Result<()> {
+	let (t, s, b) = (
+		cmd_quoted(target)?,
+		cmd_quoted(source)?,
+		cmd_quoted(backup)?,
+	);
+	let root = cmd_quoted(target.parent().unwrap_or(target))?;
+	let script = format!(
+		r#"(if exist {b} rmdir /S /Q {b}) & (if not exist {root} mkdir {root}) & (if exist {t} (move {t} {b} >nul || exit 1)) & (move {s} {t} >nul || ((if exist {b} move {b} {t} >nul) & exit 1)) & (if exist {b} rmdir /S /Q {b})"#
+	);
+	run_process("cmd.exe", &format!(r#"/S /C "{script}""#), true).map_err(|e| {
+		anyhow!(
+			"Failed to replace {} as administrator: {}",
+			target.display(),
+			e
+		)
+	})
+}
+
+/// Remove `dir` as administrator.
+pub fn remove_dir_elevated(dir: &Path) -> Result<()> {
+	let d = cmd_quoted(dir)?;
+	run_process("cmd.exe", &format!(r#"/S /C "rmdir /S /Q {d}""#), true)
+		.map_err(|e| anyhow!("Failed to remove {} as administrator: {}", dir.display(), e))
+}
+
+const LAUNCHER_EXE: &str = "PowerToys.PowerLauncher.exe";
+/// Updater PowerToys runs while it replaces its own files.
+const UPDATER_EXE: &str = "PowerToys.Update.exe";
+
+/// Seconds to wait for PowerToys to start or exit.
+const START_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time for PowerToys to create its window after the process appears.
+const START_SETTLE: Duration = Duration::from_secs(2);
+
+/// Image name pattern of the processes in `scope`.
+fn image_filter(scope: &RestartScope) -> &'static str {
+	match scope {
+		RestartScope::Runner => "PowerToys*",
+		RestartScope::Launcher => LAUNCHER_EXE,
+	}
+}
+
+/// Whether a process in `scope` is running.
+pub fn is_ptr_running(scope: &RestartScope) -> bool {
+	is_running(image_filter(scope))
+}
+
+/// Fail if the PowerToys updater is running, killing PowerToys in the middle of its update can
+/// leave it unable to start.
+pub fn check_powertoys_updating() -> Result<()> {
+	if is_running(UPDATER_EXE) {
+		bail!(t!(PowerToysUpdating));
+	}
+	Ok(())
+}
+
+/// Whether a process matching the image name `filter` is running.
+fn is_running(filter: &str) -> bool {
+	Command::new("tasklist.exe")
+		.args(["/NH", "/FI", &format!("IMAGENAME eq {filter}")])
+		.output()
+		.is_ok_and(|o| {
+			String::from_utf8_lossy(&o.stdout)
+				.to_lowercase()
+				.contains(".exe")
+		})
+}
+
+/// Process IDs of the processes in `scope`.
+#[cfg(windows)]
+fn ptr_pids(scope: &RestartScope) -> Result<Vec<u32>> {
+	let output = Command::new("tasklist.exe")
+		.args([
+			"/NH",
+			"/FO",
+			"CSV",
+			"/FI",
+			&format!("IMAGENAME eq {}", image_filter(scope)),
+		])
+		.output()?;
+	// "PowerToys.exe","1234","Console","1","100,000 K"
+	Ok(String::from_utf8_lossy(&output.stdout)
+		.lines()
+		.filter_map(|l| l.split("\",\"").nth(1)?.parse().ok())
+		.collect())
+}
+
+/// Whether killing the processes in `scope` needs administrator rights, i.e. one of them runs
+/// elevated and ptr doesn't.
+///
+/// A process whose token can't be queried is taken as elevated, that's what denies access.
+#[cfg(windows)]
+pub fn kill_needs_elevation(scope: &RestartScope) -> Result<bool> {
+	use windows::Win32::Foundation::{CloseHandle, HANDLE};
+	use windows::Win32::Security::{
+		GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY,
+	};
+	use windows::Win32::System::Threading::{
+		GetCurrentProcess, OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION,
+	};
+
+	fn is_elevated(process: HANDLE) -> Result<bool> {
+		unsafe {
+			let mut token = HANDLE::default();
+			OpenProcessToken(process, TOKEN_QUERY, &mut token)?;
+			let mut elevation = TOKEN_ELEVATION::default();
+			let mut len = 0;
+			let result = GetTokenInformation(
+				token,
+				TokenElevation,
+				Some(&mut elevation as *mut _ as *mut _),
+				mem::size_of::<TOKEN_ELEVATION>() as u32,
+				&mut len,
+			);
+			_ = CloseHandle(token);
+			result?;
+			Ok(elevation.TokenIsElevated != 0)
+		}
+	}
+
+	let elevated = is_elevated(unsafe { GetCurrentProcess() })?;
+	// listing the processes runs tasklist, which isn't needed then
+	let pids = if elevated { vec![] } else { ptr_pids(scope)? };
+	let targets = pids.into_iter().map(|pid| {
+		let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }.ok()?;
+		let elevated = is_elevated(process).ok();
+		_ = unsafe { CloseHandle(process) };
+		elevated
+	});
+	Ok(needs_elevation(elevated, targets))
+}
+
+/// Whether killing needs administrator rights, given if ptr is `elevated` and if each process
+/// to kill is, None if its token can't be queried. Stops at the first process that decides it.
+#[cfg(any(windows, test))]
+fn needs_elevation(elevated: bool, mut targets: impl Iterator<Item = Option<bool>>) -> bool {
+	!elevated && targets.any(|t| t.unwrap_or(true))
+}
+
+#[cfg(not(windows))]
+pub fn kill_needs_elevation(_scope: &RestartScope) -> Result<bool> {
+	bail!(UNSUPPORTED)
+}
+
+/// Wait until a process in `scope` is running, or none is if not `running`.
+/// A started PowerToys is given a moment to create its window.
+pub fn wait_ptr(scope: &RestartScope, running: bool) -> Result<()> {
+	let start = SystemTime::now();
+	while is_ptr_running(scope) != running {
+		if start.elapsed().unwrap_or_default() > START_TIMEOUT {
+			bail!(
+				"PowerToys didn't {} in {} seconds",
+				if running { "start" } else { "exit" },
+				START_TIMEOUT.as_secs()
+			);
+		}
+		thread::sleep(Duration::from_millis(250));
+	}
+	if running {
+		thread::sleep(START_SETTLE);
+	}
+	Ok(())
+}
+
+pub fn kill_ptr(admin: bool, scope: &RestartScope) -> Result<()> {
+	let filter = image_filter(scope);
+	run_process(
+		"taskkill.exe",
+		&format!("/F /FI \"IMAGENAME eq {filter}\""),
+		admin,
+	)?;
+	Ok(())
+}
+
+pub fn start_ptr(powertoys_path: &Path, scope: &RestartScope) -> Result<()> {
+	let path = match scope {
+		RestartScope::Runner => powertoys_path.to_path_buf(),
+		RestartScope::Launcher => {
+			// the launcher moved out of `modules\launcher` in newer PowerToys
+			let dir = powertoys_path.parent().unwrap_or(Path::new(""));
+			[
+				dir.join(LAUNCHER_EXE),
+				dir.join(r"modules\launcher").join(LAUNCHER_EXE),
+			]
+			.into_iter()
+			.find(|p| p.exists())
+			.ok_or(anyhow!("{} not found in {}", LAUNCHER_EXE, dir.display()))?
+		}
+	};
+	let c = Command::new(path).spawn()?;
+	mem::forget(c);
+	Ok(())
+}
+
+/// Detect PowerToys, or ask for its path unless `assume_yes`.
+pub fn get_powertoys_path(assume_yes: bool) -> Result<PathBuf> {
+	if let Some(path) = find_powertoys_path() {
+		return Ok(path);
+	}
+	if assume_yes {
+		bail!("PowerToys executable not found, set pt_path in version.toml");
+	}
+	let mut msg = t!(PowerToysNotFound);
+	for _ in 0..3 {
+		let path = PathBuf::from(prompt(&msg, false)?.trim_matches('"'));
+		if is_powertoys_path(&path) {
+			return Ok(path);
+		}
+		msg = t!(NotPowerToys);
+	}
+	bail!("PowerToys executable not found")
+}
+
+const TASK_NAME: &str = r"ptr\update";
+
+/// Register a weekly scheduled task running `ptr update --all --notify`.
+pub fn schedule_install() -> Result<()> {
+	let exe = env::current_exe()?;
+	run_schtasks(&[
+		"/Create",
+		"/TN",
+		TASK_NAME,
+		"/TR",
+		&format!("\"{}\" update --all --notify", exe.display()),
+		"/SC",
+		"WEEKLY",
+		"/F",
+	])
+}
+
+pub fn schedule_remove() -> Result<()> {
+	run_schtasks(&["/Delete", "/TN", TASK_NAME, "/F"])
+}
+
+fn run_schtasks(args: &[&str]) -> Result<()> {
+	let output = Command::new("schtasks.exe").args(args).output()?;
+	if !output.status.success() {
+		bail!(
+			"schtasks failed: {}",
+			String::from_utf8_lossy(&output.stderr).trim()
+		);
+	}
+	Ok(())
+}
+
+/// Lines around the part of a PowerShell profile written by `completion_install`.
+const COMPLETION_BEGIN: &str = "# begin ptr completion";
+const COMPLETION_END: &str = "# end ptr completion";
+
+/// Completion script sourced from the PowerShell profiles, shared by all ptr profiles.
+fn completion_path(ctx: &Context) -> PathBuf {
+	ctx.local_app_data.join("ptr").join("completion.ps1")
+}
+
+/// `$PROFILE` of Windows PowerShell and of PowerShell 7 if they're installed.
+fn powershell_profiles() -> Vec<PathBuf> {
+	["powershell.exe", "pwsh.exe"]
+		.into_iter()
+		.filter_map(|shell| {
+			let output = Command::new(shell)
+				.args(["-NoLogo", "-NoProfile", "-Command", "$PROFILE"])
+				.stdin(Stdio::null())
+				.output()
+				.ok()?;
+			let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+			(output.status.success() && !path.is_empty()).then(|| PathBuf::from(path))
+		})
+		.collect()
+}
+
+/// Write the completion script and dot-source it from the PowerShell profiles, creating them
+/// if needed. Profiles that already source it are left as they are.
+pub fn completion_install(ctx: &Context, script: &[u8]) -> Result<()> {
+	use crate::print_message;
+
+	let path = completion_path(ctx);
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	fs::write(&path, script)?;
+	print_message!("+", bright_green, path.display());
+
+	let profiles = powershell_profiles();
+	if profiles.is_empty() {
+		bail!(
+			"PowerShell not found, add `. \"{}\"` to your profile",
+			path.display()
+		);
+	}
+	let block = format!(
+		"{COMPLETION_BEGIN}\r\n. \"{}\"\r\n{COMPLETION_END}\r\n",
+		path.display()
+	);
+	for profile in profiles {
+		let content = fs::read_to_string(&profile).unwrap_or_default();
+		if content.contains(COMPLETION_BEGIN) {
+			print_message!("=", bright_blue, "{} already sources it", profile.display());
+			continue;
+		}
+		if let Some(parent) = profile.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		let separator = if content.is_empty() || content.ends_with('\n') {
+			""
+		} else {
+			"\r\n"
+		};
+		fs::write(&profile, format!("{content}{separator}{block}"))?;
+		print_message!("+", bright_green, profile.display());
+	}
+	info!("Restart PowerShell to complete ptr commands");
+	Ok(())
+}
+
+/// Remove the part written by `completion_install` from the PowerShell profiles, and the
+/// completion script.
+pub fn completion_uninstall(ctx: &Context) -> Result<()> {
+	use crate::remove;
+
+	for profile in powershell_profiles() {
+		let Some(content) = fs::read_to_string(&profile).ok() else {
+			continue;
+		};
+		let (Some(begin), Some(end)) =
+			(content.find(COMPLETION_BEGIN), content.find(COMPLETION_END))
+		else {
+			continue;
+		};
+		if end < begin {
+			warning!(
+				"{} has a broken ptr completion block, remove it by hand",
+				profile.display()
+			);
+			continue;
+		}
+		let rest = content[end + COMPLETION_END.len()..].trim_start_matches(['\r', '\n']);
+		fs::write(&profile, format!("{}{}", &content[..begin], rest))?;
+		remove!(profile.display());
+	}
+	let path = completion_path(ctx);
+	if path.exists() {
+		fs::remove_file(&path)?;
+		remove!(path.display());
+	}
+	Ok(())
+}
+
+/// Look for PowerToys in the default install locations.
+pub fn find_powertoys_path() -> Option<PathBuf> {
+	let env_path = |key: &str| PathBuf::from(env::var(key).unwrap_or_default());
+	let scoop = env::var("SCOOP")
+		.map(PathBuf::from)
+		.unwrap_or_else(|_| env_path("USERPROFILE").join("scoop"));
+	[
+		env_path("ProgramFiles").join("PowerToys"),
+		env_path("LOCALAPPDATA").join("PowerToys"),
+		scoop.join(r"apps\powertoys\current"),
+		env_path("LOCALAPPDATA").join(r"Microsoft\WindowsApps"),
+	]
+	.map(|p| p.join("PowerToys.exe"))
+	.into_iter()
+	.find(|p| is_powertoys_path(p))
+}
+
+/// Whether the path is an existing `PowerToys.exe`.
+pub fn is_powertoys_path(path: &Path) -> bool {
+	path.is_file()
+		&& path
+			.file_name()
+			.is_some_and(|n| n.eq_ignore_ascii_case("PowerToys.exe"))
+}
+
+/// Bytes available to the user on the volume of `path`.
+#[cfg(windows)]
+pub fn free_space(path: &Path) -> Result<u64> {
+	use windows::core::HSTRING;
+	use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+	let mut free = 0;
+	unsafe { GetDiskFreeSpaceExW(&HSTRING::from(path), Some(&mut free), None, None)? };
+	Ok(free)
+}
+
+#[cfg(not(windows))]
+pub fn free_space(_path: &Path) -> Result<u64> {
+	bail!(UNSUPPORTED)
+}
+
+/// Read the file version of the PowerToys executable, e.g. `0.87.1`.
+#[cfg(windows)]
+pub fn get_powertoys_version(powertoys_path: &Path) -> Result<String> {
+	use std::ffi::c_void;
+	use windows::core::{w, HSTRING};
+	use windows::Win32::Storage::FileSystem::{
+		GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW, VS_FIXEDFILEINFO,
+	};
+
+	let path = HSTRING::from(powertoys_path);
+	unsafe {
+		let size = GetFileVersionInfoSizeW(&path, None);
+		if size == 0 {
+			bail!("No version info found in {}", powertoys_path.display());
+		}
+		let mut data = vec![0u8; size as usize];
+		GetFileVersionInfoW(&path, None, size, data.as_mut_ptr().cast())?;
+
+		let mut info: *mut c_void = std::ptr::null_mut();
+		let mut len = 0;
+		if !VerQueryValueW(data.as_ptr().cast(), w!("\\"), &mut info, &mut len).as_bool()
+			|| info.is_null()
+		{
+			bail!("No version info found in {}", powertoys_path.display());
+		}
+		let info = &*(info as *const VS_FIXEDFILEINFO);
+		Ok(format!(
+			"{}.{}.{}",
+			info.dwFileVersionMS >> 16,
+			info.dwFileVersionMS & 0xffff,
+			info.dwFileVersionLS >> 16
+		))
+	}
+}
+
+#[cfg(not(windows))]
+pub fn get_powertoys_version(_powertoys_path: &Path) -> Result<String> {
+	bail!(UNSUPPORTED)
+}
+
+/// Compare two version strings numerically by their dot separated parts.
+///
+/// A leading `v` is ignored, and missing parts are treated as `0`.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+	fn parts(v: &str) -> Vec<u64> {
+		v.trim_start_matches(['v', 'V'])
+			.split('.')
+			.map(|p| {
+				p.chars()
+					.take_while(char::is_ascii_digit)
+					.collect::<String>()
+					.parse()
+					.unwrap_or(0)
+			})
+			.collect()
+	}
+	let (a, b) = (parts(a), parts(b));
+	let len = a.len().max(b.len());
+	let get = |v: &[u64], i: usize| v.get(i).copied().unwrap_or(0);
+	(0..len)
+		.map(|i| get(&a, i).cmp(&get(&b, i)))
+		.find(|o| o.is_ne())
+		.unwrap_or(Ordering::Equal)
+}
+
+/// Whether two tags are the same version, ignoring a leading `v` so `v1.2.3` from plugin.json
+/// matches a `1.2.3` release.
+pub fn same_version(a: &str, b: &str) -> bool {
+	a.trim_start_matches(['v', 'V']) == b.trim_start_matches(['v', 'V'])
+}
+
+/// Whether two directory names are the same plugin directory, ignoring case and surrounding
+/// spaces. Windows resolves paths case-insensitively and older extractions or manual copies can
+/// leave a trailing space that Win32 paths can't reach.
+pub fn same_dir_name(a: &str, b: &str) -> bool {
+	a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+/// Days since 1970-01-01 of the date a timestamp like `2024-05-01T12:00:00Z` starts with.
+fn unix_days(timestamp: &str) -> Option<i64> {
+	let mut parts = timestamp
+		.get(..10)?
+		.split('-')
+		.map(|p| p.parse::<i64>().ok());
+	let (y, m, d) = (parts.next()??, parts.next()??, parts.next()??);
+	if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+		return None;
+	}
+	Some(days_from_civil(y, m, d))
+}
+
+/// How long ago a release was published, e.g. `released 3 days ago`, from `published_at`.
+pub fn released_ago(published_at: &str) -> Option<String> {
+	let today = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs() / (24 * 60 * 60);
+	Some(match today as i64 - unix_days(published_at)? {
+		..=0 => "released today".to_string(),
+		1 => "released yesterday".to_string(),
+		days => format!("released {days} days ago"),
+	})
+}
+
+/// Web page of a GitHub repository.
+pub fn repo_url(repo: &str) -> String {
+	match split_host(repo) {
+		(Some(host), slug) => format!("https://{host}/{slug}"),
+		(None, slug) => format!("https://github.com/{slug}"),
+	}
+}
+
+/// Split the host of a GitHub Enterprise Server off `repo`, e.g. `ghe.corp.com` of
+/// `ghe.corp.com/team/plugin`. The host is None for github.com, whose prefix is also removed.
+///
+/// GitHub owners can't contain dots, so a first segment with one is a host.
+pub fn split_host(repo: &str) -> (Option<&str>, &str) {
+	match repo.split_once('/') {
+		Some((host, slug)) if host.contains('.') && slug.contains('/') => (
+			Some(host).filter(|h| !h.eq_ignore_ascii_case("github.com")),
+			slug,
+		),
+		_ => (None, repo),
+	}
+}
+
+/// The repository from a URL like `https://ghe.corp.com/team/plugin`, or `owner/repo` as is.
+/// The github.com host is removed, other hosts are kept for GitHub Enterprise Server.
+pub fn parse_repo(repo: &str) -> &str {
+	let repo = repo.trim().trim_end_matches('/');
+	let repo = repo
+		.strip_prefix("https://")
+		.or_else(|| repo.strip_prefix("http://"))
+		.map_or(repo, |r| r.trim_start_matches("www."));
+	match split_host(repo) {
+		(None, slug) => slug,
+		(Some(_), _) => repo,
+	}
+}
+
+/// `text` as an OSC 8 hyperlink to `url`, shown as plain text by terminals without support.
+pub fn hyperlink(url: &str, text: &str) -> String {
+	format!("\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\")
+}
+
+/// Whether stdout is a terminal known to support OSC 8 hyperlinks. Terminals ignoring them
+/// would be fine too, but some print the escape sequence, so only known ones are used.
+pub fn supports_hyperlinks() -> bool {
+	if !io::stdout().is_terminal() || !colored::control::SHOULD_COLORIZE.should_colorize() {
+		return false;
+	}
+	env::var_os("WT_SESSION").is_some()
+		|| env::var_os("DOMTERM").is_some()
+		|| env::var("TERM_PROGRAM")
+			.is_ok_and(|p| matches!(p.as_str(), "vscode" | "WezTerm" | "iTerm.app" | "ghostty"))
+		|| env::var("VTE_VERSION")
+			.ok()
+			.and_then(|v| v.parse::<u32>().ok())
+			.is_some_and(|v| v >= 5000)
+}
+
+/// Match `name` against a glob pattern with `*` and `?`, ignoring case.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+	let regex = regex::escape(pattern)
+		.replace(r"\*", ".*")
+		.replace(r"\?", ".");
+	Regex::new(&format!("(?i)^{regex}$")).is_ok_and(|r| r.is_match(name))
+}
+
+/// The candidate closest to `name`, if it's only a few edits away or differs in case.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+	let name = name.to_lowercase();
+	candidates
+		.into_iter()
+		.map(|c| (c, edit_distance(&name, &c.to_lowercase())))
+		.filter(|(_, d)| *d <= 2)
+		.min_by_key(|(_, d)| *d)
+		.map(|(c, _)| c)
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut row: Vec<usize> = (0..=b.len()).collect();
+	for (i, ca) in a.chars().enumerate() {
+		let mut prev = row[0];
+		row[0] = i + 1;
+		for (j, cb) in b.iter().enumerate() {
+			let current = row[j + 1];
+			row[j + 1] = if ca == *cb {
+				prev
+			} else {
+				1 + prev.min(row[j]).min(current)
+			};
+			prev = current;
+		}
+	}
+	row[b.len()]
+}
+
+/// Human readable size, e.g. `1.5 MB`.
+pub fn format_size(bytes: u64) -> String {
+	const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+	let mut size = bytes as f64;
+	let mut unit = 0;
+	while size >= 1024.0 && unit < UNITS.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+	match unit {
+		0 => format!("{bytes} B"),
+		_ => format!("{size:.1} {}", UNITS[unit]),
+	}
+}
+
+/// Hide all but the last 4 characters of a token.
+pub fn mask_token(token: &str) -> String {
+	let chars: Vec<char> = token.chars().collect();
+	let visible: String = chars[chars.len().saturating_sub(4)..].iter().collect();
+	format!("****{visible}")
+}
+
+/// Fields of the `plugin.json` shipped with every PowerToys Run plugin.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct PluginMetadata {
+	#[serde(rename = "ID")]
+	pub id: Option<String>,
+	pub name: String,
+	pub version: Option<String>,
+	pub author: Option<String>,
+	pub description: Option<String>,
+	pub website: Option<String>,
+	pub url_source_code: Option<String>,
+	pub url_download: Option<String>,
+	pub execute_file_name: Option<String>,
+	/// Not part of the PowerToys schema, but declared by some plugins.
+	pub min_power_toys_version: Option<String>,
+}
+
+/// Read `plugin.json` in the plugin directory.
+pub fn read_plugin_metadata(plugin_dir: &Path) -> Result<PluginMetadata> {
+	let path = plugin_dir.join("plugin.json");
+	let content = fs::read(&path)
+		.map_err(anyhow::Error::from)
+		.and_then(|bytes| decode_text(&bytes))
+		.map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+	serde_json::from_str(&content).map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Major version of .NET the plugin targets, from the runtimeconfig.json or deps.json of the
+/// `ExecuteFileName` in plugin.json.
+pub fn plugin_dotnet_version(plugin_dir: &Path) -> Option<u32> {
+	let file = read_plugin_metadata(plugin_dir).ok()?.execute_file_name?;
+	let stem = file.strip_suffix(".dll").unwrap_or(&file);
+	[".runtimeconfig.json", ".deps.json"]
+		.iter()
+		.find_map(|ext| target_dotnet_version(&plugin_dir.join(format!("{stem}{ext}"))))
+}
+
+/// Machine a PE file is built for, from the `Machine` field of its header.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PeArch {
+	X64,
+	ARM64,
+	X86,
+	/// Pure IL, loaded by either arch.
+	AnyCpu,
+	Other(u16),
+}
+
+impl PeArch {
+	/// Whether the file loads in a process of `arch`.
+	pub fn runs_on(self, arch: &Arch) -> bool {
+		matches!(
+			(self, arch),
+			(PeArch::AnyCpu, _) | (PeArch::X64, Arch::X64) | (PeArch::ARM64, Arch::ARM64)
+		)
+	}
+}
+
+impl std::fmt::Display for PeArch {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			PeArch::X64 => write!(f, "x64"),
+			PeArch::ARM64 => write!(f, "ARM64"),
+			PeArch::X86 => write!(f, "x86"),
+			PeArch::AnyCpu => write!(f, "AnyCPU"),
+			PeArch::Other(machine) => write!(f, "machine {machine:#06x}"),
+		}
+	}
+}
+
+/// Read the machine of a PE file, an I386 .NET assembly with the `ILONLY` flag is AnyCPU.
+pub fn pe_arch(path: &Path) -> Result<PeArch> {
+	const MACHINE_I386: u16 = 0x14c;
+	const MACHINE_AMD64: u16 = 0x8664;
+	const MACHINE_ARM64: u16 = 0xaa64;
+	const COMIMAGE_FLAGS_ILONLY: u32 = 0x1;
+	const COMIMAGE_FLAGS_32BITREQUIRED: u32 = 0x2;
+
+	let bytes = fs::read(path)?;
+	// offsets come from the file, so every sum is checked
+	let u16_at = |at: usize| {
+		bytes
+			.get(at..at.checked_add(2)?)
+			.map(|b| u16::from_le_bytes([b[0], b[1]]))
+	};
+	let u32_at = |at: usize| {
+		bytes
+			.get(at..at.checked_add(4)?)
+			.map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+	};
+	let invalid = || anyhow!("{} is not a PE file", path.display());
+	if bytes.get(..2) != Some(b"MZ") {
+		return Err(invalid());
+	}
+	let pe = u32_at(0x3c).ok_or_else(invalid)? as usize;
+	if bytes.get(pe..pe + 4) != Some(b"PE\0\0") {
+		return Err(invalid());
+	}
+	let machine = u16_at(pe + 4).ok_or_else(invalid)?;
+	match machine {
+		MACHINE_AMD64 => return Ok(PeArch::X64),
+		MACHINE_ARM64 => return Ok(PeArch::ARM64),
+		MACHINE_I386 => {}
+		machine => return Ok(PeArch::Other(machine)),
+	}
+
+	// find the CLR header through the data directories and the section table
+	let sections = u16_at(pe + 6).ok_or_else(invalid)? as usize;
+	let optional = pe + 24;
+	let optional_size = u16_at(pe + 20).ok_or_else(invalid)? as usize;
+	let directories = match u16_at(optional) {
+		Some(0x10b) => optional + 96,
+		Some(0x20b) => optional + 112,
+		_ => return Err(invalid()),
+	};
+	let clr_rva = u32_at(directories + 14 * 8).unwrap_or(0);
+	if clr_rva == 0 {
+		return Ok(PeArch::X86);
+	}
+	let mut clr_offset = None;
+	for i in 0..sections {
+		let section = optional + optional_size + i * 40;
+		let (Some(virtual_size), Some(address), Some(raw)) = (
+			u32_at(section + 8),
+			u32_at(section + 12),
+			u32_at(section + 20),
+		) else {
+			return Err(invalid());
+		};
+		let end = address.checked_add(virtual_size).ok_or_else(invalid)?;
+		if (address..end).contains(&clr_rva) {
+			let offset = (clr_rva - address).checked_add(raw).ok_or_else(invalid)?;
+			clr_offset = Some(offset as usize);
+			break;
+		}
+	}
+	let clr_offset = clr_offset.ok_or_else(invalid)?;
+	let flags = u32_at(clr_offset + 16).ok_or_else(invalid)?;
+	if flags & COMIMAGE_FLAGS_ILONLY != 0 && flags & COMIMAGE_FLAGS_32BITREQUIRED == 0 {
+		Ok(PeArch::AnyCpu)
+	} else {
+		Ok(PeArch::X86)
+	}
+}
+
+/// Major version of .NET bundled with PowerToys, from the runtimeconfig.json of PowerToys Run.
+pub fn powertoys_dotnet_version(powertoys_path: &Path) -> Option<u32> {
+	target_dotnet_version(
+		&powertoys_path
+			.parent()?
+			.join("PowerToys.PowerLauncher.runtimeconfig.json"),
+	)
+}
+
+/// Major version of .NET in a runtimeconfig.json or deps.json.
+fn target_dotnet_version(path: &Path) -> Option<u32> {
+	let content = decode_text(&fs::read(path).ok()?).ok()?;
+	let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+	let options = &json["runtimeOptions"];
+	let version = [
+		&options["includedFrameworks"][0]["version"],
+		&options["framework"]["version"],
+		&options["tfm"],
+		&json["runtimeTarget"]["name"],
+	]
+	.into_iter()
+	.filter_map(|v| v.as_str())
+	.find_map(dotnet_major);
+	version
+}
+
+/// Major version in a .NET version or target framework,
+/// e.g. 9 for `9.0.1`, `net9.0-windows` or `.NETCoreApp,Version=v9.0`.
+fn dotnet_major(version: &str) -> Option<u32> {
+	let version = version.rsplit_once("Version=v").map_or(version, |(_, v)| v);
+	let version = version.strip_prefix("net").unwrap_or(version);
+	version.split(['.', '-', '/']).next()?.parse().ok()
+}
+
+/// Decode a text file as UTF-16 if it starts with a UTF-16 BOM, or UTF-8 otherwise,
+/// without the BOM.
+fn decode_text(bytes: &[u8]) -> Result<String> {
+	let utf16 = |to_u16: fn([u8; 2]) -> u16| {
+		let units: Vec<u16> = bytes[2..]
+			.chunks_exact(2)
+			.map(|c| to_u16([c[0], c[1]]))
+			.collect();
+		String::from_utf16(&units).map_err(|_| anyhow!("invalid UTF-16"))
+	};
+	match bytes {
+		[0xff, 0xfe, ..] => utf16(u16::from_le_bytes),
+		[0xfe, 0xff, ..] => utf16(u16::from_be_bytes),
+		[0xef, 0xbb, 0xbf, rest @ ..] => Ok(String::from_utf8(rest.to_vec())?),
+		_ => Ok(String::from_utf8(bytes.to_vec())?),
+	}
+}
+
+/// PowerToys Run `settings.json`, which stores whether plugins are disabled.
+pub struct RunSettings(serde_json::Value);
+
+impl RunSettings {
+	pub fn load(path: &Path) -> Result<Self> {
+		let content = fs::read(path)
+			.map_err(anyhow::Error::from)
+			.and_then(|bytes| decode_text(&bytes))
+			.map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+		Ok(Self(serde_json::from_str(&content)?))
+	}
+
+	pub fn save(&self, path: &Path) -> Result<()> {
+		fs::write(path, serde_json::to_string_pretty(&self.0)?)?;
+		Ok(())
+	}
+
+	/// Whether the plugin is disabled, None if PowerToys Run has never loaded it.
+	pub fn is_disabled(&self, metadata: &PluginMetadata) -> Option<bool> {
+		self.find(metadata)
+			.map(|p| p["Disabled"].as_bool().unwrap_or(false))
+	}
+
+	/// Set the `Disabled` flag of the plugin.
+	/// Return `false` if PowerToys Run has never loaded the plugin.
+	pub fn set_disabled(&mut self, metadata: &PluginMetadata, disabled: bool) -> bool {
+		let Some(index) = self.position(metadata) else {
+			return false;
+		};
+		self.0["plugins"][index]["Disabled"] = serde_json::Value::Bool(disabled);
+		true
+	}
+
+	fn find(&self, metadata: &PluginMetadata) -> Option<&serde_json::Value> {
+		self.position(metadata).map(|i| &self.0["plugins"][i])
+	}
+
+	/// Index of the plugin in `plugins`, matched by ID, or by name if ID is absent.
+	fn position(&self, metadata: &PluginMetadata) -> Option<usize> {
+		let matches = |value: &serde_json::Value, expected: &str| {
+			value
+				.as_str()
+				.is_some_and(|v| v.eq_ignore_ascii_case(expected))
+		};
+		self.0["plugins"]
+			.as_array()?
+			.iter()
+			.position(|p| match &metadata.id {
+				Some(id) => matches(&p["Id"], id),
+				None => matches(&p["Name"], &metadata.name),
+			})
+	}
+}
+
+/// Get the `owner/repo` identifier from a GitHub URL.
+pub fn parse_github_repo(url: &str) -> Option<String> {
+	let path = url
+		.trim()
+		.trim_start_matches("https://")
+		.trim_start_matches("http://")
+		.trim_start_matches("www.")
+		.strip_prefix("github.com/")?;
+	let mut parts = path.split('/').filter(|p| !p.is_empty());
+	let owner = parts.next()?;
+	let repo = parts.next()?.trim_end_matches(".git");
+	Some(format!("{owner}/{repo}"))
+}
+
+/// Check that the directory looks like a loadable plugin: a valid `plugin.json`,
+/// and a `.dll` named by `ExecuteFileName`, or matching `Name` if it's absent.
+pub fn validate_plugin_dir(dir: &Path) -> Result<()> {
+	let metadata = read_plugin_metadata(dir)?;
+	let dlls: Vec<String> = fs::read_dir(dir)?
+		.filter_map(|e| e.ok())
+		.map(|e| e.file_name().to_string_lossy().to_lowercase())
+		.filter(|n| n.ends_with(".dll"))
+		.collect();
+	if dlls.is_empty() {
+		bail!("No .dll file found in plugin directory");
+	}
+	if let Some(file_name) = &metadata.execute_file_name {
+		if !dlls.contains(&file_name.to_lowercase()) {
+			bail!(
+				"{} from ExecuteFileName in plugin.json not found",
+				file_name
+			);
+		}
+	} else {
+		let name = metadata.name.replace(' ', "").to_lowercase();
+		if !dlls.iter().any(|d| d.contains(&name)) {
+			bail!("No .dll matching plugin name {} found", metadata.name);
+		}
+	}
+	Ok(())
+}
+
+/// Prompt the user for string input through the reporter, failing if `assume_yes`.
+pub fn prompt(msg: &str, assume_yes: bool) -> Result<String> {
+	if assume_yes {
+		bail!(t!(InputRequired, msg.trim()));
+	}
+	match reporter::prompt(msg) {
+		Some(input) => Ok(input.trim().to_string()),
+		None => bail!(
+			"No one to answer \"{}\", pass --yes to accept the defaults",
+			msg.trim()
+		),
+	}
+}
+
+/// Prompt the user for yes or no, empty input returns `default`, yes if `assume_yes`.
+pub fn confirm(msg: &str, default: bool, assume_yes: bool) -> Result<bool> {
+	if assume_yes {
+		return Ok(true);
+	}
+	let hint = if default { t!(HintYes) } else { t!(HintNo) };
+	let input = prompt(&format!("{msg} {hint} "), false)?.to_lowercase();
+	// English answers are accepted in every language
+	let is_any = |answers: String, english: &str| {
+		answers
+			.split('|')
+			.chain(english.split('|'))
+			.any(|a| a == input)
+	};
+	Ok(if is_any(t!(AnswersYes), "y|yes") {
+		true
+	} else if is_any(t!(AnswersNo), "n|no") {
+		false
+	} else {
+		default
+	})
+}
+
+/// Update ptr to the version, None for the latest version.
+///
+/// With `check`, only report whether an update is available.
+/// Installs from package managers are left to them unless `force`.
+///
+/// # Returns
+/// Whether an update is available.
+pub fn self_update(ctx: &Context, version: Option<&str>, check: bool, force: bool) -> Result<bool> {
+	use crate::{add, up_to_date};
+
+	if !check && !force {
+		if let Some(command) = package_manager_update_command() {
+			info!(
+				"ptr is installed by a package manager, update with `{}`, or use --force",
+				command.bright_cyan()
+			);
+			return Ok(false);
+		}
+	}
+
+	// download asset
+	let current_version = env!("CARGO_PKG_VERSION");
+	let source = GitHub::new(None).offline(ctx.offline);
+	let res = if let Some(version) = version {
+		let version = if version.starts_with('v') {
+			version.to_string()
+		} else {
+			format!("v{version}")
+		};
+		source.release_by_tag("8LWXpg/ptr", &version)?
+	} else {
+		source.latest_release("8LWXpg/ptr")?
+	};
+	let tag = res.tag_name;
+	if tag == format!("v{current_version}") {
+		up_to_date!("ptr", current_version);
+		return Ok(false);
+	}
+	if check {
+		info!(
+			"{} ptr v{} -> {}",
+			"update available:".bright_green(),
+			current_version,
+			tag
+		);
+		return Ok(true);
+	}
+	if compare_versions(&tag, current_version).is_lt()
+		&& !confirm(&t!(Downgrade, current_version, tag), false, ctx.assume_yes)?
+	{
+		bail!("Cancelled");
+	}
+
+	check_replaceable()?;
+	let arch = Arch::detect()?;
+	let assets = res.assets;
+	let asset = assets.iter().find(|a| a.is_arch(&arch)).ok_or(anyhow!(
+		"No asset found for {} in {}",
+		arch,
+		tag
+	))?;
+	replace_self(&source, asset, &env::temp_dir(), |exe| {
+		Ok(self_replace::self_replace(exe)?)
+	})?;
+	add!("ptr", tag);
+	Ok(true)
+}
+
+/// Download `asset` into the directory `temp`, and replace ptr with its ptr.exe by `replace`.
+fn replace_self(
+	source: &dyn ReleaseSource,
+	asset: &Assets,
+	temp: &Path,
+	replace: impl FnOnce(&Path) -> Result<()>,
+) -> Result<()> {
+	let (url, name) = (&asset.browser_download_url, &asset.name);
+
+	// the files are named by the process so concurrent updates don't overwrite each other,
+	// and removed when this function returns, even on failure
+	let lock_path = temp.join(SELF_UPDATE_LOCK);
+	let mut temp_files = TempFiles::default();
+	lock_self_update(&lock_path)?;
+	temp_files.0.push(lock_path);
+	let dir = temp.join(format!("ptr-{}", process::id()));
+	fs::create_dir_all(&dir)?;
+	let (file_path, out_path) = (dir.join(name), dir.join("ptr.exe"));
+	temp_files
+		.0
+		.extend([dir, file_path.clone(), out_path.clone()]);
+
+	source.download(url, &mut File::create(&file_path)?)?;
+
+	// extract and self replace
+	let mut archive = ZipArchive::new(File::open(&file_path)?)?;
+	let mut out_file = File::create(&out_path)?;
+	io::copy(&mut archive.by_name("ptr.exe")?, &mut out_file)?;
+	drop(out_file);
+	verify_executable(&out_path)?;
+	replace(&out_path)
+}
+
+/// Marker in the temp directory held during self update.
+const SELF_UPDATE_LOCK: &str = "ptr-self-update.lock";
+
+/// Files and empty directories removed in reverse order when dropped.
+#[derive(Default)]
+struct TempFiles(Vec<PathBuf>);
+
+impl Drop for TempFiles {
+	fn drop(&mut self) {
+		for path in self.0.iter().rev() {
+			if path.is_dir() {
+				_ = fs::remove_dir(path);
+			} else {
+				_ = fs::remove_file(path);
+			}
+		}
+	}
+}
+
+/// Create the lock marker, failing if another self update holds it.
+/// A marker older than 10 minutes is left by a crashed update and taken over.
+fn lock_self_update(path: &Path) -> Result<()> {
+	let stale = fs::metadata(path)
+		.and_then(|m| m.modified())
+		.is_ok_and(|t| t.elapsed().unwrap_or_default() > Duration::from_secs(600));
+	if stale {
+		_ = fs::remove_file(path);
+	}
+	if let Err(e) = File::options().write(true).create_new(true).open(path) {
+		if e.kind() == io::ErrorKind::AlreadyExists {
+			bail!("Another self update is running, try again later");
+		}
+		return Err(e.into());
+	}
+	Ok(())
+}
+
+/// Refuse to replace ptr on a network share or in a read-only directory.
+fn check_replaceable() -> Result<()> {
+	const HINT: &str = "install ptr with a package manager, e.g. `winget install 8LWXpg.ptr`";
+	let exe = env::current_exe()?;
+	let path = exe.to_string_lossy();
+	if path.starts_with(r"\\") && !path.starts_with(r"\\?\") || path.starts_with(r"\\?\UNC\") {
+		bail!(
+			"ptr is run from a network share and can't update itself, {}",
+			HINT
+		);
+	}
+	let dir = exe
+		.parent()
+		.ok_or(anyhow!("Invalid path of ptr: {}", path))?;
+	let probe = dir.join(format!(".ptr-{}", process::id()));
+	if let Err(e) = File::create(&probe) {
+		bail!(
+			"{} is not writable ({}) and ptr can't update itself, {}",
+			dir.display(),
+			e,
+			HINT
+		);
+	}
+	_ = fs::remove_file(&probe);
+	Ok(())
+}
+
+/// The update command of the package manager that installed ptr, if any.
+fn package_manager_update_command() -> Option<&'static str> {
+	package_manager_commands().map(|(update, _)| update)
+}
+
+/// The update and uninstall commands of the package manager that installed ptr, if any.
+fn package_manager_commands() -> Option<(&'static str, &'static str)> {
+	let exe = env::current_exe().ok()?.to_string_lossy().to_lowercase();
+	if exe.contains(r"\scoop\") {
+		Some(("scoop update ptr", "scoop uninstall ptr"))
+	} else if exe.contains(r"\winget\") {
+		Some(("winget upgrade 8LWXpg.ptr", "winget uninstall 8LWXpg.ptr"))
+	} else if exe.contains(r"\chocolatey\") {
+		Some(("choco upgrade ptr", "choco uninstall ptr"))
+	} else {
+		None
+	}
+}
+
+/// Remove the files of ptr itself: version.toml, the cache directory, the scheduled task, the
+/// stored token, and the executable once ptr exits.
+/// Installs from package managers keep the executable for them to remove.
+pub fn self_uninstall(ctx: &Context) -> Result<()> {
+	use crate::{credential, remove};
+
+	if ctx.config_path.exists() {
+		fs::remove_file(&ctx.config_path)?;
+		remove!(ctx.config_path.display());
+	}
+	if ctx.cache_path.exists() {
+		fs::remove_dir_all(&ctx.cache_path)?;
+		remove!(ctx.cache_path.display());
+	}
+	if run_schtasks(&["/Query", "/TN", TASK_NAME]).is_ok() {
+		schedule_remove()?;
+		remove!(format!("scheduled task {TASK_NAME}"));
+	}
+	if credential::read_token().is_some() {
+		credential::delete_token()?;
+		remove!("GitHub token");
+	}
+
+	if let Some((_, uninstall)) = package_manager_commands() {
+		info!(
+			"ptr is installed by a package manager, remove it with `{}`",
+			uninstall.bright_cyan()
+		);
+		return Ok(());
+	}
+	let exe = env::current_exe()?;
+	delete_after_exit(&exe)?;
+	remove!(exe.display());
+	Ok(())
+}
+
+/// Delete `path` from a detached script once ptr has exited, since a running executable can't
+/// delete itself. The script deletes itself afterwards.
+fn delete_after_exit(path: &Path) -> Result<()> {
+	let script = env::temp_dir().join(format!("ptr-uninstall-{}.cmd", process::id()));
+	fs::write(
+		&script,
+		format!(
+			"@echo off\r\nping -n 3 127.0.0.1 >nul\r\ndel /F /Q \"{}\"\r\ndel \"%~f0\"\r\n",
+			path.display()
+		),
+	)?;
+	Command::new("cmd")
+		.args(["/C", &script.to_string_lossy()])
+		.stdin(Stdio::null())
+		.stdout(Stdio::null())
+		.stderr(Stdio::null())
+		.spawn()?;
+	Ok(())
+}
+
+/// Check the PE headers, so a broken download never replaces ptr.
+fn verify_executable(path: &Path) -> Result<()> {
+	let data = fs::read(path)?;
+	let pe_offset = data
+		.get(0x3c..0x40)
+		.map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+	let is_pe = data.starts_with(b"MZ")
+		&& pe_offset
+			.and_then(|o| data.get(o..o + 4))
+			.is_some_and(|s| s == b"PE\0\0");
+	if !is_pe {
+		bail!("Downloaded ptr.exe is not a valid executable");
+	}
+	Ok(())
+}
+
+// region: macro
+/// Report a line starting with `symbol` in `color`, e.g. `bright_yellow`.
+#[macro_export]
+macro_rules! print_message {
+    ($symbol:expr, $color:ident, $msg:expr) => {
+        $crate::reporter::report($crate::reporter::Event::Message {
+            symbol: $symbol.to_string(),
+            color: $symbol.$color().fgcolor,
+            text: $msg.to_string(),
+        })
+    };
+    ($symbol:expr, $color:ident, $fmt:expr, $($arg:tt)*) => {
+        $crate::print_message!($symbol, $color, format!($fmt, $($arg)*))
+    };
+}
+
+/// Report an added item.
+#[macro_export]
+macro_rules! add {
+	($name:expr, $version:expr) => {
+		$crate::reporter::report($crate::reporter::Event::Added {
+			name: $name.to_string(),
+			version: $version.to_string(),
+		})
+	};
+}
+
+/// Report the item about to be processed, with its position in all items.
+#[macro_export]
+macro_rules! progress {
+	($index:expr, $total:expr, $name:expr) => {
+		$crate::reporter::report($crate::reporter::Event::Progress {
+			index: $index,
+			total: $total,
+			name: $name.to_string(),
+		})
+	};
+}
+
+/// Report an item that is up to date.
+#[macro_export]
+macro_rules! up_to_date {
+	($name:expr, $version:expr) => {
+		$crate::reporter::report($crate::reporter::Event::UpToDate {
+			name: $name.to_string(),
+			version: $version.to_string(),
+		})
+	};
+}
+
+/// Report a removed item.
+#[macro_export]
+macro_rules! remove {
+	($name:expr) => {
+		$crate::reporter::report($crate::reporter::Event::Removed {
+			name: $name.to_string(),
+		})
+	};
+}
+
+/// Report any other line of output, e.g. a table.
+#[macro_export]
+macro_rules! info {
+	($msg:literal) => {
+		$crate::info!(format!($msg))
+	};
+	($msg:expr) => {
+		$crate::reporter::report($crate::reporter::Event::Info {
+			text: $msg.to_string(),
+		})
+	};
+	($fmt:expr, $($arg:tt)*) => {
+		$crate::info!(format!($fmt, $($arg)*))
+	};
+}
+
+/// Report an error.
+#[macro_export]
+macro_rules! error {
+    ($msg:expr) => {
+        $crate::reporter::report($crate::reporter::Event::Error {
+            message: $msg.to_string(),
+        })
+    };
+    ($fmt:expr, $($arg:tt)*) => {
+        $crate::error!(format!($fmt, $($arg)*))
+    };
+}
+
+/// Report a warning.
+#[macro_export]
+macro_rules! warning {
+    ($msg:expr) => {
+        $crate::reporter::report($crate::reporter::Event::Warning {
+            message: $msg.to_string(),
+        })
+    };
+    ($fmt:expr, $($arg:tt)*) => {
+        $crate::warning!(format!($fmt, $($arg)*))
+    };
+}
+
+/// Message from the catalog in `i18n`, with the arguments for `{0}`, `{1}`, ...
+#[macro_export]
+macro_rules! t {
+	($msg:ident) => {
+		$crate::i18n::tr($crate::i18n::Msg::$msg).to_string()
+	};
+	($msg:ident, $($arg:expr),+ $(,)?) => {
+		$crate::i18n::fill(
+			$crate::i18n::tr($crate::i18n::Msg::$msg),
+			&[$(&$arg as &dyn std::fmt::Display),+],
+		)
+	};
+}
+// endregion
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use std::net::TcpListener;
+	use zip::write::SimpleFileOptions;
+	use zip::ZipWriter;
+
+	/// A zip archive of `files`, given as paths and contents.
+	pub(crate) fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+		let mut zip = ZipWriter::new(io::Cursor::new(Vec::new()));
+		for (name, content) in files {
+			zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+			zip.write_all(content.as_bytes()).unwrap();
+		}
+		zip.finish().unwrap().into_inner()
+	}
+
+	/// A release source serving one release, whose assets are downloaded from memory.
+	pub(crate) struct FakeSource {
+		pub(crate) tag: String,
+		pub(crate) assets: Vec<(String, Vec<u8>)>,
+	}
+
+	impl ReleaseSource for FakeSource {
+		fn latest_release(&self, _repo: &str) -> Result<Release> {
+			let assets: Vec<_> = self
+				.assets
+				.iter()
+				.map(|(name, data)| {
+					serde_json::json!({
+						"name": name,
+						"browser_download_url": name,
+						"size": data.len(),
+					})
+				})
+				.collect();
+			let release = serde_json::json!({ "tag_name": self.tag, "assets": assets });
+			Ok(serde_json::from_value(release)?)
+		}
+
+		fn release_by_tag(&self, repo: &str, tag: &str) -> Result<Release> {
+			if tag != self.tag {
+				bail!("Failed to fetch {} for {}: Not Found", tag, repo);
+			}
+			self.latest_release(repo)
+		}
+
+		fn download(&self, url: &str, writer: &mut dyn Write) -> Result<()> {
+			let (_, data) = self
+				.assets
+				.iter()
+				.find(|(name, _)| name == url)
+				.ok_or(anyhow!("{} not found", url))?;
+			writer.write_all(data)?;
+			Ok(())
+		}
+	}
+
+	/// Serve one canned response per request on a local port, in order, and return the base URL.
+	/// Each response is the status line, extra headers and body.
+	fn serve(responses: Vec<(&'static str, String, Vec<u8>)>) -> String {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("http://{}", listener.local_addr().unwrap());
+		thread::spawn(move || {
+			for ((status, headers, body), stream) in responses.into_iter().zip(listener.incoming())
+			{
+				let mut stream = stream.unwrap();
+				let mut request = Vec::new();
+				let mut buf = [0; 1024];
+				while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+					let n = stream.read(&mut buf).unwrap();
+					if n == 0 {
+						break;
+					}
+					request.extend_from_slice(&buf[..n]);
+				}
+				let head = format!("HTTP/1.1 {status}\r\nConnection: close\r\n{headers}\r\n");
+				stream.write_all(head.as_bytes()).unwrap();
+				stream.write_all(&body).unwrap();
+			}
+		});
+		url
+	}
+
+	fn json(status: &'static str, body: &str) -> (&'static str, String, Vec<u8>) {
+		let headers = format!(
+			"Content-Type: application/json\r\nContent-Length: {}\r\n",
+			body.len()
+		);
+		(status, headers, body.as_bytes().to_vec())
+	}
+
+	fn github(base_url: String) -> GitHub {
+		GitHub {
+			base_url,
+			..GitHub::new(None)
+		}
+	}
+
+	fn release(tag: &str, assets: &[&str]) -> String {
+		let assets: Vec<_> = assets
+			.iter()
+			.map(|name| {
+				serde_json::json!({
+					"name": name,
+					"browser_download_url": format!("https://example.com/{name}"),
+					"size": 10,
+				})
+			})
+			.collect();
+		serde_json::json!({ "tag_name": tag, "assets": assets }).to_string()
+	}
+
+	#[test]
+	fn latest_release_with_matching_asset() {
+		let body = release("v1.2.0", &["Foo-1.2.0-arm64.zip", "Foo-1.2.0-x64.zip"]);
+		let source = github(serve(vec![json("200 OK", &body)]));
+		let selection = select_release(
+			&source,
+			"owner/Foo",
+			None,
+			&Arch::X64,
+			None,
+			&AssetFilter::default(),
+		)
+		.unwrap();
+		assert_eq!(selection.tag, "v1.2.0");
+		assert!(!selection.up_to_date);
+		assert_eq!(
+			selection.asset(),
+			Some(("Foo-1.2.0-x64.zip".to_string(), 10))
+		);
+	}
+
+	#[test]
+	fn release_tag_not_found() {
+		let source = github(serve(vec![json(
+			"404 Not Found",
+			r#"{"message": "Not Found"}"#,
+		)]));
+		let err = source.release_by_tag("owner/Foo", "v9.9.9").err().unwrap();
+		let msg = err.to_string();
+		assert!(msg.starts_with("Failed to fetch v9.9.9 for owner/Foo: Not Found, Not Found"));
+		assert!(msg.contains("/repos/owner/Foo/releases/tags/v9.9.9"));
+	}
+
+	#[test]
+	fn release_rate_limited() {
+		let source = github(serve(vec![json(
+			"403 Forbidden",
+			r#"{"message": "API rate limit exceeded for 127.0.0.1."}"#,
+		)]));
+		let msg = source
+			.latest_release("owner/Foo")
+			.err()
+			.unwrap()
+			.to_string();
+		assert!(msg.starts_with(
+			"Failed to fetch latest for owner/Foo: Forbidden, API rate limit exceeded"
+		));
+	}
+
+	#[test]
+	fn release_pattern_selects_asset() {
+		let body = release(
+			"v1.2.0",
+			&[
+				"Foo-1.2.0-x64.zip",
+				"Foo-Lite-1.2.0-x64.zip",
+				"Foo-1.2.0-arm64.zip",
+			],
+		);
+		let source = github(serve(vec![json("200 OK", &body)]));
+		let filter = AssetFilter {
+			pattern: Some("^{name}-Lite-{version}-{arch}\\.zip$"),
+			..Default::default()
+		};
+		let selection = select_release(
+			&source,
+			"owner/Foo",
+			Some("v1.2.0"),
+			&Arch::X64,
+			None,
+			&filter,
+		)
+		.unwrap();
+		assert_eq!(
+			selection.asset(),
+			Some(("Foo-Lite-1.2.0-x64.zip".to_string(), 10))
+		);
+	}
+
+	#[test]
+	fn release_up_to_date_chooses_nothing() {
+		let body = release("v1.2.0", &["Foo-1.2.0-x64.zip"]);
+		let source = github(serve(vec![json("200 OK", &body)]));
+		let selection = select_release(
+			&source,
+			"owner/Foo",
+			None,
+			&Arch::X64,
+			Some("1.2.0"),
+			&AssetFilter::default(),
+		)
+		.unwrap();
+		assert!(selection.up_to_date);
+		assert_eq!(selection.asset(), None);
+	}
+
+	#[test]
+	fn download_complete() {
+		let headers = "Content-Length: 5\r\n".to_string();
+		let url = serve(vec![("200 OK", headers, b"hello".to_vec())]);
+		let mut out = Vec::new();
+		github(url.clone())
+			.download(&format!("{url}/Foo.zip"), &mut out)
+			.unwrap();
+		assert_eq!(out, b"hello");
+	}
+
+	#[test]
+	fn download_truncated() {
+		let headers = "Content-Length: 100\r\n".to_string();
+		let url = serve(vec![("200 OK", headers, b"hello".to_vec())]);
+		let mut out = Vec::new();
+		assert!(github(url.clone())
+			.download(&format!("{url}/Foo.zip"), &mut out)
+			.is_err());
+		assert!(out.len() < 100);
+	}
+
+	/// A plugin directory with `files`, given as names and contents.
+	fn plugin_files(files: &[(&str, &str)]) -> tempfile::TempDir {
+		let temp = tempfile::tempdir().unwrap();
+		for (name, content) in files {
+			fs::write(temp.path().join(name), content).unwrap();
+		}
+		temp
+	}
+
+	const PLUGIN_JSON: &str =
+		r#"{"Name": "Foo", "Version": "1.0.0", "ExecuteFileName": "Foo.dll"}"#;
+
+	#[test]
+	fn validate_plugin_dir_valid() {
+		let dir = plugin_files(&[("plugin.json", PLUGIN_JSON), ("Foo.dll", "")]);
+		validate_plugin_dir(dir.path()).unwrap();
+		// without ExecuteFileName, a .dll containing the name is enough
+		let dir = plugin_files(&[
+			("plugin.json", r#"{"Name": "Foo Bar", "Version": "1.0.0"}"#),
+			("Community.PowerToys.Run.Plugin.FooBar.dll", ""),
+		]);
+		validate_plugin_dir(dir.path()).unwrap();
+	}
+
+	#[test]
+	fn validate_plugin_dir_missing_plugin_json() {
+		let dir = plugin_files(&[("Foo.dll", "")]);
+		assert!(validate_plugin_dir(dir.path()).is_err());
+	}
+
+	#[test]
+	fn validate_plugin_dir_missing_dll() {
+		let dir = plugin_files(&[("plugin.json", PLUGIN_JSON)]);
+		let msg = validate_plugin_dir(dir.path()).unwrap_err().to_string();
+		assert_eq!(msg, "No .dll file found in plugin directory");
+
+		let dir = plugin_files(&[("plugin.json", PLUGIN_JSON), ("Other.dll", "")]);
+		let msg = validate_plugin_dir(dir.path()).unwrap_err().to_string();
+		assert_eq!(msg, "Foo.dll from ExecuteFileName in plugin.json not found");
+	}
+
+	#[cfg(not(windows))]
+	#[test]
+	fn long_path_normalizes_without_prefix() {
+		assert_eq!(long_path(Path::new("/a/./b/../c")), Path::new("/a/c"));
+		assert_eq!(long_path(Path::new("a/../b")), Path::new("a/../b"));
+	}
+
+	/// Builds for both arches as sibling folders, like some plugins publish them.
+	fn dual_arch_archive() -> ZipArchive<io::Cursor<Vec<u8>>> {
+		let archive = zip(&[
+			("MyPlugin-x64/plugin.json", PLUGIN_JSON),
+			("MyPlugin-x64/Foo.dll", "x64"),
+			("MyPlugin-arm64/plugin.json", PLUGIN_JSON),
+			("MyPlugin-arm64/Foo.dll", "arm64"),
+		]);
+		ZipArchive::new(io::Cursor::new(archive)).unwrap()
+	}
+
+	#[test]
+	fn plugin_root_matches_arch() {
+		let archive = dual_arch_archive();
+		assert_eq!(
+			plugin_root(&archive, &Arch::X64, false).unwrap(),
+			"MyPlugin-x64"
+		);
+		assert_eq!(
+			plugin_root(&archive, &Arch::ARM64, false).unwrap(),
+			"MyPlugin-arm64"
+		);
+	}
+
+	#[test]
+	fn plugin_root_ambiguous_without_prompt() {
+		let archive = zip(&[
+			("One/plugin.json", PLUGIN_JSON),
+			("One/Foo.dll", ""),
+			("Two/plugin.json", PLUGIN_JSON),
+			("Two/Foo.dll", ""),
+		]);
+		let archive = ZipArchive::new(io::Cursor::new(archive)).unwrap();
+		assert!(plugin_root(&archive, &Arch::X64, false).is_err());
+	}
+
+	#[test]
+	fn extract_archive_dual_arch() {
+		for (arch, dll) in [(Arch::X64, "x64"), (Arch::ARM64, "arm64")] {
+			let temp = tempfile::tempdir().unwrap();
+			let root = temp.path().join("Foo");
+			extract_archive(
+				dual_arch_archive(),
+				&root,
+				&arch,
+				&ExtractLimits::default(),
+				false,
+			)
+			.unwrap();
+			assert_eq!(fs::read_to_string(root.join("Foo.dll")).unwrap(), dll);
+			assert!(root.join("plugin.json").exists());
+		}
+	}
+
+	/// Assets named `names`, without a download.
+	fn assets(names: &[&str]) -> Vec<Assets> {
+		names
+			.iter()
+			.map(|name| Assets {
+				name: name.to_string(),
+				browser_download_url: String::new(),
+				size: 0,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn best_asset_for_arch() {
+		let assets = assets(&["Foo-1.0.0-arm64.zip", "Foo-1.0.0-x64.zip", "Foo.zip.sha256"]);
+		let best = |arch| best_asset(&assets, arch).map(|a| a.name.as_str());
+		assert_eq!(best(&Arch::X64), Some("Foo-1.0.0-x64.zip"));
+		assert_eq!(best(&Arch::ARM64), Some("Foo-1.0.0-arm64.zip"));
+	}
+
+	/// Shaped like the QuickLookup release, which once had files extracted beside version.toml:
+	/// the plugin in one folder, with its own directory entries and a subfolder of images.
+	fn quicklookup_archive(extra: &[(&str, &str)]) -> ZipArchive<io::Cursor<Vec<u8>>> {
+		let plugin_json = r#"{"Name": "QuickLookup", "Version": "1.0.0", "ExecuteFileName": "Community.PowerToys.Run.Plugin.QuickLookup.dll"}"#;
+		let mut zip = ZipWriter::new(io::Cursor::new(Vec::new()));
+		let options = SimpleFileOptions::default();
+		zip.add_directory("QuickLookup/", options).unwrap();
+		zip.add_directory("QuickLookup/Images/", options).unwrap();
+		let files = [
+			("QuickLookup/plugin.json", plugin_json),
+			(
+				"QuickLookup/Community.PowerToys.Run.Plugin.QuickLookup.dll",
+				"dll",
+			),
+			("QuickLookup/Images/quicklookup.dark.png", "png"),
+			("QuickLookup/Images/quicklookup.light.png", "png"),
+		];
+		for (name, content) in files.iter().chain(extra) {
+			zip.start_file(*name, options).unwrap();
+			zip.write_all(content.as_bytes()).unwrap();
+		}
+		ZipArchive::new(zip.finish().unwrap()).unwrap()
+	}
+
+	#[test]
+	fn extract_archive_quicklookup() {
+		let archive = quicklookup_archive(&[]);
+		assert_eq!(
+			plugin_root(&archive, &Arch::X64, false).unwrap(),
+			"QuickLookup"
+		);
+
+		let temp = tempfile::tempdir().unwrap();
+		let root = temp.path().join("QuickLookup");
+		extract_archive(archive, &root, &Arch::X64, &ExtractLimits::default(), false).unwrap();
+		validate_plugin_dir(&root).unwrap();
+		assert!(root.join("Images/quicklookup.dark.png").exists());
+		let plugins: Vec<_> = fs::read_dir(temp.path()).unwrap().collect();
+		assert_eq!(plugins.len(), 1, "files extracted beside the plugin");
+	}
+
+	#[test]
+	fn extract_archive_refuses_entry_outside_plugin() {
+		let archive = quicklookup_archive(&[("QuickLookup/../version.toml", "")]);
+		let temp = tempfile::tempdir().unwrap();
+		let root = temp.path().join("QuickLookup");
+		let msg = extract_archive(archive, &root, &Arch::X64, &ExtractLimits::default(), false)
+			.unwrap_err()
+			.to_string();
+		assert_eq!(
+			msg,
+			"Archive entry QuickLookup/../version.toml would be extracted outside of the plugin \
+			 directory"
+		);
+		assert!(!temp.path().join("version.toml").exists());
+	}
+
+	#[cfg(windows)]
+	#[test]
+	fn long_path_adds_prefix() {
+		assert_eq!(
+			long_path(Path::new(r"C:\a\.\b\..\c")),
+			Path::new(r"\\?\C:\a\c")
+		);
+		assert_eq!(
+			long_path(Path::new(r"\\server\share\a")),
+			Path::new(r"\\?\UNC\server\share\a")
+		);
+		assert_eq!(long_path(Path::new(r"a\b")), Path::new(r"a\b"));
+	}
+
+	#[cfg(windows)]
+	#[test]
+	fn extract_and_remove_long_path() {
+		let temp = tempfile::tempdir().unwrap();
+		let root = temp.path().join("d".repeat(120)).join("Foo");
+		let nested = format!("Foo/{}/Foo.dll", "e".repeat(120));
+		let archive = zip(&[("Foo/plugin.json", PLUGIN_JSON), (&nested, "nested")]);
+		let archive = ZipArchive::new(io::Cursor::new(archive)).unwrap();
+
+		extract_archive(archive, &root, &Arch::X64, &ExtractLimits::default(), false).unwrap();
+		let file = root.join(nested.strip_prefix("Foo/").unwrap());
+		assert!(file.as_os_str().len() > 260);
+		assert_eq!(fs::read_to_string(long_path(&file)).unwrap(), "nested");
+
+		polling::remove_dir_all(&root).unwrap();
+		assert!(!long_path(&root).exists());
+	}
+
+	#[test]
+	fn read_plugin_metadata_encodings() {
+		let fixtures: [(&str, &[u8]); 3] = [
+			(
+				"UTF-8 BOM",
+				include_bytes!("../tests/fixtures/plugin_json/utf8_bom.json"),
+			),
+			(
+				"UTF-16LE",
+				include_bytes!("../tests/fixtures/plugin_json/utf16le.json"),
+			),
+			(
+				"UTF-16BE",
+				include_bytes!("../tests/fixtures/plugin_json/utf16be.json"),
+			),
+		];
+		for (encoding, bytes) in fixtures {
+			let temp = tempfile::tempdir().unwrap();
+			fs::write(temp.path().join("plugin.json"), bytes).unwrap();
+			let metadata =
+				read_plugin_metadata(temp.path()).unwrap_or_else(|e| panic!("{encoding}: {e}"));
+			assert_eq!(metadata.name, "Émoji Search", "{encoding}");
+			assert_eq!(metadata.version.as_deref(), Some("1.2.0"), "{encoding}");
+			assert_eq!(
+				metadata.execute_file_name.as_deref(),
+				Some("Community.PowerToys.Run.Plugin.EmojiSearch.dll"),
+				"{encoding}"
+			);
+		}
+	}
+
+	#[test]
+	fn decode_text_rejects_unpaired_surrogate() {
+		assert!(decode_text(&[0xff, 0xfe, 0x00, 0xd8]).is_err());
+		assert_eq!(decode_text(b"plain").unwrap(), "plain");
+	}
+
+	/// Bytes passing `verify_executable`, a PE header at 0x40.
+	fn executable() -> String {
+		let mut exe = "MZ".to_string();
+		exe.push_str(&"\0".repeat(0x3a));
+		exe.push_str("\x40\0\0\0PE\0\0");
+		exe
+	}
+
+	/// The asset of a ptr release, served by the source if it has `archive`.
+	fn ptr_release(archive: Option<Vec<u8>>) -> (FakeSource, Assets) {
+		let name = "ptr-x64.zip".to_string();
+		let source = FakeSource {
+			tag: "v1.0.0".to_string(),
+			assets: archive.map(|a| (name.clone(), a)).into_iter().collect(),
+		};
+		let asset = Assets {
+			browser_download_url: name.clone(),
+			name,
+			size: 0,
+		};
+		(source, asset)
+	}
+
+	/// Replace ptr from a release of `archive` in a temp directory, which is checked to be empty
+	/// afterwards.
+	fn replace_self_with(
+		archive: Option<Vec<u8>>,
+		replace: impl FnOnce(&Path) -> Result<()>,
+	) -> Result<()> {
+		let temp = tempfile::tempdir().unwrap();
+		let (source, asset) = ptr_release(archive);
+		let result = replace_self(&source, &asset, temp.path(), replace);
+		let left: Vec<_> = fs::read_dir(temp.path())
+			.unwrap()
+			.flatten()
+			.map(|e| e.path())
+			.collect();
+		assert!(left.is_empty(), "left behind: {left:?}");
+		result
+	}
+
+	#[test]
+	fn replace_self_cleans_up_after_success() {
+		let mut replaced = None;
+		replace_self_with(Some(zip(&[("ptr.exe", &executable())])), |exe| {
+			replaced = Some(fs::read_to_string(exe)?);
+			Ok(())
+		})
+		.unwrap();
+		assert_eq!(replaced, Some(executable()));
+	}
+
+	#[test]
+	fn replace_self_cleans_up_after_failed_replace() {
+		let archive = zip(&[("ptr.exe", &executable())]);
+		let result = replace_self_with(Some(archive), |_| bail!("Access is denied"));
+		assert_eq!(result.unwrap_err().to_string(), "Access is denied");
+	}
+
+	#[test]
+	fn replace_self_cleans_up_after_failed_download() {
+		assert!(replace_self_with(None, |_| panic!("replaced")).is_err());
+	}
+
+	#[test]
+	fn replace_self_cleans_up_after_invalid_archive() {
+		let replace = |_: &Path| panic!("replaced");
+		assert!(replace_self_with(Some(b"not a zip".to_vec()), replace).is_err());
+		assert!(replace_self_with(Some(zip(&[("README.md", "")])), replace).is_err());
+		let error = replace_self_with(Some(zip(&[("ptr.exe", "MZ")])), replace).unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"Downloaded ptr.exe is not a valid executable"
+		);
+	}
+
+	#[test]
+	fn replace_self_refuses_held_lock() {
+		let temp = tempfile::tempdir().unwrap();
+		let lock = temp.path().join(SELF_UPDATE_LOCK);
+		fs::write(&lock, "").unwrap();
+		let (source, asset) = ptr_release(None);
+		let error = replace_self(&source, &asset, temp.path(), |_| panic!("replaced"));
+		assert_eq!(
+			error.unwrap_err().to_string(),
+			"Another self update is running, try again later"
+		);
+		// the lock of the other update is kept
+		assert!(lock.exists());
+	}
+
+	/// A plugin named Foo with the .NET files in `fixtures`, named after its DLL.
+	fn dotnet_plugin(fixtures: &[(&str, &str)]) -> tempfile::TempDir {
+		let json =
+			r#"{"Name": "Foo", "ExecuteFileName": "Community.PowerToys.Run.Plugin.Foo.dll"}"#;
+		let dir = plugin_files(&[("plugin.json", json)]);
+		for (ext, content) in fixtures {
+			let name = format!("Community.PowerToys.Run.Plugin.Foo.{ext}");
+			fs::write(dir.path().join(name), content).unwrap();
+		}
+		dir
+	}
+
+	const RUNTIMECONFIG: &str = include_str!("../tests/fixtures/dotnet/plugin.runtimeconfig.json");
+	const DEPS: &str = include_str!("../tests/fixtures/dotnet/plugin.deps.json");
+
+	#[test]
+	fn plugin_dotnet_version_fixtures() {
+		let dir = dotnet_plugin(&[("runtimeconfig.json", RUNTIMECONFIG)]);
+		assert_eq!(plugin_dotnet_version(dir.path()), Some(9));
+		let dir = dotnet_plugin(&[("deps.json", DEPS)]);
+		assert_eq!(plugin_dotnet_version(dir.path()), Some(8));
+		// runtimeconfig.json is read first
+		let dir = dotnet_plugin(&[("runtimeconfig.json", RUNTIMECONFIG), ("deps.json", DEPS)]);
+		assert_eq!(plugin_dotnet_version(dir.path()), Some(9));
+		let dir = dotnet_plugin(&[("runtimeconfig.json", "{}")]);
+		assert_eq!(plugin_dotnet_version(dir.path()), None);
+		let dir = dotnet_plugin(&[]);
+		assert_eq!(plugin_dotnet_version(dir.path()), None);
+	}
+
+	#[test]
+	fn powertoys_dotnet_version_fixture() {
+		let dir = plugin_files(&[
+			(
+				"PowerToys.PowerLauncher.runtimeconfig.json",
+				include_str!("../tests/fixtures/dotnet/PowerToys.PowerLauncher.runtimeconfig.json"),
+			),
+			("PowerToys.exe", ""),
+		]);
+		let pt_path = dir.path().join("PowerToys.exe");
+		assert_eq!(powertoys_dotnet_version(&pt_path), Some(8));
+		let empty = plugin_files(&[("PowerToys.exe", "")]);
+		assert_eq!(
+			powertoys_dotnet_version(&empty.path().join("PowerToys.exe")),
+			None
+		);
+	}
+
+	#[test]
+	fn dotnet_major_formats() {
+		for (version, major) in [
+			("9.0.1", Some(9)),
+			("net8.0-windows10.0.22621.0", Some(8)),
+			("net10.0", Some(10)),
+			(".NETCoreApp,Version=v9.0/win-x64", Some(9)),
+			("netstandard2.0", None),
+		] {
+			assert_eq!(dotnet_major(version), major, "{version}");
+		}
+	}
+
+	#[test]
+	fn expand_pattern_for_both_arches() {
+		let pattern = r"^{name}-{version}-{arch}\.zip$";
+		assert_eq!(
+			expand_pattern(pattern, &Arch::X64, "v1.2.0", Some("Foo")),
+			r"^Foo-v?1\.2\.0-(?:x64|x86_64)\.zip$"
+		);
+		assert_eq!(
+			expand_pattern(pattern, &Arch::ARM64, "1.2.0", Some("Foo")),
+			r"^Foo-v?1\.2\.0-(?:arm64|aarch64)\.zip$"
+		);
+		// escaped variables, quantifiers and unknown names are kept
+		assert_eq!(
+			expand_pattern(r"\{arch}-[0-9]{2}-{name}", &Arch::X64, "v1", None),
+			r"\{arch}-[0-9]{2}-{name}"
+		);
+	}
+
+	#[test]
+	fn pattern_selects_asset_for_both_arches() {
+		let assets = assets(&[
+			"Foo-1.2.0-x64.zip",
+			"Foo-1.2.0-arm64.zip",
+			"Foo-Lite-1.2.0-x86_64.zip",
+			"Foo-Lite-1.2.0-aarch64.zip",
+			"Foo-1.2.0-x64.zip.sha256",
+		]);
+		let select = |arch: &Arch, pattern: Option<&str>| {
+			let pattern = pattern
+				.map(|p| compile_pattern(&expand_pattern(p, arch, "v1.2.0", Some("Foo"))).unwrap());
+			match_asset(&assets, arch, pattern.as_ref()).map(|a| a.name.as_str())
+		};
+		let lite = Some(r"^{name}-Lite-{version}-{arch}\.zip$");
+		for (arch, default, lite_asset) in [
+			(Arch::X64, "Foo-1.2.0-x64.zip", "Foo-Lite-1.2.0-x86_64.zip"),
+			(
+				Arch::ARM64,
+				"Foo-1.2.0-arm64.zip",
+				"Foo-Lite-1.2.0-aarch64.zip",
+			),
+		] {
+			assert_eq!(select(&arch, None), Some(default), "{arch}");
+			assert_eq!(select(&arch, lite), Some(lite_asset), "{arch}");
+		}
+		assert_eq!(select(&Arch::X64, Some("^Bar-{arch}")), None);
+	}
+
+	#[test]
+	fn needs_elevation_branches() {
+		// an elevated ptr never needs to elevate, and doesn't look at the processes
+		let untouched = std::iter::from_fn(|| panic!("queried"));
+		assert!(!needs_elevation(true, untouched));
+		assert!(!needs_elevation(false, std::iter::empty()));
+		assert!(!needs_elevation(
+			false,
+			[Some(false), Some(false)].into_iter()
+		));
+		assert!(needs_elevation(
+			false,
+			[Some(false), Some(true)].into_iter()
+		));
+		// a token that can't be queried belongs to an elevated process
+		assert!(needs_elevation(false, [None].into_iter()));
+		// the first elevated process decides
+		let mut queried = 0;
+		let targets = [Some(true), Some(false)]
+			.into_iter()
+			.inspect(|_| queried += 1);
+		assert!(needs_elevation(false, targets));
+		assert_eq!(queried, 1);
+	}
+
+	#[cfg(windows)]
+	#[test]
+	fn quote_arg_keeps_path_whole() {
+		for (path, quoted) in [
+			(r"C:\Users\me\version.toml", r#""C:\Users\me\version.toml""#),
+			(
+				r"C:\Users\John Doe\My Plugins\version.toml",
+				r#""C:\Users\John Doe\My Plugins\version.toml""#,
+			),
+			(r"C:\Tools & More\a&b.toml", r#""C:\Tools & More\a&b.toml""#),
+			(r"C:\My Plugins\", r#""C:\My Plugins\\""#),
+		] {
+			assert_eq!(quote_arg(Path::new(path)), quoted, "{path}");
+		}
+	}
+
+	#[test]
+	fn cmd_quoted_rejects_variables() {
+		assert_eq!(
+			cmd_quoted(Path::new("Plugins/Foo & Bar")).unwrap(),
+			"\"Plugins/Foo & Bar\""
+		);
+		for path in ["Plugins/A%TEMP%B", "Plugins/100%", "Plugins/\"Foo"] {
+			assert!(cmd_quoted(Path::new(path)).is_err(), "{path}");
+		}
+	}
+
+	/// A minimal PE file of `machine`, with a CLR header holding `clr_flags` if given, in a
+	/// section at `address` of `size` bytes stored at file offset `raw`.
+	fn pe_file(
+		machine: u16,
+		clr_flags: Option<u32>,
+		(address, size, raw): (u32, u32, u32),
+	) -> Vec<u8> {
+		let mut bytes = vec![0; 0x300];
+		let put = |bytes: &mut Vec<u8>, at: usize, value: &[u8]| {
+			bytes[at..at + value.len()].copy_from_slice(value)
+		};
+		put(&mut bytes, 0, b"MZ");
+		put(&mut bytes, 0x3c, &0x40u32.to_le_bytes());
+		put(&mut bytes, 0x40, b"PE\0\0");
+		put(&mut bytes, 0x44, &machine.to_le_bytes());
+		put(&mut bytes, 0x46, &1u16.to_le_bytes());
+		// PE32 optional header with 16 data directories
+		put(&mut bytes, 0x54, &224u16.to_le_bytes());
+		let optional = 0x58;
+		put(&mut bytes, optional, &0x10bu16.to_le_bytes());
+		let section = optional + 224;
+		put(&mut bytes, section, b".text\0\0\0");
+		put(&mut bytes, section + 8, &size.to_le_bytes());
+		put(&mut bytes, section + 12, &address.to_le_bytes());
+		put(&mut bytes, section + 20, &raw.to_le_bytes());
+		if let Some(flags) = clr_flags {
+			let clr_rva = address.wrapping_add(8);
+			put(&mut bytes, optional + 96 + 14 * 8, &clr_rva.to_le_bytes());
+			put(&mut bytes, optional + 96 + 14 * 8 + 4, &72u32.to_le_bytes());
+			put(&mut bytes, 0x208 + 16, &flags.to_le_bytes());
+		}
+		bytes
+	}
+
+	fn pe_arch_of(bytes: &[u8]) -> Result<PeArch> {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("Foo.dll");
+		fs::write(&path, bytes).unwrap();
+		pe_arch(&path)
+	}
+
+	#[test]
+	fn pe_arch_machines() {
+		const SECTION: (u32, u32, u32) = (0x2000, 0x1000, 0x200);
+		let cases = [
+			(pe_file(0x8664, None, SECTION), PeArch::X64),
+			(pe_file(0xaa64, None, SECTION), PeArch::ARM64),
+			(pe_file(0x14c, None, SECTION), PeArch::X86),
+			// ILONLY
+			(pe_file(0x14c, Some(0x1), SECTION), PeArch::AnyCpu),
+			// ILONLY | 32BITREQUIRED
+			(pe_file(0x14c, Some(0x3), SECTION), PeArch::X86),
+			(pe_file(0x1c4, None, SECTION), PeArch::Other(0x1c4)),
+		];
+		for (bytes, arch) in cases {
+			assert_eq!(pe_arch_of(&bytes).unwrap(), arch);
+		}
+	}
+
+	#[test]
+	fn pe_arch_malformed() {
+		let valid = pe_file(0x14c, Some(0x1), (0x2000, 0x1000, 0x200));
+		let cases = [
+			b"not a PE file".to_vec(),
+			// cut in the PE signature, the optional header and the section table
+			valid[..0x42].to_vec(),
+			valid[..0x50].to_vec(),
+			valid[..0x130].to_vec(),
+			// the section end doesn't fit in 32 bits
+			pe_file(0x14c, Some(0x1), (0xffff_f000, 0x2000, 0x200)),
+			// the file offset of the CLR header doesn't fit in 32 bits
+			pe_file(0x14c, Some(0x1), (0x2000, 0x1000, 0xffff_fffc)),
+		];
+		for bytes in cases {
+			assert!(pe_arch_of(&bytes).is_err());
+		}
+		// the PE header offset points past the end
+		let mut far = valid.clone();
+		far[0x3c..0x40].copy_from_slice(&u32::MAX.to_le_bytes());
+		assert!(pe_arch_of(&far).is_err());
+	}
+
+	/// Paths of the files under `dir`, relative to it.
+	fn files_under(dir: &Path) -> Vec<PathBuf> {
+		let mut files = vec![];
+		let mut dirs = vec![dir.to_path_buf()];
+		while let Some(d) = dirs.pop() {
+			for entry in fs::read_dir(d).unwrap() {
+				let path = entry.unwrap().path();
+				if path.is_dir() {
+					dirs.push(path);
+				} else {
+					files.push(path.strip_prefix(dir).unwrap().to_path_buf());
+				}
+			}
+		}
+		files.sort();
+		files
+	}
+
+	#[test]
+	fn hostile_asset_names() {
+		let archive = zip(&[("Foo/plugin.json", PLUGIN_JSON), ("Foo/Foo.dll", "")]);
+		let temp = tempfile::tempdir().unwrap();
+		let (cache, plugins) = (temp.path().join("cache"), temp.path().join("a/b/plugins"));
+		fs::create_dir_all(plugins.join("Existing")).unwrap();
+		fs::write(plugins.join("Existing/keep.txt"), "kept").unwrap();
+		let filter = AssetFilter {
+			pattern: Some(".*"),
+			..AssetFilter::default()
+		};
+		let cases = [
+			("releases/Foo-x64.zip", true),
+			(r"..\..\Foo-x64.zip", true),
+			("../../Foo-x64.zip", true),
+			("Existing", true),
+			("..", false),
+			("", false),
+			("  ", false),
+		];
+		for (i, (name, valid)) in cases.into_iter().enumerate() {
+			let source = FakeSource {
+				tag: "v1.0.0".to_string(),
+				assets: vec![(name.to_string(), archive.clone())],
+			};
+			let root = plugins.join(format!("Foo{i}"));
+			let result = download_release(
+				&source,
+				&root,
+				"owner/Foo",
+				None,
+				&Arch::X64,
+				None,
+				&filter,
+				Some(&cache),
+				&ExtractLimits::default(),
+			);
+			if valid {
+				result.unwrap();
+				assert!(root.join("Foo.dll").is_file(), "{name:?}");
+			} else {
+				let e = result.err().unwrap();
+				assert!(
+					e.to_string().contains("Invalid asset name"),
+					"{name:?}: {e}"
+				);
+			}
+		}
+
+		// loose files are written by their own path
+		let source = FakeSource {
+			tag: "v1.0.0".to_string(),
+			assets: vec![
+				("plugin.json".to_string(), PLUGIN_JSON.as_bytes().to_vec()),
+				(r"..\..\Foo.dll".to_string(), vec![]),
+			],
+		};
+		let root = plugins.join("Loose");
+		download_release(
+			&source,
+			&root,
+			"owner/Foo",
+			None,
+			&Arch::X64,
+			None,
+			&AssetFilter::default(),
+			None,
+			&ExtractLimits::default(),
+		)
+		.unwrap();
+		assert!(root.join("Foo.dll").is_file());
+
+		// nothing outside the plugin directories and the cache
+		for file in files_under(temp.path()) {
+			assert!(
+				file.starts_with("cache") || file.starts_with("a/b/plugins"),
+				"{}",
+				file.display()
+			);
+		}
+		assert_eq!(
+			files_under(&plugins.join("Existing")),
+			[PathBuf::from("keep.txt")]
+		);
+		assert_eq!(
+			fs::read_to_string(plugins.join("Existing/keep.txt")).unwrap(),
+			"kept"
+		);
+	}
+
+	#[test]
+	fn probe_hosts_of_repos() {
+		let unreachable = || github("http://127.0.0.1:1".to_string());
+		// github.com isn't probed without a plugin there
+		unreachable().probe([]).unwrap();
+		assert!(unreachable().probe(["owner/Foo"]).is_err());
+		let err = unreachable().probe(["127.0.0.1:1/team/Foo"]).err().unwrap();
+		assert!(
+			err.to_string().contains("https://127.0.0.1:1/api/v3"),
+			"{err}"
+		);
+		// one request per host
+		let served = github(serve(vec![json("200 OK", "{}")]));
+		served.probe(["owner/Foo", "owner/Bar"]).unwrap();
+	}
+
+	#[test]
+	fn unix_days_of_timestamps() {
+		assert_eq!(unix_days("1970-01-01T00:00:00Z"), Some(0));
+		assert_eq!(unix_days("1969-12-31T23:59:59Z"), Some(-1));
+		// leap days, and the dates around them
+		assert_eq!(unix_days("2024-02-29T12:00:00Z"), Some(19782));
+		assert_eq!(unix_days("2024-03-01T00:00:00Z"), Some(19783));
+		assert_eq!(unix_days("2000-02-29T00:00:00Z"), Some(11016));
+		assert_eq!(unix_days("1900-03-01T00:00:00Z"), Some(-25508));
+		// before March, counted in the previous year by the algorithm
+		assert_eq!(unix_days("2023-01-15T08:30:00Z"), Some(19372));
+		for malformed in [
+			"",
+			"yesterday",
+			"2024-02",
+			"2024/02/29T00:00:00Z",
+			"2024-13-01T00:00:00Z",
+			"2024-02-00T00:00:00Z",
+		] {
+			assert_eq!(unix_days(malformed), None, "{malformed:?}");
+		}
+	}
+
+	#[test]
+	fn released_ago_counts_days() {
+		let today = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap()
+			.as_secs()
+			/ (24 * 60 * 60);
+		assert_eq!(
+			released_ago("1970-01-01T00:00:00Z").unwrap(),
+			format!("released {today} days ago")
+		);
+		assert_eq!(
+			released_ago("2999-01-01T00:00:00Z").unwrap(),
+			"released today"
+		);
+		assert_eq!(released_ago("not a date"), None);
+	}
+
+	#[test]
+	fn zip_time_matches_unix_days() {
+		let time = zip::DateTime::from_date_and_time(2024, 2, 29, 6, 0, 0).unwrap();
+		let secs = zip_time(time)
+			.unwrap()
+			.duration_since(UNIX_EPOCH)
+			.unwrap()
+			.as_secs();
+		assert_eq!(secs, 19782 * 86400 + 6 * 3600);
+	}
 }
-// endregion